@@ -1,8 +1,8 @@
-use anyhow::{Context, Result};
 use reqwest::Client;
 use tracing::{debug, warn};
 
 use super::API_BASE_URL;
+use super::error::ApiError;
 use crate::models::TokenResponse;
 
 const API_LOGIN_PATH: &str = "/api/Profil/zaloguj";
@@ -10,7 +10,11 @@ const LOGIN_ROLE_ID: &str = "2";
 const LOGIN_GRANT_TYPE: &str = "password";
 
 /// Performs the WPS login and returns the access token response.
-pub async fn login(client: &Client, username: &str, password: &str) -> Result<TokenResponse> {
+pub async fn login(
+  client: &Client,
+  username: &str,
+  password: &str,
+) -> Result<TokenResponse, ApiError> {
   let url = format!("{API_BASE_URL}{API_LOGIN_PATH}");
 
   debug!("POST {API_LOGIN_PATH}");
@@ -24,18 +28,17 @@ pub async fn login(client: &Client, username: &str, password: &str) -> Result<To
     ])
     .send()
     .await
-    .context("login request failed")?;
+    .map_err(|error| ApiError::from_reqwest("login", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
     warn!(?status, "login failed");
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("login failed: {status} body={text}");
+    return Err(ApiError::from_login_response(resp).await);
   }
 
   debug!(?status, "login ok");
   resp
     .json::<TokenResponse>()
     .await
-    .context("invalid token json")
+    .map_err(|error| ApiError::from_reqwest("login", &error))
 }
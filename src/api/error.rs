@@ -0,0 +1,220 @@
+use std::fmt;
+use std::time::Duration;
+
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Response, StatusCode};
+use serde::Deserialize;
+
+/// Used when WPS is down but did not say for how long
+const DEFAULT_RETRY_AFTER: Duration = Duration::from_secs(60);
+
+/// Why a WPS call failed.
+///
+/// Upstream bodies are deliberately not kept: they can carry personal data,
+/// and these errors end up in logs.
+#[derive(Clone, Debug)]
+pub enum ApiError {
+  /// WPS rejected the username or password
+  InvalidCredentials,
+  /// The credentials are known to WPS, but the account may not log in
+  AccountLocked,
+  /// WPS could not be reached or answered with a server-side failure
+  Unavailable {
+    operation: &'static str,
+    reason: String,
+    retry_after: Option<Duration>,
+  },
+  /// WPS did not answer within the client timeout
+  Timeout { operation: &'static str },
+  /// WPS answered successfully, but not with the JSON shape we expect
+  SchemaMismatch {
+    operation: &'static str,
+    detail: String,
+  },
+  /// WPS refused the request for a reason the caller cannot fix
+  UnexpectedStatus {
+    operation: &'static str,
+    status: StatusCode,
+  },
+}
+
+/// OAuth-style error payload returned by the login endpoint
+#[derive(Deserialize)]
+struct LoginErrorBody {
+  #[serde(default)]
+  error: String,
+  #[serde(default)]
+  error_description: String,
+}
+
+impl ApiError {
+  /// Classifies a transport or decoding failure reported by `reqwest`
+  pub(super) fn from_reqwest(operation: &'static str, error: &reqwest::Error) -> Self {
+    if error.is_timeout() {
+      Self::Timeout { operation }
+    } else if error.is_decode() {
+      Self::SchemaMismatch {
+        operation,
+        detail: error.to_string(),
+      }
+    } else {
+      Self::Unavailable {
+        operation,
+        reason: error.to_string(),
+        retry_after: None,
+      }
+    }
+  }
+
+  /// Classifies a non-success status from any data endpoint
+  pub(super) fn from_response(operation: &'static str, response: &Response) -> Self {
+    let retry_after = response
+      .headers()
+      .get(RETRY_AFTER)
+      .and_then(|value| value.to_str().ok())
+      .and_then(parse_retry_after);
+    from_status(operation, response.status(), retry_after)
+  }
+
+  /// Classifies a failed login, reading the body only to tell a wrong
+  /// password apart from a locked account
+  pub(super) async fn from_login_response(response: Response) -> Self {
+    let status = response.status();
+    if matches!(status, StatusCode::BAD_REQUEST | StatusCode::UNAUTHORIZED) {
+      let body = response.text().await.unwrap_or_default();
+      return login_rejection(&body);
+    }
+    if status == StatusCode::FORBIDDEN {
+      return Self::AccountLocked;
+    }
+
+    Self::from_response("login", &response)
+  }
+
+  /// How long clients should wait before retrying, when waiting can help
+  #[must_use]
+  pub fn retry_after(&self) -> Option<Duration> {
+    match self {
+      Self::Unavailable { retry_after, .. } => Some(retry_after.unwrap_or(DEFAULT_RETRY_AFTER)),
+      Self::Timeout { .. } => Some(DEFAULT_RETRY_AFTER),
+      _ => None,
+    }
+  }
+}
+
+fn from_status(
+  operation: &'static str,
+  status: StatusCode,
+  retry_after: Option<Duration>,
+) -> ApiError {
+  if status == StatusCode::GATEWAY_TIMEOUT || status == StatusCode::REQUEST_TIMEOUT {
+    return ApiError::Timeout { operation };
+  }
+  if status.is_server_error() || status == StatusCode::TOO_MANY_REQUESTS {
+    return ApiError::Unavailable {
+      operation,
+      reason: status.to_string(),
+      retry_after,
+    };
+  }
+
+  ApiError::UnexpectedStatus { operation, status }
+}
+
+/// Only the delta-seconds form; an HTTP-date falls back to the default
+fn parse_retry_after(value: &str) -> Option<Duration> {
+  value.trim().parse().ok().map(Duration::from_secs)
+}
+
+fn login_rejection(body: &str) -> ApiError {
+  let Ok(payload) = serde_json::from_str::<LoginErrorBody>(body) else {
+    return ApiError::InvalidCredentials;
+  };
+
+  let message = format!("{} {}", payload.error, payload.error_description).to_lowercase();
+  let locked = ["zablokowan", "locked", "blocked", "disabled"]
+    .iter()
+    .any(|marker| message.contains(marker));
+
+  if locked {
+    ApiError::AccountLocked
+  } else {
+    ApiError::InvalidCredentials
+  }
+}
+
+impl fmt::Display for ApiError {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::InvalidCredentials => write!(formatter, "WPS rejected the username or password"),
+      Self::AccountLocked => write!(formatter, "WPS account is locked"),
+      Self::Unavailable {
+        operation, reason, ..
+      } => write!(formatter, "{operation} failed: WPS unavailable ({reason})"),
+      Self::Timeout { operation } => write!(formatter, "{operation} failed: WPS timed out"),
+      Self::SchemaMismatch { operation, detail } => {
+        write!(
+          formatter,
+          "{operation} failed: unexpected response ({detail})"
+        )
+      }
+      Self::UnexpectedStatus { operation, status } => {
+        write!(formatter, "{operation} failed: WPS answered {status}")
+      }
+    }
+  }
+}
+
+impl std::error::Error for ApiError {}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn login_body_distinguishes_locked_accounts() {
+    assert!(matches!(
+      login_rejection(
+        r#"{"error":"invalid_grant","error_description":"Nieprawidłowy login lub hasło"}"#
+      ),
+      ApiError::InvalidCredentials
+    ));
+    assert!(matches!(
+      login_rejection(
+        r#"{"error":"invalid_grant","error_description":"Konto zostało zablokowane"}"#
+      ),
+      ApiError::AccountLocked
+    ));
+    assert!(matches!(
+      login_rejection("<html>not json</html>"),
+      ApiError::InvalidCredentials
+    ));
+  }
+
+  #[test]
+  fn server_errors_are_retryable_and_client_errors_are_not() {
+    let unavailable = from_status("plan", StatusCode::SERVICE_UNAVAILABLE, None);
+    assert!(matches!(unavailable, ApiError::Unavailable { .. }));
+    assert_eq!(unavailable.retry_after(), Some(DEFAULT_RETRY_AFTER));
+
+    let throttled = from_status(
+      "plan",
+      StatusCode::TOO_MANY_REQUESTS,
+      Some(Duration::from_secs(5)),
+    );
+    assert_eq!(throttled.retry_after(), Some(Duration::from_secs(5)));
+
+    let timeout = from_status("plan", StatusCode::GATEWAY_TIMEOUT, None);
+    assert!(matches!(timeout, ApiError::Timeout { .. }));
+
+    let not_found = from_status("plan", StatusCode::NOT_FOUND, None);
+    assert!(matches!(not_found, ApiError::UnexpectedStatus { .. }));
+    assert_eq!(not_found.retry_after(), None);
+  }
+
+  #[test]
+  fn retry_after_accepts_seconds_only() {
+    assert_eq!(parse_retry_after(" 120 "), Some(Duration::from_secs(120)));
+    assert_eq!(parse_retry_after("Wed, 21 Oct 2026 07:28:00 GMT"), None);
+  }
+}
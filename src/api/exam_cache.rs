@@ -0,0 +1,94 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use moka::future::Cache;
+use reqwest::Client;
+use tracing::debug;
+
+use super::error::ApiError;
+use super::exams::{get_current_academic_year, get_exam_schedule};
+use crate::cache::{CacheSnapshot, EvictionCounters};
+use crate::models::{ExamScheduleItem, TermQuery};
+
+/// Exam rooms and times get corrected during the session, so listings are
+/// refreshed hourly; the academic year only rolls over once a year.
+const EXAM_SCHEDULE_TTL_SECONDS: u64 = 3_600;
+const ACADEMIC_YEAR_TTL_SECONDS: u64 = 43_200;
+/// A handful of terms are ever in use, each listing being one full download
+const EXAM_SCHEDULE_MAX_TERMS: u64 = 16;
+
+/// Public exam data that is identical for every student, shared process-wide.
+///
+/// Only the raw `GETEgazminFiltr` listings and the current academic year are
+/// kept here; filtering by a student's protocol subjects and section still
+/// happens per request.
+#[derive(Clone)]
+pub(super) struct ExamScheduleCache {
+  schedules: Cache<TermQuery, Arc<Vec<ExamScheduleItem>>>,
+  academic_year: Cache<(), i32>,
+  evictions: Arc<EvictionCounters>,
+}
+
+impl ExamScheduleCache {
+  pub(super) fn new() -> Self {
+    let evictions = Arc::new(EvictionCounters::default());
+    Self {
+      schedules: Cache::builder()
+        .max_capacity(EXAM_SCHEDULE_MAX_TERMS)
+        .time_to_live(Duration::from_secs(EXAM_SCHEDULE_TTL_SECONDS))
+        .eviction_listener(EvictionCounters::listener(&evictions))
+        .build(),
+      academic_year: Cache::builder()
+        .max_capacity(1)
+        .time_to_live(Duration::from_secs(ACADEMIC_YEAR_TTL_SECONDS))
+        .build(),
+      evictions,
+    }
+  }
+
+  pub(super) fn snapshot(&self) -> CacheSnapshot {
+    let entries = self.schedules.entry_count();
+    CacheSnapshot::new(
+      "exam_schedule",
+      entries,
+      entries,
+      EXAM_SCHEDULE_MAX_TERMS,
+      &self.evictions,
+    )
+  }
+
+  /// Any student's token can read the public data; concurrent misses share one fetch
+  pub(super) async fn academic_year(
+    &self,
+    client: &Client,
+    access_token: &str,
+  ) -> Result<i32, ApiError> {
+    self
+      .academic_year
+      .try_get_with((), get_current_academic_year(client, access_token))
+      .await
+      .map_err(Arc::unwrap_or_clone)
+  }
+
+  pub(super) async fn schedule(
+    &self,
+    client: &Client,
+    access_token: &str,
+    term: TermQuery,
+  ) -> Result<Arc<Vec<ExamScheduleItem>>, ApiError> {
+    self
+      .schedules
+      .try_get_with(term, async {
+        debug!(
+          academic_year = term.academic_year,
+          semester_id = term.semester_id,
+          "exam schedule cache miss"
+        );
+        get_exam_schedule(client, access_token, term)
+          .await
+          .map(Arc::new)
+      })
+      .await
+      .map_err(Arc::unwrap_or_clone)
+  }
+}
@@ -1,15 +1,21 @@
-use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
 
-use anyhow::{Context, Result};
-use chrono::{Duration, NaiveDate, NaiveTime};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime, Utc};
+use futures_util::{StreamExt, stream};
 use reqwest::Client;
 use tracing::{debug, warn};
 
-use super::API_BASE_URL;
+use super::error::ApiError;
+use super::exam_cache::ExamScheduleCache;
+use super::{API_BASE_URL, UPSTREAM_CONCURRENCY};
+use crate::config::ExamTimeDefaults;
 use crate::models::{
-  CurrentAcademicYearResponse, ExamEvent, ExamProtocolIntermediateItem, ExamProtocolItem,
-  ExamRecipient, ExamScheduleItem, TermQuery,
+  CurrentAcademicYearResponse, ExamEvent, ExamIndex, ExamProtocolIntermediateItem,
+  ExamProtocolItem, ExamRecipient, ExamScheduleItem, TermQuery, first_url, meeting_url,
+  normalize_text,
 };
+use crate::timezone::SOURCE_ZONE;
 
 const API_EXAM_PROTOCOL_PATH: &str =
   "/api/ProtokolyEgzaminacyjne/GetProtokolEgzaminacyjnySzczegolowy";
@@ -19,83 +25,210 @@ const API_EXAM_FILTER_PATH: &str = "/api/Egzaminy/GETEgazminFiltr";
 const API_CURRENT_ACADEMIC_YEAR_PATH: &str = "/api/Slowniki/GETPobierzAktualnyRokAkademicki";
 /// Settlement label that marks a subject as an exam (vs a plain pass).
 const EXAM_SETTLEMENT_NAME: &str = "egzamin";
+const ACADEMIC_YEAR_START_MONTH: u32 = 10;
+const SUMMER_SEMESTER_START_MONTH: u32 = 3;
+/// How long after a semester's nominal end its resit exams may still be held
+const RESIT_GRACE_DAYS: i64 = 31;
+
+/// Exams found for a student, and whether every upstream call behind them
+/// succeeded
+#[derive(Debug, Default)]
+pub struct ExamFetch {
+  pub events: Vec<ExamEvent>,
+  /// False when a protocol, settlement or schedule lookup failed, so exams
+  /// missing from `events` may still exist
+  pub complete: bool,
+}
 
+/// Runs the exam pipeline in stages, each fanned out over at most
+/// `UPSTREAM_CONCURRENCY` requests: protocols per indeks and term, then the
+/// settlement lookups they leave open, then the public schedule of every term
+/// that has exam subjects.
+///
+/// A failed lookup is logged and skipped so the rest still shows up, but marks
+/// the fetch incomplete.
 pub async fn get_exams(
   client: &Client,
+  public_data: &ExamScheduleCache,
   access_token: &str,
-  index_id: i64,
-  section_name: Option<&str>,
+  indexes: &[ExamIndex],
   from: NaiveDate,
   to: NaiveDate,
-) -> Result<Vec<ExamEvent>> {
-  let academic_year = get_current_academic_year(client, access_token).await?;
-  let terms = build_terms_for_year(academic_year);
-
-  let mut subjects_by_term: BTreeMap<TermQuery, BTreeSet<String>> = BTreeMap::new();
-  for term in terms {
-    match get_exam_protocol(client, access_token, index_id, term).await {
-      Ok(items) => {
-        let subjects = resolve_exam_subjects_for_term(client, access_token, term, items).await;
-        if !subjects.is_empty() {
-          subjects_by_term.insert(term, subjects);
+  defaults: ExamTimeDefaults,
+) -> Result<ExamFetch, ApiError> {
+  if indexes.is_empty() {
+    return Ok(ExamFetch {
+      events: Vec::new(),
+      complete: true,
+    });
+  }
+
+  let current_academic_year = public_data.academic_year(client, access_token).await?;
+  // The university's today, whatever zone the server runs in
+  let today = Utc::now().with_timezone(&SOURCE_ZONE).date_naive();
+  let terms = terms_for_range(from, to, today, current_academic_year);
+  debug!(?terms, "exam terms for requested range");
+
+  // Owned `(index_id, term)` pairs: borrowing the indexes inside the stream
+  // closure trips the higher-ranked `Send` check on the handler futures
+  let lookups: Vec<(i64, TermQuery)> = indexes
+    .iter()
+    .flat_map(|index| terms.iter().map(|term| (index.index_id, *term)))
+    .collect();
+  let fetched: Vec<Option<(i64, TermQuery, Vec<ExamProtocolItem>)>> = stream::iter(lookups)
+    .map(|(index_id, term)| async move {
+      match get_exam_protocol(client, access_token, index_id, term).await {
+        Ok(items) => Some((index_id, term, items)),
+        Err(error) => {
+          warn!(
+            index_id,
+            academic_year = term.academic_year,
+            semester_id = term.semester_id,
+            error = %error,
+            "exam protocol fetch failed"
+          );
+          None
         }
       }
-      Err(error) => {
-        warn!(
-          academic_year = term.academic_year,
-          semester_id = term.semester_id,
-          error = %error,
-          "exam protocol fetch failed"
-        );
-      }
-    }
+    })
+    .buffered(UPSTREAM_CONCURRENCY)
+    .collect()
+    .await;
+  let mut complete = true;
+  let protocols: Vec<(&ExamIndex, TermQuery, Vec<ExamProtocolItem>)> =
+    successes(fetched, &mut complete)
+      .into_iter()
+      .filter_map(|(index_id, term, items)| {
+        let index = indexes.iter().find(|index| index.index_id == index_id)?;
+        Some((index, term, items))
+      })
+      .collect();
+
+  let settlements: HashMap<(i64, i64), bool> = successes(
+    resolve_settlements(
+      client,
+      access_token,
+      pending_exam_cards(protocols.iter().flat_map(|(_, _, items)| items)),
+    )
+    .await,
+    &mut complete,
+  )
+  .into_iter()
+  .collect();
+
+  let subjects: Vec<(&ExamIndex, TermQuery, BTreeSet<String>)> = protocols
+    .iter()
+    .map(|(index, term, items)| (*index, *term, exam_subjects(items, &settlements)))
+    .filter(|(_, _, subjects)| !subjects.is_empty())
+    .collect();
+
+  if subjects.is_empty() {
+    debug!("no exam protocol subjects found for requested range");
+    return Ok(ExamFetch {
+      events: Vec::new(),
+      complete,
+    });
   }
 
-  if subjects_by_term.is_empty() {
-    debug!(
-      index_id,
-      "no exam protocol subjects found for requested range"
-    );
-    return Ok(Vec::new());
-  }
-
-  let mut events = Vec::new();
-  let mut seen = HashSet::new();
-
-  for (term, subjects) in subjects_by_term {
-    match get_exam_schedule(client, access_token, term).await {
-      Ok(items) => {
-        for item in items {
-          let Some(normalized_subject) = normalize_subject(&item.exam_subject) else {
-            continue;
-          };
-          if !subjects.contains(&normalized_subject) {
-            continue;
-          }
-          if !recipient_section_matches(&item.recipients, section_name) {
-            continue;
-          }
-
-          let Some(event) = map_exam_event(item, from, to) else {
-            continue;
-          };
-
-          let key = format!(
-            "{}|{}|{}",
-            event.published_data_id, event.starts, normalized_subject
+  let needed_terms: BTreeSet<TermQuery> = subjects.iter().map(|(_, term, _)| *term).collect();
+  let fetched: Vec<Option<(TermQuery, Arc<Vec<ExamScheduleItem>>)>> = stream::iter(needed_terms)
+    .map(|term| async move {
+      match public_data.schedule(client, access_token, term).await {
+        Ok(items) => Some((term, items)),
+        Err(error) => {
+          warn!(
+            academic_year = term.academic_year,
+            semester_id = term.semester_id,
+            error = %error,
+            "exam schedule fetch failed"
           );
-          if seen.insert(key) {
-            events.push(event);
-          }
+          None
         }
       }
-      Err(error) => {
-        warn!(
-          academic_year = term.academic_year,
-          semester_id = term.semester_id,
-          error = %error,
-          "exam schedule fetch failed"
-        );
+    })
+    .buffered(UPSTREAM_CONCURRENCY)
+    .collect()
+    .await;
+  let schedules: HashMap<TermQuery, Arc<Vec<ExamScheduleItem>>> =
+    successes(fetched, &mut complete).into_iter().collect();
+
+  let matches = subjects.iter().filter_map(|(index, term, subjects)| {
+    let items = schedules.get(term)?;
+    Some((*index, subjects, items.as_slice()))
+  });
+  Ok(ExamFetch {
+    events: merge_exam_events(matches, indexes.len() > 1, from, to, defaults),
+    complete,
+  })
+}
+
+/// Results of the lookups that succeeded; a failed one, already logged as
+/// `None`, clears `complete`
+fn successes<T>(results: Vec<Option<T>>, complete: &mut bool) -> Vec<T> {
+  let total = results.len();
+  let succeeded: Vec<T> = results.into_iter().flatten().collect();
+  *complete &= succeeded.len() == total;
+  succeeded
+}
+
+/// Picks each indeks' exams out of the term listings and merges them.
+///
+/// An exam found for several indexes becomes one event listing every
+/// programme; programmes are only recorded when `label_programmes` is set.
+/// Exams are told apart by `published_data_id` alone, as their UID is, so a
+/// listing that repeats an id keeps its first row.
+fn merge_exam_events<'data>(
+  matches: impl IntoIterator<
+    Item = (
+      &'data ExamIndex,
+      &'data BTreeSet<String>,
+      &'data [ExamScheduleItem],
+    ),
+  >,
+  label_programmes: bool,
+  from: NaiveDate,
+  to: NaiveDate,
+  defaults: ExamTimeDefaults,
+) -> Vec<ExamEvent> {
+  let mut events: Vec<ExamEvent> = Vec::new();
+  let mut positions: HashMap<i64, usize> = HashMap::new();
+
+  for (index, subjects, items) in matches {
+    let programme = label_programmes.then(|| {
+      index
+        .programme
+        .clone()
+        .unwrap_or_else(|| index.index_id.to_string())
+    });
+
+    for item in items {
+      let Some(normalized_subject) = normalize_subject(&item.exam_subject) else {
+        continue;
+      };
+      if !subjects.contains(&normalized_subject) {
+        continue;
+      }
+      if !recipient_section_matches(&item.recipients, index.section_name.as_deref()) {
+        continue;
+      }
+
+      // Only matching items are copied out of the shared listing
+      let Some(mut event) = map_exam_event(item.clone(), from, to, defaults) else {
+        continue;
+      };
+
+      let key = event.published_data_id;
+      if let Some(&position) = positions.get(&key) {
+        let programmes = &mut events[position].programmes;
+        if let Some(programme) = &programme
+          && !programmes.contains(programme)
+        {
+          programmes.push(programme.clone());
+        }
+      } else {
+        event.programmes.extend(programme.clone());
+        positions.insert(key, events.len());
+        events.push(event);
       }
     }
   }
@@ -106,11 +239,14 @@ pub async fn get_exams(
       .cmp(&right.starts)
       .then_with(|| left.subject.cmp(&right.subject))
   });
-  Ok(events)
+  events
 }
 
 /// Reads current academic year used by WPS dictionary endpoints.
-async fn get_current_academic_year(client: &Client, access_token: &str) -> Result<i32> {
+pub(super) async fn get_current_academic_year(
+  client: &Client,
+  access_token: &str,
+) -> Result<i32, ApiError> {
   let url = format!("{API_BASE_URL}{API_CURRENT_ACADEMIC_YEAR_PATH}");
 
   debug!("GET {API_CURRENT_ACADEMIC_YEAR_PATH}");
@@ -119,18 +255,17 @@ async fn get_current_academic_year(client: &Client, access_token: &str) -> Resul
     .bearer_auth(access_token)
     .send()
     .await
-    .context("current academic year request failed")?;
+    .map_err(|error| ApiError::from_reqwest("current academic year", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("current academic year failed: {status} body={text}");
+    return Err(ApiError::from_response("current academic year", &resp));
   }
 
   let payload = resp
     .json::<CurrentAcademicYearResponse>()
     .await
-    .context("invalid current academic year json")?;
+    .map_err(|error| ApiError::from_reqwest("current academic year", &error))?;
 
   Ok(payload.academic_year)
 }
@@ -141,7 +276,7 @@ async fn get_exam_protocol(
   access_token: &str,
   index_id: i64,
   term: TermQuery,
-) -> Result<Vec<ExamProtocolItem>> {
+) -> Result<Vec<ExamProtocolItem>, ApiError> {
   let url = format!(
     "{API_BASE_URL}{API_EXAM_PROTOCOL_PATH}?IndeksID={index_id}&RokAkad={}&SemestrID={}",
     term.academic_year, term.semester_id
@@ -158,54 +293,47 @@ async fn get_exam_protocol(
     .bearer_auth(access_token)
     .send()
     .await
-    .context("exam protocol request failed")?;
+    .map_err(|error| ApiError::from_reqwest("exam protocol", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("exam protocol failed: {status} body={text}");
+    return Err(ApiError::from_response("exam protocol", &resp));
   }
 
   resp
     .json::<Vec<ExamProtocolItem>>()
     .await
-    .context("invalid exam protocol json")
+    .map_err(|error| ApiError::from_reqwest("exam protocol", &error))
 }
 
-/// Resolves subjects that should be treated as exams for a given term.
-async fn resolve_exam_subjects_for_term(
+/// Exam card positions whose settlement is missing from the detailed protocol.
+///
+/// We only keep subjects that are explicitly settled as an exam; a null
+/// settlement in "Szczegolowy" is resolved via the "Posredni" endpoint. Each
+/// pair is listed once even when it appears in several items or terms.
+fn pending_exam_cards<'item>(
+  items: impl IntoIterator<Item = &'item ExamProtocolItem>,
+) -> BTreeSet<(i64, i64)> {
+  items
+    .into_iter()
+    .filter(|item| !is_exam_settlement(item.settlement_method_name.as_deref()))
+    .map(|item| (item.exam_card_id, item.exam_card_position_id))
+    .filter(|&(card_id, position_id)| card_id > 0 && position_id > 0)
+    .collect()
+}
+
+/// Looks up whether each exam card position is settled as an exam.
+///
+/// A failed lookup is `None` and its card counts as "not an exam", so one
+/// broken card cannot hide the rest of the calendar.
+async fn resolve_settlements(
   client: &Client,
   access_token: &str,
-  term: TermQuery,
-  items: Vec<ExamProtocolItem>,
-) -> BTreeSet<String> {
-  // We only keep subjects that are explicitly settled as an exam.
-  // If "Szczegolowy" has null settlement, we resolve it via the "Posredni" endpoint.
-  let mut subjects = BTreeSet::new();
-  let mut settlement_cache: HashMap<(i64, i64), bool> = HashMap::new();
-
-  for item in items {
-    let Some(normalized_subject) = normalize_subject(&item.subject) else {
-      continue;
-    };
-
-    if is_exam_settlement(item.settlement_method_name.as_deref()) {
-      subjects.insert(normalized_subject);
-      continue;
-    }
-
-    let exam_card_id = item.exam_card_id;
-    let exam_card_position_id = item.exam_card_position_id;
-    if exam_card_id <= 0 || exam_card_position_id <= 0 {
-      continue;
-    }
-
-    let cache_key = (exam_card_id, exam_card_position_id);
-    // Avoid duplicate network calls when the same exam card pair appears multiple times.
-    let is_exam = if let Some(value) = settlement_cache.get(&cache_key) {
-      *value
-    } else {
-      let value = match get_exam_protocol_intermediate(
+  cards: BTreeSet<(i64, i64)>,
+) -> Vec<Option<((i64, i64), bool)>> {
+  stream::iter(cards)
+    .map(|(exam_card_id, exam_card_position_id)| async move {
+      match get_exam_protocol_intermediate(
         client,
         access_token,
         exam_card_id,
@@ -213,31 +341,45 @@ async fn resolve_exam_subjects_for_term(
       )
       .await
       {
-        Ok(entries) => entries
-          .iter()
-          .any(|entry| is_exam_settlement(entry.settlement_method_name.as_deref())),
+        Ok(entries) => Some((
+          (exam_card_id, exam_card_position_id),
+          entries
+            .iter()
+            .any(|entry| is_exam_settlement(entry.settlement_method_name.as_deref())),
+        )),
         Err(error) => {
           warn!(
-            academic_year = term.academic_year,
-            semester_id = term.semester_id,
             exam_card_id,
             exam_card_position_id,
             error = %error,
             "exam protocol intermediate fetch failed"
           );
-          false
+          None
         }
-      };
-      settlement_cache.insert(cache_key, value);
-      value
-    };
-
-    if is_exam {
-      subjects.insert(normalized_subject);
-    }
-  }
+      }
+    })
+    .buffer_unordered(UPSTREAM_CONCURRENCY)
+    .collect()
+    .await
+}
 
-  subjects
+/// Subjects of one term's protocol that are settled as an exam, either
+/// directly or through a resolved intermediate settlement.
+fn exam_subjects(
+  items: &[ExamProtocolItem],
+  settlements: &HashMap<(i64, i64), bool>,
+) -> BTreeSet<String> {
+  items
+    .iter()
+    .filter(|item| {
+      is_exam_settlement(item.settlement_method_name.as_deref())
+        || settlements
+          .get(&(item.exam_card_id, item.exam_card_position_id))
+          .copied()
+          .unwrap_or(false)
+    })
+    .filter_map(|item| normalize_subject(&item.subject))
+    .collect()
 }
 
 /// Fetches intermediate protocol details used when settlement is missing in the detailed protocol.
@@ -246,7 +388,7 @@ async fn get_exam_protocol_intermediate(
   access_token: &str,
   exam_card_id: i64,
   exam_card_position_id: i64,
-) -> Result<Vec<ExamProtocolIntermediateItem>> {
+) -> Result<Vec<ExamProtocolIntermediateItem>, ApiError> {
   let url = format!(
     "{API_BASE_URL}{API_EXAM_PROTOCOL_INTERMEDIATE_PATH}?KartaEgzID={exam_card_id}&KartaEgzPozID={exam_card_position_id}"
   );
@@ -260,26 +402,25 @@ async fn get_exam_protocol_intermediate(
     .bearer_auth(access_token)
     .send()
     .await
-    .context("exam protocol intermediate request failed")?;
+    .map_err(|error| ApiError::from_reqwest("exam protocol intermediate", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("exam protocol intermediate failed: {status} body={text}");
+    return Err(ApiError::from_response("exam protocol intermediate", &resp));
   }
 
   resp
     .json::<Vec<ExamProtocolIntermediateItem>>()
     .await
-    .context("invalid exam protocol intermediate json")
+    .map_err(|error| ApiError::from_reqwest("exam protocol intermediate", &error))
 }
 
 /// Fetches public exam schedule entries for the selected academic term.
-async fn get_exam_schedule(
+pub(super) async fn get_exam_schedule(
   client: &Client,
   access_token: &str,
   term: TermQuery,
-) -> Result<Vec<ExamScheduleItem>> {
+) -> Result<Vec<ExamScheduleItem>, ApiError> {
   let url = format!(
     "{API_BASE_URL}{API_EXAM_FILTER_PATH}?KierunekID=&PracownikID=&RokAkad={}&SekcjaID=&SemestrID={}&SystemID=&TrybID=",
     term.academic_year, term.semester_id
@@ -295,27 +436,22 @@ async fn get_exam_schedule(
     .bearer_auth(access_token)
     .send()
     .await
-    .context("exam schedule request failed")?;
+    .map_err(|error| ApiError::from_reqwest("exam schedule", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("exam schedule failed: {status} body={text}");
+    return Err(ApiError::from_response("exam schedule", &resp));
   }
 
   resp
     .json::<Vec<ExamScheduleItem>>()
     .await
-    .context("invalid exam schedule json")
+    .map_err(|error| ApiError::from_reqwest("exam schedule", &error))
 }
 
 /// Normalizes free text values for case-insensitive matching.
 fn normalize_subject(value: &str) -> Option<String> {
-  let normalized = value
-    .split_whitespace()
-    .collect::<Vec<_>>()
-    .join(" ")
-    .to_lowercase();
+  let normalized = normalize_text(value);
   if normalized.is_empty() {
     None
   } else {
@@ -329,32 +465,35 @@ fn is_exam_settlement(value: Option<&str>) -> bool {
 }
 
 /// Maps a raw exam schedule item into an ICS event within the requested date window.
-fn map_exam_event(item: ExamScheduleItem, from: NaiveDate, to: NaiveDate) -> Option<ExamEvent> {
+///
+/// Missing or unusable times fall back to `defaults`, and the event records
+/// which of its times were guessed.
+fn map_exam_event(
+  item: ExamScheduleItem,
+  from: NaiveDate,
+  to: NaiveDate,
+  defaults: ExamTimeDefaults,
+) -> Option<ExamEvent> {
   let exam_date = item.exam_date.date();
   if exam_date < from || exam_date > to {
     return None;
   }
 
-  let start_time = item
-    .start_time
-    .as_deref()
-    .and_then(parse_time)
-    .or_else(|| NaiveTime::from_hms_opt(9, 0, 0))?;
-  let starts = exam_date.and_time(start_time);
+  let published_start = item.start_time.as_deref().and_then(parse_time);
+  let time_confirmed = published_start.is_some();
+  let starts = exam_date.and_time(published_start.unwrap_or(defaults.start));
 
-  let mut ends = item
+  let published_end = item
     .end_time
     .as_deref()
     .and_then(parse_time)
-    .map_or(starts + Duration::minutes(90), |time| {
-      exam_date.and_time(time)
-    });
-
-  if ends <= starts {
-    ends = starts + Duration::minutes(90);
-  }
+    .map(|time| exam_date.and_time(time))
+    .filter(|ends| *ends > starts);
+  let end_estimated = published_end.is_none();
+  let ends = published_end.unwrap_or(starts + defaults.duration);
 
   let is_retake = is_retake_notes(item.notes.as_deref());
+  let meeting = exam_meeting_url(&item);
 
   Some(ExamEvent {
     published_data_id: item.published_data_id,
@@ -366,6 +505,20 @@ fn map_exam_event(item: ExamScheduleItem, from: NaiveDate, to: NaiveDate) -> Opt
     starts,
     ends,
     is_retake,
+    time_confirmed,
+    end_estimated,
+    programmes: Vec::new(),
+    meeting_url: meeting,
+  })
+}
+
+/// A link in place of the room is where an online exam is held; elsewhere
+/// only links to known meeting services count
+fn exam_meeting_url(item: &ExamScheduleItem) -> Option<String> {
+  item.room.as_deref().and_then(first_url).or_else(|| {
+    [&item.room, &item.notes, &item.details]
+      .into_iter()
+      .find_map(|text| text.as_deref().and_then(meeting_url))
   })
 }
 
@@ -429,28 +582,88 @@ fn clean_lecturer(value: Option<String>) -> Option<String> {
   })
 }
 
-/// Builds the default pair of terms (winter/summer) for the academic year.
-fn build_terms_for_year(academic_year: i32) -> Vec<TermQuery> {
-  vec![
-    TermQuery {
-      academic_year,
-      semester_id: 1,
-    },
-    TermQuery {
-      academic_year,
-      semester_id: 2,
-    },
-  ]
+/// Academic year `N` runs from October of `N` to the end of September of `N + 1`.
+fn academic_year_of(date: NaiveDate) -> i32 {
+  if date.month() >= ACADEMIC_YEAR_START_MONTH {
+    date.year()
+  } else {
+    date.year() - 1
+  }
+}
+
+/// Nominal first and last day of a semester: winter is October to February,
+/// summer is March to September.
+fn semester_bounds(academic_year: i32, semester_id: i32) -> Option<(NaiveDate, NaiveDate)> {
+  let winter_start = NaiveDate::from_ymd_opt(academic_year, ACADEMIC_YEAR_START_MONTH, 1)?;
+  let summer_start = NaiveDate::from_ymd_opt(academic_year + 1, SUMMER_SEMESTER_START_MONTH, 1)?;
+  let next_year_start = NaiveDate::from_ymd_opt(academic_year + 1, ACADEMIC_YEAR_START_MONTH, 1)?;
+
+  match semester_id {
+    1 => Some((winter_start, summer_start.pred_opt()?)),
+    2 => Some((summer_start, next_year_start.pred_opt()?)),
+    _ => None,
+  }
+}
+
+/// Every term whose exams can fall inside `from..=to`.
+///
+/// Terms are laid out on the academic calendar and numbered after the year WPS
+/// reports as current. WPS names a year after the calendar year it starts in,
+/// so the range maps onto its years directly, whichever day WPS switches the
+/// current year on; any other numbering is anchored by `today` on the nominal
+/// October start.
+/// Each semester is stretched by the resit period, so last term's resits are
+/// still found early in the next one.
+fn terms_for_range(
+  from: NaiveDate,
+  to: NaiveDate,
+  today: NaiveDate,
+  current_academic_year: i32,
+) -> Vec<TermQuery> {
+  let nominal_year = academic_year_of(today);
+  let offset = if (current_academic_year - nominal_year).abs() <= 1 {
+    0
+  } else {
+    current_academic_year - nominal_year
+  };
+  let first_year = academic_year_of(from - Duration::days(RESIT_GRACE_DAYS));
+  let last_year = academic_year_of(to);
+
+  let mut terms = Vec::new();
+  for academic_year in first_year..=last_year {
+    for semester_id in [1, 2] {
+      let Some((start, end)) = semester_bounds(academic_year, semester_id) else {
+        continue;
+      };
+      if start <= to && end + Duration::days(RESIT_GRACE_DAYS) >= from {
+        terms.push(TermQuery {
+          academic_year: academic_year + offset,
+          semester_id,
+        });
+      }
+    }
+  }
+
+  terms
 }
 
 #[cfg(test)]
 mod tests {
   use super::*;
+  use crate::cache::EventHistory;
 
   fn date(year: i32, month: u32, day: u32) -> NaiveDate {
     NaiveDate::from_ymd_opt(year, month, day).expect("valid date")
   }
 
+  fn map_with_defaults(
+    item: ExamScheduleItem,
+    from: NaiveDate,
+    to: NaiveDate,
+  ) -> Option<ExamEvent> {
+    map_exam_event(item, from, to, ExamTimeDefaults::default())
+  }
+
   fn recipient(section: Option<&str>) -> ExamRecipient {
     ExamRecipient {
       section: section.map(str::to_string),
@@ -557,7 +770,7 @@ mod tests {
 
     for day in [date(2026, 1, 10), date(2026, 1, 15), date(2026, 1, 20)] {
       assert!(
-        map_exam_event(schedule_item(day), from, to).is_some(),
+        map_with_defaults(schedule_item(day), from, to).is_some(),
         "expected {day} to fall inside the window"
       );
     }
@@ -568,17 +781,19 @@ mod tests {
     let from = date(2026, 1, 10);
     let to = date(2026, 1, 20);
 
-    assert!(map_exam_event(schedule_item(date(2026, 1, 9)), from, to).is_none());
-    assert!(map_exam_event(schedule_item(date(2026, 1, 21)), from, to).is_none());
+    assert!(map_with_defaults(schedule_item(date(2026, 1, 9)), from, to).is_none());
+    assert!(map_with_defaults(schedule_item(date(2026, 1, 21)), from, to).is_none());
   }
 
   #[test]
   fn map_exam_event_uses_declared_times() {
     let day = date(2026, 1, 15);
-    let event = map_exam_event(schedule_item(day), day, day).expect("event in window");
+    let event = map_with_defaults(schedule_item(day), day, day).expect("event in window");
 
     assert_eq!(event.starts, day.and_hms_opt(10, 0, 0).expect("start"));
     assert_eq!(event.ends, day.and_hms_opt(11, 30, 0).expect("end"));
+    assert!(event.time_confirmed);
+    assert!(!event.end_estimated);
   }
 
   #[test]
@@ -588,11 +803,30 @@ mod tests {
     item.start_time = None;
     item.end_time = None;
 
-    let event = map_exam_event(item, day, day).expect("event in window");
+    let event = map_with_defaults(item, day, day).expect("event in window");
 
     assert_eq!(event.starts, day.and_hms_opt(9, 0, 0).expect("start"));
     // No end time in the feed means a 90 minute slot.
     assert_eq!(event.ends, day.and_hms_opt(10, 30, 0).expect("end"));
+    assert!(!event.time_confirmed);
+    assert!(event.end_estimated);
+  }
+
+  #[test]
+  fn map_exam_event_uses_configured_defaults() {
+    let day = date(2026, 1, 15);
+    let mut item = schedule_item(day);
+    item.start_time = None;
+    item.end_time = None;
+    let defaults = ExamTimeDefaults {
+      start: NaiveTime::from_hms_opt(8, 0, 0).expect("valid time"),
+      duration: Duration::minutes(120),
+    };
+
+    let event = map_exam_event(item, day, day, defaults).expect("event in window");
+
+    assert_eq!(event.starts, day.and_hms_opt(8, 0, 0).expect("start"));
+    assert_eq!(event.ends, day.and_hms_opt(10, 0, 0).expect("end"));
   }
 
   #[test]
@@ -603,10 +837,12 @@ mod tests {
     // End before start (or equal to it) must fall back to the default length
     item.end_time = Some("13:00".to_string());
 
-    let event = map_exam_event(item, day, day).expect("event in window");
+    let event = map_with_defaults(item, day, day).expect("event in window");
 
     assert_eq!(event.starts, day.and_hms_opt(14, 0, 0).expect("start"));
     assert_eq!(event.ends, day.and_hms_opt(15, 30, 0).expect("end"));
+    assert!(event.time_confirmed);
+    assert!(event.end_estimated);
   }
 
   #[test]
@@ -616,10 +852,12 @@ mod tests {
     item.start_time = Some("nope".to_string());
     item.end_time = Some("11:30".to_string());
 
-    let event = map_exam_event(item, day, day).expect("event in window");
+    let event = map_with_defaults(item, day, day).expect("event in window");
 
     assert_eq!(event.starts, day.and_hms_opt(9, 0, 0).expect("start"));
     assert_eq!(event.ends, day.and_hms_opt(11, 30, 0).expect("end"));
+    assert!(!event.time_confirmed);
+    assert!(!event.end_estimated);
   }
 
   #[test]
@@ -632,7 +870,7 @@ mod tests {
     item.details = Some("  sala A  ".to_string());
     item.notes = Some("Egzamin poprawkowy".to_string());
 
-    let event = map_exam_event(item, day, day).expect("event in window");
+    let event = map_with_defaults(item, day, day).expect("event in window");
 
     assert_eq!(event.subject, "Analiza matematyczna");
     assert_eq!(event.location, None);
@@ -641,6 +879,33 @@ mod tests {
     assert!(event.is_retake);
   }
 
+  #[test]
+  fn map_exam_event_finds_online_exam_links() {
+    let day = date(2026, 1, 15);
+    let mut in_room = schedule_item(day);
+    in_room.room = Some("https://ahe.lodz.pl/egzamin-online".to_string());
+    let mut in_notes = schedule_item(day);
+    in_notes.notes =
+      Some("Egzamin zdalny: https://teams.microsoft.com/l/meetup-join/1".to_string());
+    let mut unrelated = schedule_item(day);
+    unrelated.details = Some("Zasady: https://ahe.lodz.pl/regulamin".to_string());
+
+    let link = |item| {
+      map_with_defaults(item, day, day)
+        .expect("event in window")
+        .meeting_url
+    };
+    assert_eq!(
+      link(in_room).as_deref(),
+      Some("https://ahe.lodz.pl/egzamin-online")
+    );
+    assert_eq!(
+      link(in_notes).as_deref(),
+      Some("https://teams.microsoft.com/l/meetup-join/1")
+    );
+    assert_eq!(link(unrelated), None);
+  }
+
   #[test]
   fn clean_lecturer_strips_leading_dashes() {
     assert_eq!(
@@ -658,13 +923,205 @@ mod tests {
     assert_eq!(clean_text(None), None);
   }
 
+  fn protocol_item(
+    subject: &str,
+    settlement: Option<&str>,
+    exam_card_id: i64,
+    exam_card_position_id: i64,
+  ) -> ExamProtocolItem {
+    ExamProtocolItem {
+      exam_card_position_id,
+      exam_card_id,
+      subject: subject.to_string(),
+      settlement_method_name: settlement.map(str::to_string),
+    }
+  }
+
+  #[test]
+  fn pending_exam_cards_skip_settled_and_unusable_items() {
+    let first_term = [
+      protocol_item("Analiza", Some("egzamin"), 1, 1),
+      protocol_item("Fizyka", None, 2, 1),
+      protocol_item("Chemia", None, 0, 1),
+    ];
+    let second_term = [protocol_item("Fizyka II", None, 2, 1)];
+
+    let pending = pending_exam_cards(first_term.iter().chain(&second_term));
+
+    assert_eq!(pending, BTreeSet::from([(2, 1)]));
+  }
+
+  #[test]
+  fn exam_subjects_combine_direct_and_resolved_settlements() {
+    let items = [
+      protocol_item("Analiza", Some("Egzamin"), 1, 1),
+      protocol_item("Fizyka", None, 2, 1),
+      protocol_item("Chemia", None, 3, 1),
+      protocol_item("Etyka", Some("zaliczenie"), 4, 1),
+    ];
+    let settlements = HashMap::from([((2, 1), true), ((3, 1), false)]);
+
+    assert_eq!(
+      exam_subjects(&items, &settlements),
+      BTreeSet::from(["analiza".to_string(), "fizyka".to_string()])
+    );
+  }
+
+  fn exam_index(index_id: i64, section: &str, programme: &str) -> ExamIndex {
+    ExamIndex {
+      index_id,
+      section_name: Some(section.to_string()),
+      programme: Some(programme.to_string()),
+    }
+  }
+
+  #[test]
+  fn merge_exam_events_lists_every_programme_once() {
+    let day = date(2026, 1, 15);
+    let first = exam_index(1, "IN1", "Informatyka");
+    let second = exam_index(2, "ZA1", "Zarządzanie");
+    let subjects = BTreeSet::from(["analiza matematyczna".to_string()]);
+    let listing = [schedule_item(day)];
+
+    let merged = merge_exam_events(
+      [
+        (&first, &subjects, listing.as_slice()),
+        (&second, &subjects, listing.as_slice()),
+      ],
+      true,
+      day,
+      day,
+      ExamTimeDefaults::default(),
+    );
+
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].programmes, vec!["Informatyka", "Zarządzanie"]);
+  }
+
+  #[test]
+  fn merge_exam_events_keeps_one_event_per_published_id() {
+    let day = date(2026, 1, 15);
+    let only = exam_index(1, "IN1", "Informatyka");
+    let subjects = BTreeSet::from(["analiza matematyczna".to_string()]);
+    let mut repeated = schedule_item(day);
+    repeated.start_time = Some("12:00".to_string());
+    repeated.end_time = Some("13:30".to_string());
+    let listing = [schedule_item(day), repeated];
+
+    let merged = merge_exam_events(
+      [(&only, &subjects, listing.as_slice())],
+      false,
+      day,
+      day,
+      ExamTimeDefaults::default(),
+    );
+
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].starts.time().to_string(), "10:00:00");
+  }
+
   #[test]
-  fn build_terms_for_year_covers_both_semesters() {
-    let terms = build_terms_for_year(2025);
+  fn merge_exam_events_skips_labels_for_a_single_index() {
+    let day = date(2026, 1, 15);
+    let only = exam_index(1, "IN1", "Informatyka");
+    let subjects = BTreeSet::from(["analiza matematyczna".to_string()]);
+    let listing = [schedule_item(day)];
+
+    let merged = merge_exam_events(
+      [(&only, &subjects, listing.as_slice())],
+      false,
+      day,
+      day,
+      ExamTimeDefaults::default(),
+    );
+
+    assert_eq!(merged.len(), 1);
+    assert!(merged[0].programmes.is_empty());
+  }
+
+  fn term(academic_year: i32, semester_id: i32) -> TermQuery {
+    TermQuery {
+      academic_year,
+      semester_id,
+    }
+  }
+
+  #[test]
+  fn academic_year_rolls_over_in_october() {
+    assert_eq!(academic_year_of(date(2025, 9, 30)), 2024);
+    assert_eq!(academic_year_of(date(2025, 10, 1)), 2025);
+    assert_eq!(academic_year_of(date(2026, 2, 15)), 2025);
+  }
+
+  #[test]
+  fn terms_for_a_range_inside_one_semester() {
+    let today = date(2025, 11, 20);
+    assert_eq!(
+      terms_for_range(date(2025, 11, 1), date(2025, 12, 31), today, 2025),
+      vec![term(2025, 1)]
+    );
+  }
+
+  #[test]
+  fn terms_keep_previous_semester_resits() {
+    // Early March still belongs to winter resits
+    let today = date(2026, 3, 10);
+    assert_eq!(
+      terms_for_range(date(2026, 3, 5), date(2026, 4, 30), today, 2025),
+      vec![term(2025, 1), term(2025, 2)]
+    );
+  }
+
+  #[test]
+  fn terms_cross_the_academic_year_boundary() {
+    let today = date(2025, 10, 10);
+    assert_eq!(
+      terms_for_range(date(2025, 8, 15), date(2025, 11, 15), today, 2025),
+      vec![term(2024, 2), term(2025, 1)]
+    );
+  }
+
+  #[test]
+  fn wps_decides_when_the_year_rolls_over() {
+    // WPS already reports the new year in late September, and still the old
+    // one in early October; the range picks the same terms either way
+    for (today, current) in [(date(2025, 9, 25), 2025), (date(2025, 10, 3), 2024)] {
+      assert_eq!(
+        terms_for_range(date(2025, 10, 1), date(2025, 12, 31), today, current),
+        vec![term(2024, 2), term(2025, 1)]
+      );
+    }
+  }
+
+  #[test]
+  fn terms_follow_the_wps_year_numbering() {
+    // Whatever WPS calls the current year, neighbours are numbered relative to it
+    let today = date(2025, 10, 10);
+    assert_eq!(
+      terms_for_range(date(2025, 9, 1), date(2026, 3, 31), today, 7),
+      vec![term(6, 2), term(7, 1), term(7, 2)]
+    );
+  }
 
-    assert_eq!(terms.len(), 2);
-    assert!(terms.iter().all(|term| term.academic_year == 2025));
-    assert_eq!(terms[0].semester_id, 1);
-    assert_eq!(terms[1].semester_id, 2);
+  #[tokio::test]
+  async fn a_failed_protocol_term_cancels_no_exam() {
+    let window = (date(2026, 1, 1), date(2026, 2, 28));
+    let exam =
+      map_with_defaults(schedule_item(date(2026, 1, 20)), window.0, window.1).expect("in range");
+    let history = EventHistory::new(14, 1_000);
+    history
+      .observe("cal", &[], &[exam], true, window, false)
+      .await;
+
+    // The term holding the exam failed, so its absence proves nothing
+    let mut complete = true;
+    let protocols = successes(vec![Some(term(2025, 2)), None], &mut complete);
+    assert_eq!(protocols, vec![term(2025, 2)]);
+    assert!(!complete);
+
+    let revisions = history
+      .observe("cal", &[], &[], complete, window, false)
+      .await;
+    assert!(revisions.cancelled.is_empty());
   }
 }
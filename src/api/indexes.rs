@@ -1,14 +1,17 @@
-use anyhow::{Context, Result};
 use reqwest::Client;
 use tracing::{debug, warn};
 
 use super::API_BASE_URL;
+use super::error::ApiError;
 use crate::models::StudentIndex;
 
 const API_STUDENT_INDEXES_PATH: &str = "/api/Indeks/GETPobierzListeIndeksowDlaStudenta";
 
 /// Fetches all indeks entries for the currently authenticated student.
-pub async fn get_student_indexes(client: &Client, access_token: &str) -> Result<Vec<StudentIndex>> {
+pub async fn get_student_indexes(
+  client: &Client,
+  access_token: &str,
+) -> Result<Vec<StudentIndex>, ApiError> {
   let url = format!("{API_BASE_URL}{API_STUDENT_INDEXES_PATH}");
 
   debug!("GET {API_STUDENT_INDEXES_PATH}");
@@ -17,18 +20,17 @@ pub async fn get_student_indexes(client: &Client, access_token: &str) -> Result<
     .bearer_auth(access_token)
     .send()
     .await
-    .context("student indexes request failed")?;
+    .map_err(|error| ApiError::from_reqwest("student indexes", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
     warn!(?status, "student indexes fetch failed");
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("student indexes failed: {status} body={text}");
+    return Err(ApiError::from_response("student indexes", &resp));
   }
 
   debug!(?status, "student indexes fetch ok");
   resp
     .json::<Vec<StudentIndex>>()
     .await
-    .context("invalid student indexes json")
+    .map_err(|error| ApiError::from_reqwest("student indexes", &error))
 }
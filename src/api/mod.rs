@@ -1,33 +1,58 @@
 mod auth;
+mod error;
+mod exam_cache;
 mod exams;
 mod indexes;
 mod schedule;
 mod student;
 
+use std::time::Duration;
+
 use anyhow::Result;
 use chrono::NaiveDate;
 use reqwest::Client;
 
-use crate::models::{ExamEvent, PlanItem, StudentData, StudentIndex, TokenResponse};
+pub use error::ApiError;
+pub use exams::ExamFetch;
+
+use self::exam_cache::ExamScheduleCache;
+use crate::cache::CacheSnapshot;
+use crate::config::ExamTimeDefaults;
+
+use crate::models::{ExamIndex, PlanItem, StudentData, StudentIndex, TokenResponse};
 
 /// WPS API host, shared by every endpoint call in the submodules below.
 const API_BASE_URL: &str = "https://wpsapi.ahe.lodz.pl";
 const USER_AGENT: &str = concat!("ahe-ics/", env!("CARGO_PKG_VERSION"));
+/// Upper bound for a single WPS call, so a hung upstream surfaces as a 504
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(10);
+/// Most WPS calls one calendar request keeps in flight at a time
+const UPSTREAM_CONCURRENCY: usize = 4;
 
 #[derive(Clone)]
 pub struct ApiClient {
   http: Client,
+  /// Shared by every clone, so all users of the process hit the same cache
+  exam_schedules: ExamScheduleCache,
 }
 
 impl ApiClient {
-  /// Creates a new API client with a configured user-agent.
+  /// Creates a new API client with a configured user-agent and timeouts.
   ///
   /// # Errors
   ///
   /// Returns an error if the underlying HTTP client cannot be built.
   pub fn new() -> Result<Self> {
-    let http = Client::builder().user_agent(USER_AGENT).build()?;
-    Ok(Self { http })
+    let http = Client::builder()
+      .user_agent(USER_AGENT)
+      .timeout(REQUEST_TIMEOUT)
+      .connect_timeout(CONNECT_TIMEOUT)
+      .build()?;
+    Ok(Self {
+      http,
+      exam_schedules: ExamScheduleCache::new(),
+    })
   }
 
   /// Logs into the WPS API and returns an access token payload.
@@ -35,23 +60,23 @@ impl ApiClient {
   /// # Errors
   ///
   /// Returns an error if the request fails or the credentials are rejected.
-  pub async fn login(&self, username: &str, password: &str) -> Result<TokenResponse> {
+  pub async fn login(&self, username: &str, password: &str) -> Result<TokenResponse, ApiError> {
     auth::login(&self.http, username, password).await
   }
 
-  /// Fetches the detailed schedule plan for a student in a date range.
+  /// Fetches the detailed schedule plan for a student in an inclusive date range.
   ///
   /// # Errors
   ///
-  /// Returns an error if the request fails or the response cannot be parsed.
+  /// Returns an error if any request fails or a response cannot be parsed.
   pub async fn get_plan(
     &self,
     access_token: &str,
     student_id: i64,
-    date_from: &str,
-    date_to: &str,
-  ) -> Result<Vec<PlanItem>> {
-    schedule::get_plan(&self.http, access_token, student_id, date_from, date_to).await
+    from: NaiveDate,
+    to: NaiveDate,
+  ) -> Result<Vec<PlanItem>, ApiError> {
+    schedule::get_plan(&self.http, access_token, student_id, from, to).await
   }
 
   /// Fetches the current student's data (includes `IDStudent`).
@@ -59,7 +84,7 @@ impl ApiClient {
   /// # Errors
   ///
   /// Returns an error if the request fails or the response cannot be parsed.
-  pub async fn get_student_data(&self, access_token: &str) -> Result<StudentData> {
+  pub async fn get_student_data(&self, access_token: &str) -> Result<StudentData, ApiError> {
     student::get_student_data(&self.http, access_token).await
   }
 
@@ -68,23 +93,43 @@ impl ApiClient {
   /// # Errors
   ///
   /// Returns an error if the request fails or the response cannot be parsed.
-  pub async fn get_student_indexes(&self, access_token: &str) -> Result<Vec<StudentIndex>> {
+  pub async fn get_student_indexes(
+    &self,
+    access_token: &str,
+  ) -> Result<Vec<StudentIndex>, ApiError> {
     indexes::get_student_indexes(&self.http, access_token).await
   }
 
-  /// Fetches exam events for a student's index in the selected date range.
+  /// Fetches exam events for a student's indexes in the selected date range.
   ///
   /// # Errors
   ///
-  /// Returns an error if the request fails or the response cannot be parsed.
+  /// Returns an error if the current academic year cannot be read; later
+  /// failed lookups only mark the result incomplete.
   pub async fn get_exams(
     &self,
     access_token: &str,
-    index_id: i64,
-    section_name: Option<&str>,
+    indexes: &[ExamIndex],
     from: NaiveDate,
     to: NaiveDate,
-  ) -> Result<Vec<ExamEvent>> {
-    exams::get_exams(&self.http, access_token, index_id, section_name, from, to).await
+    defaults: ExamTimeDefaults,
+  ) -> Result<ExamFetch, ApiError> {
+    // Boxed: the cached lookups make this future too large to embed in callers
+    Box::pin(exams::get_exams(
+      &self.http,
+      &self.exam_schedules,
+      access_token,
+      indexes,
+      from,
+      to,
+      defaults,
+    ))
+    .await
+  }
+
+  /// Size and eviction counts of the shared exam schedule cache
+  #[must_use]
+  pub fn exam_schedule_snapshot(&self) -> CacheSnapshot {
+    self.exam_schedules.snapshot()
   }
 }
@@ -1,8 +1,12 @@
-use anyhow::{Context, Result};
+use std::collections::HashSet;
+
+use chrono::{Datelike, Duration, NaiveDate};
+use futures_util::{StreamExt, TryStreamExt, stream};
 use reqwest::Client;
 use tracing::{debug, warn};
 
-use super::API_BASE_URL;
+use super::error::ApiError;
+use super::{API_BASE_URL, UPSTREAM_CONCURRENCY};
 use crate::models::PlanItem;
 
 const API_PLAN_PATH: &str = "/api/PlanyZajec/GETPlanSzczegolowy";
@@ -10,35 +14,157 @@ const PLAN_INACTIVE_PARAM: &str = "CzyNieaktywnePlany=0";
 const PLAN_LOADER_PARAM: &str = "loader=none";
 
 /// Fetches the detailed schedule plan for the given student and date range.
+///
+/// WPS gets slow on long windows, so the range is split into calendar months
+/// fetched in parallel; any failed month fails the whole plan rather than
+/// silently leaving a hole in the calendar.
 pub async fn get_plan(
   client: &Client,
   access_token: &str,
   student_id: i64,
-  date_from: &str,
-  date_to: &str,
-) -> Result<Vec<PlanItem>> {
+  from: NaiveDate,
+  to: NaiveDate,
+) -> Result<Vec<PlanItem>, ApiError> {
+  let chunks = stream::iter(month_chunks(from, to))
+    .map(|(chunk_from, chunk_to)| {
+      get_plan_chunk(client, access_token, student_id, chunk_from, chunk_to)
+    })
+    .buffered(UPSTREAM_CONCURRENCY)
+    .try_collect::<Vec<_>>()
+    .await?;
+
+  Ok(merge_chunks(chunks))
+}
+
+async fn get_plan_chunk(
+  client: &Client,
+  access_token: &str,
+  student_id: i64,
+  from: NaiveDate,
+  to: NaiveDate,
+) -> Result<Vec<PlanItem>, ApiError> {
+  let date_from = from.format("%Y-%m-%d");
+  let date_to = to.format("%Y-%m-%d");
   let url = format!(
     "{API_BASE_URL}{API_PLAN_PATH}?{PLAN_INACTIVE_PARAM}&DataDo={date_to}&DataOd={date_from}&StudentID={student_id}&{PLAN_LOADER_PARAM}"
   );
 
-  debug!(student_id, date_from, date_to, "GET {API_PLAN_PATH}");
+  debug!(student_id, %date_from, %date_to, "GET {API_PLAN_PATH}");
   let resp = client
     .get(url)
     .bearer_auth(access_token)
     .send()
     .await
-    .context("plan request failed")?;
+    .map_err(|error| ApiError::from_reqwest("plan", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
     warn!(?status, "plan fetch failed");
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("plan failed: {status} body={text}");
+    return Err(ApiError::from_response("plan", &resp));
   }
 
   debug!(?status, "plan fetch ok");
   resp
     .json::<Vec<PlanItem>>()
     .await
-    .context("invalid plan json")
+    .map_err(|error| ApiError::from_reqwest("plan", &error))
+}
+
+/// Splits `from..=to` at month boundaries; both ends stay inclusive.
+fn month_chunks(from: NaiveDate, to: NaiveDate) -> Vec<(NaiveDate, NaiveDate)> {
+  let mut chunks = Vec::new();
+  let mut start = from;
+
+  while start <= to {
+    let month_end = next_month_start(start).map_or(NaiveDate::MAX, |next| next - Duration::days(1));
+    let end = month_end.min(to);
+    chunks.push((start, end));
+
+    let Some(next) = end.succ_opt() else {
+      break;
+    };
+    start = next;
+  }
+
+  chunks
+}
+
+fn next_month_start(date: NaiveDate) -> Option<NaiveDate> {
+  if date.month() == 12 {
+    NaiveDate::from_ymd_opt(date.year() + 1, 1, 1)
+  } else {
+    NaiveDate::from_ymd_opt(date.year(), date.month() + 1, 1)
+  }
+}
+
+/// Concatenates chunks in order, keeping the first copy of a class that WPS
+/// returned for two neighbouring months (e.g. one crossing midnight).
+fn merge_chunks(chunks: Vec<Vec<PlanItem>>) -> Vec<PlanItem> {
+  let mut seen = HashSet::new();
+  chunks
+    .into_iter()
+    .flatten()
+    .filter(|item| seen.insert(item.schedule_item_id))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn date(year: i32, month: u32, day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(year, month, day).expect("valid date")
+  }
+
+  fn plan_item(schedule_item_id: i64, subject_name: &str) -> PlanItem {
+    PlanItem {
+      starts_at: date(2026, 1, 31).and_hms_opt(22, 0, 0).expect("valid time"),
+      ends_at: date(2026, 2, 1).and_hms_opt(1, 0, 0).expect("valid time"),
+      subject_name: subject_name.to_string(),
+      class_type: "Wykład".to_string(),
+      class_type_short: "W".to_string(),
+      room_number: None,
+      room_address: None,
+      webinar: false,
+      instructors: Vec::new(),
+      schedule_item_id,
+      form_color: None,
+      meeting_url: None,
+      place: None,
+      merged_from: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn month_chunks_split_at_month_boundaries() {
+    assert_eq!(
+      month_chunks(date(2025, 11, 15), date(2026, 1, 10)),
+      vec![
+        (date(2025, 11, 15), date(2025, 11, 30)),
+        (date(2025, 12, 1), date(2025, 12, 31)),
+        (date(2026, 1, 1), date(2026, 1, 10)),
+      ]
+    );
+  }
+
+  #[test]
+  fn month_chunks_keep_short_and_single_day_ranges_whole() {
+    let day = date(2026, 2, 28);
+    assert_eq!(month_chunks(day, day), vec![(day, day)]);
+    assert_eq!(
+      month_chunks(date(2026, 3, 2), date(2026, 3, 20)),
+      vec![(date(2026, 3, 2), date(2026, 3, 20))]
+    );
+  }
+
+  #[test]
+  fn merge_chunks_keeps_order_and_drops_duplicates() {
+    let merged = merge_chunks(vec![
+      vec![plan_item(1, "Analiza"), plan_item(2, "Fizyka")],
+      vec![plan_item(2, "Fizyka"), plan_item(3, "Chemia")],
+    ]);
+
+    let ids: Vec<i64> = merged.iter().map(|item| item.schedule_item_id).collect();
+    assert_eq!(ids, vec![1, 2, 3]);
+  }
 }
@@ -1,14 +1,17 @@
-use anyhow::{Context, Result};
 use reqwest::Client;
 use tracing::{debug, warn};
 
 use super::API_BASE_URL;
+use super::error::ApiError;
 use crate::models::StudentData;
 
 const API_STUDENT_PATH: &str = "/api/Student/GetDaneStudenta";
 
 /// Fetches data for the currently authenticated student.
-pub async fn get_student_data(client: &Client, access_token: &str) -> Result<StudentData> {
+pub async fn get_student_data(
+  client: &Client,
+  access_token: &str,
+) -> Result<StudentData, ApiError> {
   let url = format!("{API_BASE_URL}{API_STUDENT_PATH}");
 
   debug!("GET {API_STUDENT_PATH}");
@@ -17,18 +20,17 @@ pub async fn get_student_data(client: &Client, access_token: &str) -> Result<Stu
     .bearer_auth(access_token)
     .send()
     .await
-    .context("student data request failed")?;
+    .map_err(|error| ApiError::from_reqwest("student data", &error))?;
 
   let status = resp.status();
   if !status.is_success() {
     warn!(?status, "student data fetch failed");
-    let text = resp.text().await.unwrap_or_default();
-    anyhow::bail!("student data failed: {status} body={text}");
+    return Err(ApiError::from_response("student data", &resp));
   }
 
   debug!(?status, "student data fetch ok");
   resp
     .json::<StudentData>()
     .await
-    .context("invalid student data json")
+    .map_err(|error| ApiError::from_reqwest("student data", &error))
 }
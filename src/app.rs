@@ -1,9 +1,10 @@
 use std::sync::Arc;
 
 use anyhow::Result;
+use tracing::{info, warn};
 
 use crate::api::ApiClient;
-use crate::cache::{IcsCache, StudentContextCache, TokenCache};
+use crate::cache::{CacheSnapshot, EventHistory, IcsCache, StudentContextCache, TokenCache};
 use crate::config::ServerSettings;
 
 #[derive(Clone)]
@@ -13,6 +14,7 @@ pub struct AppState<C: ServerSettings> {
   pub token_cache: Arc<TokenCache>,
   pub student_context_cache: Arc<StudentContextCache>,
   pub ics_cache: IcsCache,
+  pub event_history: Arc<EventHistory>,
 }
 
 impl<C: ServerSettings> AppState<C> {
@@ -23,12 +25,53 @@ impl<C: ServerSettings> AppState<C> {
   /// Returns an error if the API client cannot be constructed.
   pub fn new(config: C) -> Result<Self> {
     let api = ApiClient::new()?;
+    let limits = config.cache_limits();
+    let event_history =
+      EventHistory::new(config.calendar_cancelled_days(), limits.history_max_events);
     Ok(Self {
       config,
       api,
-      token_cache: Arc::new(TokenCache::default()),
-      student_context_cache: Arc::new(StudentContextCache::default()),
-      ics_cache: IcsCache::new(),
+      token_cache: Arc::new(TokenCache::new(limits.token_max_entries)),
+      student_context_cache: Arc::new(StudentContextCache::new(limits.student_max_entries)),
+      ics_cache: IcsCache::new(limits.ics_max_bytes),
+      event_history: Arc::new(event_history),
     })
   }
+
+  /// Loads the event history saved by the previous run, if a file is configured.
+  ///
+  /// An unreadable file is logged and the history starts empty, as it would
+  /// without one.
+  pub async fn restore_history(&self) {
+    let Some(path) = self.config.history_file() else {
+      return;
+    };
+    match self.event_history.restore(path).await {
+      Ok(calendars) => info!(calendars, path = %path.display(), "restored event history"),
+      Err(error) => warn!(error = %format!("{error:#}"), "starting with an empty event history"),
+    }
+  }
+
+  /// Writes the event history to the configured file, if any, for the next run
+  pub fn save_history(&self) {
+    let Some(path) = self.config.history_file() else {
+      return;
+    };
+    match self.event_history.save(path) {
+      Ok(calendars) => info!(calendars, path = %path.display(), "saved event history"),
+      Err(error) => warn!(error = %format!("{error:#}"), "failed to save event history"),
+    }
+  }
+
+  /// Current size and eviction counts of every cache
+  #[must_use]
+  pub fn cache_snapshots(&self) -> [CacheSnapshot; 5] {
+    [
+      self.token_cache.snapshot(),
+      self.student_context_cache.snapshot(),
+      self.ics_cache.snapshot(),
+      self.api.exam_schedule_snapshot(),
+      self.event_history.snapshot(),
+    ]
+  }
 }
@@ -1,10 +1,9 @@
-use std::net::SocketAddr;
-
 use anyhow::Result;
 use tracing_subscriber::EnvFilter;
 
 use ahe_ics::app::AppState;
-use ahe_ics::config::Config;
+use ahe_ics::config::{CliOptions, Config};
+use ahe_ics::server::serve;
 use ahe_ics::web::router;
 
 #[tokio::main]
@@ -14,18 +13,19 @@ async fn main() -> Result<()> {
     EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("ahe_ics=info,axum=info"));
   tracing_subscriber::fmt().with_env_filter(filter).init();
 
-  let config = Config::from_env()?;
-  let bind_addr = config.bind_addr.clone();
-  let state = AppState::new(config)?;
+  let options = CliOptions::from_args()?;
+  let config = Config::load(options.config_path.as_deref())?;
+  if options.print_config {
+    println!("{config:#?}");
+    return Ok(());
+  }
 
-  let app = router(state);
-  let listener = tokio::net::TcpListener::bind(&bind_addr).await?;
-  println!("listening on http://{bind_addr}");
-  axum::serve(
-    listener,
-    app.into_make_service_with_connect_info::<SocketAddr>(),
-  )
-  .await?;
+  let state = AppState::new(config.clone())?;
+  state.restore_history().await;
 
-  Ok(())
+  let app = router(state.clone());
+  let served = serve(app, &config).await;
+  // In-flight requests have drained, so the history holds every served stamp
+  state.save_history();
+  served
 }
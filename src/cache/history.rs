@@ -0,0 +1,937 @@
+use std::collections::hash_map::{DefaultHasher, Entry};
+use std::collections::{BTreeMap, HashMap};
+use std::future;
+use std::hash::{Hash, Hasher};
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result, bail};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, SubsecRound, Utc};
+use moka::future::Cache;
+use moka::ops::compute::Op;
+use serde::{Deserialize, Serialize};
+
+use crate::cache::{CacheSnapshot, EvictionCounters};
+use crate::ics::{class_uid, exam_uid, series_uid};
+use crate::models::{ExamEvent, Instructor, Place, PlanBlock, PlanItem};
+use crate::recurrence::{self, PlanEntry, Series};
+
+/// Calendars nobody has polled for this long lose their history
+const HISTORY_IDLE_SECONDS: u64 = 30 * 86_400;
+/// Events that started this long ago are forgotten once a feed no longer
+/// lists them, whatever window the feed was requested for
+const PAST_EVENT_DAYS: i64 = 366;
+/// Bumped whenever the layout of the history file changes
+const HISTORY_FILE_VERSION: u32 = 1;
+
+/// An event as it was last served to subscribers
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub enum TrackedItem {
+  Class(#[serde(with = "StoredPlanItem")] PlanItem),
+  Exam(ExamEvent),
+}
+
+/// Field-for-field copy of `PlanItem` for the history file; `PlanItem`'s own
+/// serde attributes describe the WPS response, which skips some fields
+#[derive(Deserialize, Serialize)]
+#[serde(remote = "PlanItem")]
+struct StoredPlanItem {
+  starts_at: NaiveDateTime,
+  ends_at: NaiveDateTime,
+  subject_name: String,
+  class_type: String,
+  class_type_short: String,
+  room_number: Option<String>,
+  room_address: Option<String>,
+  webinar: bool,
+  instructors: Vec<Instructor>,
+  schedule_item_id: i64,
+  form_color: Option<String>,
+  meeting_url: Option<String>,
+  place: Option<Place>,
+  merged_from: Vec<PlanBlock>,
+}
+
+impl TrackedItem {
+  fn starts(&self) -> NaiveDateTime {
+    match self {
+      Self::Class(item) => item.starts_at,
+      Self::Exam(exam) => exam.starts,
+    }
+  }
+
+  /// Covers what subscribers notice: time, room, summary and status.
+  ///
+  /// Built from the WPS fields rather than rendered text, so switching the
+  /// calendar language does not count as a change.
+  fn fingerprint(&self) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    match self {
+      Self::Class(item) => (
+        item.starts_at,
+        item.ends_at,
+        &item.subject_name,
+        &item.class_type,
+        &item.class_type_short,
+        &item.room_number,
+        &item.room_address,
+        item.webinar,
+        &item.meeting_url,
+        item
+          .place
+          .as_ref()
+          .map(|place| (&place.name, &place.address)),
+        &item.merged_from,
+      )
+        .hash(&mut hasher),
+      Self::Exam(exam) => (
+        exam.starts,
+        exam.ends,
+        &exam.subject,
+        exam.is_retake,
+        &exam.location,
+        exam.time_confirmed,
+        &exam.meeting_url,
+      )
+        .hash(&mut hasher),
+    }
+    hasher.finish()
+  }
+}
+
+/// Revision data of one event, unchanged across renders until its content does
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct EventStamp {
+  /// Starts at `0` and grows by one with every change
+  pub sequence: u32,
+  /// First time the event was served
+  pub created: DateTime<Utc>,
+  /// Last time the event's time, room, summary or status changed
+  pub last_modified: DateTime<Utc>,
+}
+
+impl EventStamp {
+  fn new(now: DateTime<Utc>) -> Self {
+    Self {
+      sequence: 0,
+      created: now,
+      last_modified: now,
+    }
+  }
+
+  fn bump(&mut self, now: DateTime<Utc>) {
+    self.sequence = self.sequence.saturating_add(1);
+    // Two changes within one second still get distinct timestamps
+    self.last_modified = now.max(self.last_modified + chrono::Duration::seconds(1));
+  }
+}
+
+/// Where a class was served as an occurrence of a series
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct SeriesSlot {
+  /// UID of the series master
+  pub uid: String,
+  /// Start the rule generates for the occurrence
+  pub recurrence_id: NaiveDateTime,
+}
+
+/// An event that vanished upstream and is still announced as cancelled
+#[derive(Clone, Debug)]
+pub struct CancelledItem {
+  pub item: TrackedItem,
+  /// Already bumped past the revision the live event was served with
+  pub stamp: EventStamp,
+  /// The series it was last served in, which subscribers hold it under
+  pub series: Option<SeriesSlot>,
+}
+
+/// What a render needs from the history of its calendar
+#[derive(Clone, Debug, Default)]
+pub struct FeedRevisions {
+  /// Stamps of the live events and series masters, by UID
+  pub stamps: HashMap<String, EventStamp>,
+  pub cancelled: Vec<CancelledItem>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct TrackedEvent {
+  item: TrackedItem,
+  fingerprint: u64,
+  stamp: EventStamp,
+  /// First time a feed covering the event's day came back without it
+  vanished_at: Option<DateTime<Utc>>,
+  series: Option<SeriesSlot>,
+}
+
+/// A series master as a feed serves it, before its vanished occurrences are
+/// folded in
+#[derive(Clone, Debug)]
+struct ServedSeries {
+  uid: String,
+  /// Covers the master's content, `DTSTART`, `RRULE` and overrides
+  fingerprint: u64,
+  exdates: Vec<NaiveDateTime>,
+  /// Class UIDs of the members with their slots
+  occurrences: Vec<(String, NaiveDateTime)>,
+  last_start: NaiveDateTime,
+}
+
+impl ServedSeries {
+  fn of(calendar_id: &str, series: &Series) -> Self {
+    let mut hasher = DefaultHasher::new();
+    let mut moved: Vec<_> = series
+      .overrides
+      .iter()
+      .map(|moved| moved.recurrence_id)
+      .collect();
+    moved.sort_unstable();
+    (
+      TrackedItem::Class(series.master.clone()).fingerprint(),
+      series.rrule(),
+      moved,
+    )
+      .hash(&mut hasher);
+    Self {
+      uid: series_uid(calendar_id, series),
+      fingerprint: hasher.finish(),
+      exdates: series.exdates.clone(),
+      occurrences: series
+        .occurrences()
+        .map(|(item, recurrence_id)| (class_uid(calendar_id, item), recurrence_id))
+        .collect(),
+      last_start: series.last_start(),
+    }
+  }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct TrackedSeries {
+  fingerprint: u64,
+  stamp: EventStamp,
+  last_start: NaiveDateTime,
+}
+
+/// Events recently served for one calendar, keyed by UID
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+struct CalendarHistory {
+  events: HashMap<String, TrackedEvent>,
+  series: HashMap<String, TrackedSeries>,
+}
+
+impl CalendarHistory {
+  /// Records the events just fetched for `from`..=`to`, bumping the stamps of
+  /// the ones that changed, and collects the ones that disappeared from that
+  /// window less than `retention` ago.
+  ///
+  /// Exams are only judged when `exams_complete` is set, so a failed exam
+  /// fetch does not cancel every exam.
+  fn reconcile(
+    &mut self,
+    current: Vec<(String, TrackedItem)>,
+    exams_complete: bool,
+    (from, to): (NaiveDate, NaiveDate),
+    now: DateTime<Utc>,
+    retention: chrono::Duration,
+  ) -> FeedRevisions {
+    let mut stamps = HashMap::with_capacity(current.len());
+    for (uid, item) in current {
+      let fingerprint = item.fingerprint();
+      let stamp = match self.events.entry(uid.clone()) {
+        Entry::Occupied(mut entry) => {
+          let event = entry.get_mut();
+          // Coming back after being served as cancelled is a status change
+          if event.fingerprint != fingerprint || event.vanished_at.is_some() {
+            event.stamp.bump(now);
+          }
+          event.item = item;
+          event.fingerprint = fingerprint;
+          event.vanished_at = None;
+          event.stamp
+        }
+        Entry::Vacant(entry) => {
+          entry
+            .insert(TrackedEvent {
+              item,
+              fingerprint,
+              stamp: EventStamp::new(now),
+              vanished_at: None,
+              series: None,
+            })
+            .stamp
+        }
+      };
+      stamps.insert(uid, stamp);
+    }
+
+    let forget_before = now.date_naive() - chrono::Duration::days(PAST_EVENT_DAYS);
+    let mut cancelled = Vec::new();
+    self.events.retain(|uid, event| {
+      if stamps.contains_key(uid) {
+        return true;
+      }
+      let within_retention = |at: DateTime<Utc>| now - at < retention;
+      let day = event.item.starts().date();
+      let judged = exams_complete || matches!(event.item, TrackedItem::Class(_));
+      if !judged || day < from || day > to {
+        // Nothing is known about it from this feed, which may just cover a
+        // narrower window; only long past events are forgotten
+        return day >= forget_before && event.vanished_at.is_none_or(within_retention);
+      }
+
+      if event.vanished_at.is_none() {
+        event.vanished_at = Some(now);
+        event.stamp.bump(now);
+      }
+      if !event.vanished_at.is_some_and(within_retention) {
+        return false;
+      }
+      cancelled.push(CancelledItem {
+        item: event.item.clone(),
+        stamp: event.stamp,
+        series: event.series.clone(),
+      });
+      true
+    });
+
+    cancelled.sort_by_key(|cancelled| cancelled.item.starts());
+    FeedRevisions { stamps, cancelled }
+  }
+
+  /// Records which series each live class is served in, and stamps the
+  /// series masters of the feed.
+  ///
+  /// A master changes whenever its `DTSTART`, `RRULE`, `EXDATE`s or
+  /// overrides do, cancelled occurrences included, so sliding the window
+  /// past its first occurrence is a new revision too.
+  fn reconcile_series(
+    &mut self,
+    served: Vec<ServedSeries>,
+    revisions: &mut FeedRevisions,
+    now: DateTime<Utc>,
+  ) {
+    let mut slots: HashMap<&str, SeriesSlot> = served
+      .iter()
+      .flat_map(|series| {
+        series.occurrences.iter().map(|(uid, recurrence_id)| {
+          let slot = SeriesSlot {
+            uid: series.uid.clone(),
+            recurrence_id: *recurrence_id,
+          };
+          (uid.as_str(), slot)
+        })
+      })
+      .collect();
+    // Events outside the feed keep the slot they were last served in
+    for (uid, event) in &mut self.events {
+      if revisions.stamps.contains_key(uid) {
+        event.series = slots.remove(uid.as_str());
+      }
+    }
+
+    let forget_before = now.date_naive() - chrono::Duration::days(PAST_EVENT_DAYS);
+    let live: Vec<String> = served.iter().map(|series| series.uid.clone()).collect();
+    for series in served {
+      let mut cancelled: Vec<NaiveDateTime> = revisions
+        .cancelled
+        .iter()
+        .filter_map(|cancelled| cancelled.series.as_ref())
+        .filter(|slot| slot.uid == series.uid)
+        .map(|slot| slot.recurrence_id)
+        .collect();
+      cancelled.sort_unstable();
+      let exdates: Vec<_> = series
+        .exdates
+        .iter()
+        .filter(|exdate| !cancelled.contains(exdate))
+        .collect();
+      let mut hasher = DefaultHasher::new();
+      (series.fingerprint, exdates, &cancelled).hash(&mut hasher);
+      let fingerprint = hasher.finish();
+
+      let tracked = self
+        .series
+        .entry(series.uid.clone())
+        .and_modify(|tracked| {
+          if tracked.fingerprint != fingerprint {
+            tracked.stamp.bump(now);
+            tracked.fingerprint = fingerprint;
+          }
+          tracked.last_start = series.last_start;
+        })
+        .or_insert_with(|| TrackedSeries {
+          fingerprint,
+          stamp: EventStamp::new(now),
+          last_start: series.last_start,
+        });
+      revisions.stamps.insert(series.uid, tracked.stamp);
+    }
+    self
+      .series
+      .retain(|uid, tracked| live.contains(uid) || tracked.last_start.date() >= forget_before);
+  }
+}
+
+/// Layout of the history file, written from borrowed and read into owned maps
+#[derive(Deserialize, Serialize)]
+struct HistoryFile<M> {
+  version: u32,
+  calendars: M,
+}
+
+/// Read before the rest, so a file from another layout is reported as such
+#[derive(Deserialize)]
+struct HistoryFileVersion {
+  version: u32,
+}
+
+/// Per-calendar memory of served events.
+///
+/// Keeps `SEQUENCE`, `DTSTAMP` and `LAST-MODIFIED` stable across renders and
+/// lets vanished events be announced as cancelled instead of silently
+/// disappearing.
+pub struct EventHistory {
+  inner: Cache<String, Arc<CalendarHistory>>,
+  retention: chrono::Duration,
+  max_events: u64,
+  evictions: Arc<EvictionCounters>,
+}
+
+impl EventHistory {
+  /// Serves vanished events as cancelled for `retention_days` (`0` serves
+  /// none) and remembers at most `max_events` events across all calendars
+  #[must_use]
+  pub fn new(retention_days: i64, max_events: u64) -> Self {
+    let evictions = Arc::new(EvictionCounters::default());
+    Self {
+      inner: Cache::builder()
+        .max_capacity(max_events)
+        .weigher(|_, history: &Arc<CalendarHistory>| {
+          u32::try_from(history.events.len() + history.series.len()).unwrap_or(u32::MAX)
+        })
+        .time_to_idle(Duration::from_secs(HISTORY_IDLE_SECONDS))
+        .eviction_listener(EvictionCounters::listener(&evictions))
+        .build(),
+      retention: chrono::Duration::days(retention_days),
+      max_events,
+      evictions,
+    }
+  }
+
+  #[must_use]
+  pub fn snapshot(&self) -> CacheSnapshot {
+    CacheSnapshot::new(
+      "event_history",
+      self.inner.entry_count(),
+      self.inner.weighted_size(),
+      self.max_events,
+      &self.evictions,
+    )
+  }
+
+  /// Loads the histories `save` wrote to `path`, so stamps and pending
+  /// cancellations survive a restart. A missing file is not an error.
+  ///
+  /// Returns the number of calendars restored.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the file cannot be read or was written in another
+  /// layout; the history is left empty then.
+  pub async fn restore(&self, path: &Path) -> Result<usize> {
+    let contents = match std::fs::read(path) {
+      Ok(contents) => contents,
+      Err(error) if error.kind() == ErrorKind::NotFound => return Ok(0),
+      Err(error) => {
+        return Err(error)
+          .with_context(|| format!("failed to read history file {}", path.display()));
+      }
+    };
+    let HistoryFileVersion { version } = serde_json::from_slice(&contents)
+      .with_context(|| format!("failed to parse history file {}", path.display()))?;
+    if version != HISTORY_FILE_VERSION {
+      bail!(
+        "history file {} has version {version}, expected {HISTORY_FILE_VERSION}",
+        path.display()
+      );
+    }
+    let file: HistoryFile<HashMap<String, CalendarHistory>> = serde_json::from_slice(&contents)
+      .with_context(|| format!("failed to parse history file {}", path.display()))?;
+
+    let restored = file.calendars.len();
+    for (calendar_id, history) in file.calendars {
+      self.inner.insert(calendar_id, Arc::new(history)).await;
+    }
+    Ok(restored)
+  }
+
+  /// Writes every calendar's history to `path` for `restore`, replacing the
+  /// file only once the new one is complete.
+  ///
+  /// Returns the number of calendars saved.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the file cannot be written.
+  pub fn save(&self, path: &Path) -> Result<usize> {
+    let entries: Vec<_> = self.inner.iter().collect();
+    let calendars: BTreeMap<&str, &CalendarHistory> = entries
+      .iter()
+      .map(|(calendar_id, history)| (calendar_id.as_str(), history.as_ref()))
+      .collect();
+    let contents = serde_json::to_vec(&HistoryFile {
+      version: HISTORY_FILE_VERSION,
+      calendars,
+    })?;
+
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".tmp");
+    std::fs::write(&partial, contents).with_context(|| {
+      format!(
+        "failed to write history file {}",
+        Path::new(&partial).display()
+      )
+    })?;
+    std::fs::rename(&partial, path)
+      .with_context(|| format!("failed to replace history file {}", path.display()))?;
+    Ok(entries.len())
+  }
+
+  /// Remembers the events of a freshly fetched feed and returns their stamps
+  /// plus the events that should be served as cancelled alongside them.
+  ///
+  /// `exams_complete` is false when some exam lookups failed, so exams missing
+  /// from `exams` are kept as they were rather than cancelled.
+  /// `compress_series` must match the render, so series masters are stamped
+  /// as they are served.
+  pub async fn observe(
+    &self,
+    calendar_id: &str,
+    plan: &[PlanItem],
+    exams: &[ExamEvent],
+    exams_complete: bool,
+    (from, to): (NaiveDate, NaiveDate),
+    compress_series: bool,
+  ) -> FeedRevisions {
+    let current = plan
+      .iter()
+      .map(|item| {
+        (
+          class_uid(calendar_id, item),
+          TrackedItem::Class(item.clone()),
+        )
+      })
+      .chain(
+        exams
+          .iter()
+          .map(|exam| (exam_uid(calendar_id, exam), TrackedItem::Exam(exam.clone()))),
+      )
+      .collect();
+    let served: Vec<ServedSeries> = if compress_series {
+      recurrence::group(plan)
+        .iter()
+        .filter_map(|entry| match entry {
+          PlanEntry::Series(series) => Some(ServedSeries::of(calendar_id, series)),
+          PlanEntry::Single(_) => None,
+        })
+        .collect()
+    } else {
+      Vec::new()
+    };
+
+    // ICS timestamps have whole-second precision, so stamps are kept at it
+    let now = Utc::now().trunc_subsecs(0);
+    let mut revisions = FeedRevisions::default();
+    // Renders of one calendar in other languages, zones or windows run in
+    // parallel; reconciling under the entry's lock means none of them works
+    // on a stale copy and overwrites the bumps of another
+    self
+      .inner
+      .entry_by_ref(calendar_id)
+      .and_compute_with(|entry| {
+        let mut history = entry
+          .map(|entry| CalendarHistory::clone(entry.value()))
+          .unwrap_or_default();
+        revisions = history.reconcile(current, exams_complete, (from, to), now, self.retention);
+        history.reconcile_series(served, &mut revisions, now);
+        future::ready(Op::Put(Arc::new(history)))
+      })
+      .await;
+
+    revisions
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn day(day: u32) -> NaiveDate {
+    NaiveDate::from_ymd_opt(2026, 3, day).expect("valid date")
+  }
+
+  fn class(id: i64, on: u32) -> (String, TrackedItem) {
+    (format!("class-{id}"), TrackedItem::Class(lecture(id, on)))
+  }
+
+  fn lecture(id: i64, on: u32) -> PlanItem {
+    let starts_at = day(on).and_hms_opt(10, 0, 0).expect("valid time");
+    PlanItem {
+      starts_at,
+      ends_at: starts_at + chrono::Duration::minutes(90),
+      subject_name: "Algebra".to_string(),
+      class_type: "Wyklad".to_string(),
+      class_type_short: "W".to_string(),
+      room_number: None,
+      room_address: None,
+      webinar: false,
+      instructors: Vec::new(),
+      schedule_item_id: id,
+      form_color: None,
+      meeting_url: None,
+      place: None,
+      merged_from: Vec::new(),
+    }
+  }
+
+  fn exam(id: i64, on: u32) -> (String, TrackedItem) {
+    let starts = day(on).and_hms_opt(9, 0, 0).expect("valid time");
+    let exam = ExamEvent {
+      published_data_id: id,
+      subject: "Algebra".to_string(),
+      notes: None,
+      location: None,
+      lecturer: None,
+      details: None,
+      starts,
+      ends: starts + chrono::Duration::minutes(90),
+      is_retake: false,
+      time_confirmed: true,
+      end_estimated: false,
+      programmes: Vec::new(),
+      meeting_url: None,
+    };
+    (format!("exam-{id}"), TrackedItem::Exam(exam))
+  }
+
+  fn at(hours: i64) -> DateTime<Utc> {
+    DateTime::from_timestamp(1_772_000_000, 0).expect("valid timestamp")
+      + chrono::Duration::hours(hours)
+  }
+
+  const WINDOW: (u32, u32) = (1, 31);
+
+  fn reconcile(
+    history: &mut CalendarHistory,
+    current: Vec<(String, TrackedItem)>,
+    exams_complete: bool,
+    now: DateTime<Utc>,
+  ) -> Vec<CancelledItem> {
+    reconcile_revisions(history, current, exams_complete, now).cancelled
+  }
+
+  fn reconcile_revisions(
+    history: &mut CalendarHistory,
+    current: Vec<(String, TrackedItem)>,
+    exams_complete: bool,
+    now: DateTime<Utc>,
+  ) -> FeedRevisions {
+    history.reconcile(
+      current,
+      exams_complete,
+      (day(WINDOW.0), day(WINDOW.1)),
+      now,
+      chrono::Duration::days(7),
+    )
+  }
+
+  fn ids(cancelled: &[CancelledItem]) -> Vec<i64> {
+    cancelled
+      .iter()
+      .map(|cancelled| match &cancelled.item {
+        TrackedItem::Class(item) => item.schedule_item_id,
+        TrackedItem::Exam(exam) => exam.published_data_id,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn vanished_events_are_cancelled_until_retention_runs_out() {
+    let mut history = CalendarHistory::default();
+    assert!(reconcile(&mut history, vec![class(1, 10), class(2, 11)], true, at(0)).is_empty());
+
+    let cancelled = reconcile(&mut history, vec![class(1, 10)], true, at(1));
+    assert_eq!(ids(&cancelled), [2]);
+    assert_eq!(cancelled[0].stamp.sequence, 1);
+    assert_eq!(cancelled[0].stamp.last_modified, at(1));
+
+    // Still announced within the retention, counted from when it vanished
+    let cancelled = reconcile(&mut history, vec![class(1, 10)], true, at(7 * 24));
+    assert_eq!(ids(&cancelled), [2]);
+
+    assert!(reconcile(&mut history, vec![class(1, 10)], true, at(9 * 24)).is_empty());
+    assert!(!history.events.contains_key("class-2"));
+  }
+
+  #[test]
+  fn reappearing_events_are_live_again() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 10)], true, at(0));
+    assert_eq!(ids(&reconcile(&mut history, Vec::new(), true, at(1))), [1]);
+
+    assert!(reconcile(&mut history, vec![class(1, 10)], true, at(2)).is_empty());
+    assert!(history.events["class-1"].vanished_at.is_none());
+  }
+
+  #[test]
+  fn events_outside_the_window_are_not_judged() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 10), class(2, 25)], true, at(0));
+
+    let revisions = history.reconcile(
+      Vec::new(),
+      true,
+      (day(1), day(15)),
+      at(1),
+      chrono::Duration::days(7),
+    );
+    assert_eq!(ids(&revisions.cancelled), [1]);
+    assert!(history.events["class-2"].vanished_at.is_none());
+  }
+
+  #[test]
+  fn a_narrower_window_forgets_nothing() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 2), class(2, 20)], true, at(0));
+    reconcile(&mut history, vec![class(2, 20)], true, at(1));
+
+    history.reconcile(
+      vec![class(2, 20)],
+      true,
+      (day(5), day(31)),
+      at(2),
+      chrono::Duration::days(7),
+    );
+    let stamp = history.events["class-1"].stamp;
+    assert_eq!(stamp.sequence, 1);
+    assert_eq!(stamp.created, at(0));
+  }
+
+  #[test]
+  fn long_past_events_are_forgotten() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 2), class(2, 20)], true, at(0));
+
+    let next_year = at(400 * 24);
+    history.reconcile(
+      Vec::new(),
+      true,
+      (next_year.date_naive(), next_year.date_naive()),
+      next_year,
+      chrono::Duration::days(7),
+    );
+    assert!(history.events.is_empty());
+  }
+
+  #[test]
+  fn exams_are_kept_when_their_fetch_failed() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 10), exam(9, 12)], true, at(0));
+
+    assert!(reconcile(&mut history, vec![class(1, 10)], false, at(1)).is_empty());
+    assert_eq!(
+      ids(&reconcile(&mut history, vec![class(1, 10)], true, at(2))),
+      [9]
+    );
+  }
+
+  #[test]
+  fn unchanged_events_keep_their_stamps() {
+    let mut history = CalendarHistory::default();
+    let first = reconcile_revisions(&mut history, vec![class(1, 10)], true, at(0));
+    let second = reconcile_revisions(&mut history, vec![class(1, 10)], true, at(5));
+
+    assert_eq!(first.stamps["class-1"], second.stamps["class-1"]);
+    assert_eq!(second.stamps["class-1"], EventStamp::new(at(0)));
+  }
+
+  #[test]
+  fn visible_changes_bump_the_sequence() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 10)], true, at(0));
+
+    let (uid, mut moved) = class(1, 10);
+    if let TrackedItem::Class(item) = &mut moved {
+      item.room_number = Some("B7".to_string());
+    }
+    let revisions = reconcile_revisions(&mut history, vec![(uid, moved)], true, at(3));
+    let stamp = revisions.stamps["class-1"];
+    assert_eq!(stamp.sequence, 1);
+    assert_eq!(stamp.created, at(0));
+    assert_eq!(stamp.last_modified, at(3));
+  }
+
+  #[test]
+  fn rescheduled_exams_are_updated_rather_than_cancelled() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![exam(9, 12)], true, at(0));
+
+    let (uid, mut moved) = exam(9, 14);
+    if let TrackedItem::Exam(exam) = &mut moved {
+      exam.time_confirmed = false;
+    }
+    let revisions = reconcile_revisions(&mut history, vec![(uid, moved)], true, at(3));
+    assert!(revisions.cancelled.is_empty());
+    assert_eq!(revisions.stamps["exam-9"].sequence, 1);
+  }
+
+  #[tokio::test]
+  async fn stamps_survive_a_restart() {
+    let window = (day(WINDOW.0), day(WINDOW.1));
+    let path = std::env::temp_dir().join(format!("ahe-ics-{}-history.json", std::process::id()));
+    let history = EventHistory::new(7, 1_000);
+    history
+      .observe(
+        "cal",
+        &[lecture(1, 10), lecture(2, 11)],
+        &[],
+        true,
+        window,
+        false,
+      )
+      .await;
+    let mut moved = lecture(1, 10);
+    moved.room_number = Some("B7".to_string());
+    let uid = class_uid("cal", &moved);
+    let served = history
+      .observe(
+        "cal",
+        &[moved.clone(), lecture(2, 11)],
+        &[],
+        true,
+        window,
+        false,
+      )
+      .await;
+    assert_eq!(history.save(&path).expect("save"), 1);
+
+    let restarted = EventHistory::new(7, 1_000);
+    assert_eq!(restarted.restore(&path).await.expect("restore"), 1);
+    std::fs::remove_file(&path).expect("remove history file");
+    let revisions = restarted
+      .observe("cal", &[moved], &[], true, window, false)
+      .await;
+    assert_eq!(revisions.stamps[&uid], served.stamps[&uid]);
+    assert_eq!(revisions.stamps[&uid].sequence, 1);
+    // The vanished class is still known, so it is cancelled rather than dropped
+    assert_eq!(ids(&revisions.cancelled), [2]);
+  }
+
+  #[tokio::test]
+  async fn a_missing_history_file_restores_nothing() {
+    let path = std::env::temp_dir().join(format!("ahe-ics-{}-no-history.json", std::process::id()));
+    let history = EventHistory::new(7, 1_000);
+    assert_eq!(history.restore(&path).await.expect("restore"), 0);
+  }
+
+  #[test]
+  fn sequences_count_changes_from_zero() {
+    let mut stamp = EventStamp::new(at(0));
+    assert_eq!(stamp.sequence, 0);
+    stamp.bump(at(0));
+    stamp.bump(at(0));
+    assert_eq!(stamp.sequence, 2);
+    // Two changes within one second still get distinct timestamps
+    assert_eq!(stamp.last_modified, at(0) + chrono::Duration::seconds(2));
+  }
+
+  /// Reconciles a compressed feed of `items`, as `EventHistory::observe` does
+  fn observe_series(
+    history: &mut CalendarHistory,
+    items: &[PlanItem],
+    now: DateTime<Utc>,
+  ) -> FeedRevisions {
+    let current = items
+      .iter()
+      .map(|item| (class_uid("cal", item), TrackedItem::Class(item.clone())))
+      .collect();
+    let served = recurrence::group(items)
+      .iter()
+      .filter_map(|entry| match entry {
+        PlanEntry::Series(series) => Some(ServedSeries::of("cal", series)),
+        PlanEntry::Single(_) => None,
+      })
+      .collect();
+    let mut revisions = reconcile_revisions(history, current, true, now);
+    history.reconcile_series(served, &mut revisions, now);
+    revisions
+  }
+
+  #[test]
+  fn series_masters_are_stamped_by_their_rule() {
+    let items: Vec<PlanItem> = [2, 9, 16, 23, 30]
+      .into_iter()
+      .zip(0..)
+      .map(|(on, id)| lecture(id, on))
+      .collect();
+    let mut history = CalendarHistory::default();
+    let first = observe_series(&mut history, &items, at(0));
+    let series = history.series.keys().next().expect("a series").clone();
+    assert_eq!(
+      observe_series(&mut history, &items, at(1)).stamps[&series],
+      first.stamps[&series]
+    );
+
+    // The last occurrence vanishes: COUNT shrinks and the master moves on,
+    // while the class stays an occurrence of the series it was served in
+    let shorter = observe_series(&mut history, &items[..4], at(2));
+    assert_eq!(shorter.stamps[&series].sequence, 1);
+    assert_eq!(
+      shorter.cancelled[0].series,
+      Some(SeriesSlot {
+        uid: series.clone(),
+        recurrence_id: items[4].starts_at,
+      })
+    );
+  }
+
+  #[test]
+  fn invisible_changes_do_not_bump_the_sequence() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 10)], true, at(0));
+
+    let (uid, mut recoloured) = class(1, 10);
+    if let TrackedItem::Class(item) = &mut recoloured {
+      item.form_color = Some("#abcdef".to_string());
+    }
+    let revisions = reconcile_revisions(&mut history, vec![(uid, recoloured)], true, at(3));
+    assert_eq!(revisions.stamps["class-1"].sequence, 0);
+  }
+
+  #[test]
+  fn reinstated_events_are_a_new_revision() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 10)], true, at(0));
+    reconcile(&mut history, Vec::new(), true, at(1));
+
+    let revisions = reconcile_revisions(&mut history, vec![class(1, 10)], true, at(2));
+    assert_eq!(revisions.stamps["class-1"].sequence, 2);
+  }
+
+  #[test]
+  fn zero_retention_serves_no_cancellations() {
+    let mut history = CalendarHistory::default();
+    reconcile(&mut history, vec![class(1, 10)], true, at(0));
+
+    let revisions = history.reconcile(
+      Vec::new(),
+      true,
+      (day(1), day(31)),
+      at(1),
+      chrono::Duration::zero(),
+    );
+    assert!(revisions.cancelled.is_empty());
+    assert!(history.events.is_empty());
+  }
+}
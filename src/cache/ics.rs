@@ -1,43 +1,357 @@
+use std::fmt::Write;
+use std::io;
+use std::sync::{Arc, OnceLock};
 use std::time::Duration;
 
-use chrono::NaiveDate;
+use axum::body::Bytes;
+use chrono::{DateTime, NaiveDate, SubsecRound, Utc};
 use moka::future::Cache;
+use sha2::{Digest, Sha256};
+
+use crate::cache::{CacheSnapshot, EvictionCounters};
+use crate::compression::ContentEncoding;
+use crate::config::CalendarLanguage;
+use crate::timezone::OutputZone;
 
 const ICS_CACHE_TTL_SECONDS: u64 = 600;
+/// Versions outlive the rendered bodies so `Last-Modified` survives re-renders
+const FEED_VERSION_TTL_SECONDS: u64 = 604_800;
+/// Versions are a few dozen bytes each, so a fixed bound is enough
+const FEED_VERSION_MAX_ENTRIES: u64 = 100_000;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct IcsCacheKey {
   pub student_id: i64,
   pub from: NaiveDate,
   pub to: NaiveDate,
+  /// Feeds in different languages are different bodies
+  pub lang: CalendarLanguage,
+  /// So are feeds written in different zones
+  pub zone: OutputZone,
+}
+
+/// Output format a feed version is tracked for
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum FeedFormat {
+  Ics,
+  Json,
+  Jcal,
+  Xcal,
+}
+
+/// Validators sent with a rendered feed
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FeedVersion {
+  /// Strong, quoted entity tag derived from the body bytes
+  pub etag: String,
+  /// When the body for this key last changed, truncated to whole seconds
+  pub last_modified: DateTime<Utc>,
 }
 
 #[derive(Clone, Debug)]
-pub struct IcsCache {
-  inner: Cache<IcsCacheKey, String>,
+pub struct CachedCalendar {
+  pub body: Bytes,
+  pub version: FeedVersion,
+  /// Compressed variants, filled on first request and shared by every clone
+  encoded: Arc<EncodedBodies>,
+}
+
+#[derive(Debug, Default)]
+struct EncodedBodies {
+  gzip: OnceLock<Bytes>,
+  brotli: OnceLock<Bytes>,
+  zstd: OnceLock<Bytes>,
 }
 
-impl Default for IcsCache {
-  fn default() -> Self {
-    Self::new()
+impl CachedCalendar {
+  /// Returns the body compressed with `encoding`, compressing it at most once
+  /// per cache entry.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if compression fails.
+  pub fn encoded(&self, encoding: ContentEncoding) -> io::Result<Bytes> {
+    let slot = match encoding {
+      ContentEncoding::Gzip => &self.encoded.gzip,
+      ContentEncoding::Brotli => &self.encoded.brotli,
+      ContentEncoding::Zstd => &self.encoded.zstd,
+    };
+
+    if let Some(bytes) = slot.get() {
+      return Ok(bytes.clone());
+    }
+
+    let compressed = Bytes::from(encoding.compress(&self.body)?);
+    // A concurrent request may have won the race; either result is identical
+    Ok(slot.get_or_init(|| compressed).clone())
   }
 }
 
+#[derive(Clone, Debug)]
+pub struct IcsCache {
+  inner: Cache<IcsCacheKey, CachedCalendar>,
+  versions: Cache<(IcsCacheKey, FeedFormat), FeedVersion>,
+  max_bytes: u64,
+  evictions: Arc<EvictionCounters>,
+}
+
 impl IcsCache {
+  /// Creates a cache whose rendered bodies add up to at most `max_bytes`
   #[must_use]
-  pub fn new() -> Self {
+  pub fn new(max_bytes: u64) -> Self {
+    let evictions = Arc::new(EvictionCounters::default());
     Self {
       inner: Cache::builder()
+        .max_capacity(max_bytes)
+        .weigher(|_, calendar: &CachedCalendar| calendar_weight(calendar))
         .time_to_live(Duration::from_secs(ICS_CACHE_TTL_SECONDS))
+        .eviction_listener(EvictionCounters::listener(&evictions))
+        .build(),
+      versions: Cache::builder()
+        .max_capacity(FEED_VERSION_MAX_ENTRIES)
+        .time_to_live(Duration::from_secs(FEED_VERSION_TTL_SECONDS))
         .build(),
+      max_bytes,
+      evictions,
     }
   }
 
-  pub async fn get(&self, key: &IcsCacheKey) -> Option<String> {
+  #[must_use]
+  pub fn snapshot(&self) -> CacheSnapshot {
+    CacheSnapshot::new(
+      "ics",
+      self.inner.entry_count(),
+      self.inner.weighted_size(),
+      self.max_bytes,
+      &self.evictions,
+    )
+  }
+
+  pub async fn get(&self, key: &IcsCacheKey) -> Option<CachedCalendar> {
     self.inner.get(key).await
   }
 
-  pub async fn insert(&self, key: IcsCacheKey, value: String) {
-    self.inner.insert(key, value).await;
+  /// Returns the cached calendar for `key`, running `render` on a miss.
+  ///
+  /// Concurrent misses for the same key wait for a single `render`, so
+  /// subscribers polling at the same moment cost one upstream fetch.
+  ///
+  /// # Errors
+  ///
+  /// Returns the error from `render`, shared by every caller that waited on it.
+  pub async fn get_or_render<F, E>(
+    &self,
+    key: IcsCacheKey,
+    render: F,
+  ) -> Result<CachedCalendar, Arc<E>>
+  where
+    F: Future<Output = Result<String, E>>,
+    E: Send + Sync + 'static,
+  {
+    self
+      .inner
+      .try_get_with(key.clone(), async {
+        let body = render.await?;
+        Ok(self.versioned(key, body).await)
+      })
+      .await
+  }
+
+  /// Stores a freshly rendered calendar and returns it with its version.
+  pub async fn insert(&self, key: IcsCacheKey, body: String) -> CachedCalendar {
+    let cached = self.versioned(key.clone(), body).await;
+    self.inner.insert(key, cached.clone()).await;
+
+    cached
+  }
+
+  async fn versioned(&self, key: IcsCacheKey, body: String) -> CachedCalendar {
+    let version = self
+      .track_version(key, FeedFormat::Ics, body.as_bytes())
+      .await;
+
+    CachedCalendar {
+      body: Bytes::from(body),
+      version,
+      encoded: Arc::default(),
+    }
+  }
+
+  /// Returns the version for a rendered body, keeping the previous
+  /// `last_modified` when the bytes are unchanged.
+  pub async fn track_version(
+    &self,
+    key: IcsCacheKey,
+    format: FeedFormat,
+    body: &[u8],
+  ) -> FeedVersion {
+    let etag = strong_etag(body);
+    let key = (key, format);
+
+    if let Some(previous) = self.versions.get(&key).await
+      && previous.etag == etag
+    {
+      return previous;
+    }
+
+    let version = FeedVersion {
+      etag,
+      last_modified: Utc::now().trunc_subsecs(0),
+    };
+    self.versions.insert(key, version.clone()).await;
+
+    version
+  }
+}
+
+/// Body size plus headroom for the compressed variants filled in later
+fn calendar_weight(calendar: &CachedCalendar) -> u32 {
+  let bytes = calendar.body.len().saturating_add(calendar.body.len() / 2);
+  u32::try_from(bytes).unwrap_or(u32::MAX)
+}
+
+fn strong_etag(body: &[u8]) -> String {
+  let digest = Sha256::digest(body);
+  let mut etag = String::with_capacity(34);
+  etag.push('"');
+  for byte in &digest[..16] {
+    let _ = write!(etag, "{byte:02x}");
+  }
+  etag.push('"');
+
+  etag
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::atomic::{AtomicUsize, Ordering};
+
+  use super::*;
+
+  const TEST_MAX_BYTES: u64 = 1 << 20;
+
+  fn key() -> IcsCacheKey {
+    let day = NaiveDate::from_ymd_opt(2026, 1, 15).expect("valid date");
+    IcsCacheKey {
+      student_id: 1,
+      from: day,
+      to: day,
+      lang: CalendarLanguage::PL,
+      zone: OutputZone::Floating,
+    }
+  }
+
+  #[test]
+  fn etag_is_quoted_and_content_addressed() {
+    let etag = strong_etag(b"BEGIN:VCALENDAR");
+
+    assert_eq!(etag.len(), 34);
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+    assert_eq!(etag, strong_etag(b"BEGIN:VCALENDAR"));
+    assert_ne!(etag, strong_etag(b"BEGIN:VCALENDAR\r\n"));
+  }
+
+  #[tokio::test]
+  async fn compressed_variants_are_shared_between_clones() {
+    let cache = IcsCache::new(TEST_MAX_BYTES);
+    cache.insert(key(), "BEGIN:VCALENDAR\r\n".repeat(100)).await;
+
+    let first = cache.get(&key()).await.expect("cached");
+    let gzip = first.encoded(ContentEncoding::Gzip).expect("compresses");
+    let second = cache.get(&key()).await.expect("cached");
+
+    assert!(second.encoded.gzip.get().is_some());
+    assert_eq!(second.encoded(ContentEncoding::Gzip).expect("cached"), gzip);
+    assert!(second.encoded.zstd.get().is_none());
+  }
+
+  #[tokio::test]
+  async fn languages_and_zones_are_cached_separately() {
+    let cache = IcsCache::new(TEST_MAX_BYTES);
+    cache
+      .insert(key(), "X-WR-CALNAME:Plan AHE".to_string())
+      .await;
+
+    let english = IcsCacheKey {
+      lang: CalendarLanguage::EN,
+      ..key()
+    };
+    let utc = IcsCacheKey {
+      zone: OutputZone::Utc,
+      ..key()
+    };
+    assert!(cache.get(&english).await.is_none());
+    assert!(cache.get(&utc).await.is_none());
+    assert!(cache.get(&key()).await.is_some());
+  }
+
+  #[tokio::test]
+  async fn concurrent_misses_render_once() {
+    let cache = IcsCache::new(TEST_MAX_BYTES);
+    let renders = AtomicUsize::new(0);
+    let render = || async {
+      renders.fetch_add(1, Ordering::SeqCst);
+      tokio::time::sleep(Duration::from_millis(20)).await;
+      Ok::<_, io::Error>("BEGIN:VCALENDAR".to_string())
+    };
+
+    let (first, second, third) = tokio::join!(
+      cache.get_or_render(key(), render()),
+      cache.get_or_render(key(), render()),
+      cache.get_or_render(key(), render()),
+    );
+
+    assert_eq!(renders.load(Ordering::SeqCst), 1);
+    let first = first.expect("rendered");
+    assert_eq!(first.version, second.expect("shared").version);
+    assert_eq!(first.version, third.expect("shared").version);
+  }
+
+  #[tokio::test]
+  async fn failed_render_is_not_cached() {
+    let cache = IcsCache::new(TEST_MAX_BYTES);
+    let failed = cache
+      .get_or_render(key(), async { Err(io::Error::other("upstream down")) })
+      .await;
+    assert!(failed.is_err());
+
+    let retried = cache
+      .get_or_render(key(), async {
+        Ok::<_, io::Error>("BEGIN:VCALENDAR".to_string())
+      })
+      .await;
+    assert!(retried.is_ok());
+  }
+
+  #[tokio::test]
+  async fn unchanged_body_keeps_its_last_modified() {
+    let cache = IcsCache::new(TEST_MAX_BYTES);
+    let first = cache.track_version(key(), FeedFormat::Ics, b"a").await;
+    let second = cache.track_version(key(), FeedFormat::Ics, b"a").await;
+
+    assert_eq!(first, second);
+  }
+
+  #[tokio::test]
+  async fn changed_body_gets_a_new_etag() {
+    let cache = IcsCache::new(TEST_MAX_BYTES);
+    let first = cache.track_version(key(), FeedFormat::Ics, b"a").await;
+    let second = cache.track_version(key(), FeedFormat::Ics, b"b").await;
+
+    assert_ne!(first.etag, second.etag);
+    assert!(second.last_modified >= first.last_modified);
+  }
+
+  #[tokio::test]
+  async fn formats_are_tracked_separately() {
+    let cache = IcsCache::new(TEST_MAX_BYTES);
+    cache.track_version(key(), FeedFormat::Ics, b"a").await;
+    let json = cache.track_version(key(), FeedFormat::Json, b"b").await;
+
+    assert_eq!(json.etag, strong_etag(b"b"));
+    assert_eq!(
+      cache.track_version(key(), FeedFormat::Ics, b"a").await.etag,
+      strong_etag(b"a")
+    );
   }
 }
@@ -0,0 +1,84 @@
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use moka::notification::RemovalCause;
+
+/// Removal counters for one cache, fed by its eviction listener
+#[derive(Debug, Default)]
+pub struct EvictionCounters {
+  size: AtomicU64,
+  expired: AtomicU64,
+}
+
+impl EvictionCounters {
+  /// Listener for `CacheBuilder::eviction_listener`; replacements and explicit
+  /// invalidations are not evictions and are ignored.
+  pub(crate) fn listener<K, V>(
+    counters: &Arc<Self>,
+  ) -> impl Fn(Arc<K>, V, RemovalCause) + Send + Sync + 'static {
+    let counters = Arc::clone(counters);
+    move |_, _, cause| counters.record(cause)
+  }
+
+  fn record(&self, cause: RemovalCause) {
+    match cause {
+      RemovalCause::Size => self.size.fetch_add(1, Ordering::Relaxed),
+      RemovalCause::Expired => self.expired.fetch_add(1, Ordering::Relaxed),
+      RemovalCause::Explicit | RemovalCause::Replaced => return,
+    };
+  }
+}
+
+/// Point-in-time view of one cache, as reported by `/metrics`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheSnapshot {
+  pub name: &'static str,
+  pub entries: u64,
+  /// Bytes for weighed caches, otherwise equal to `entries`
+  pub weighted_size: u64,
+  pub capacity: u64,
+  pub size_evictions: u64,
+  pub expirations: u64,
+}
+
+impl CacheSnapshot {
+  pub(crate) fn new(
+    name: &'static str,
+    entries: u64,
+    weighted_size: u64,
+    capacity: u64,
+    counters: &EvictionCounters,
+  ) -> Self {
+    Self {
+      name,
+      entries,
+      weighted_size,
+      capacity,
+      size_evictions: counters.size.load(Ordering::Relaxed),
+      expirations: counters.expired.load(Ordering::Relaxed),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn only_evictions_are_counted() {
+    let counters = EvictionCounters::default();
+    for cause in [
+      RemovalCause::Size,
+      RemovalCause::Size,
+      RemovalCause::Expired,
+      RemovalCause::Explicit,
+      RemovalCause::Replaced,
+    ] {
+      counters.record(cause);
+    }
+
+    let snapshot = CacheSnapshot::new("ics", 0, 0, 1, &counters);
+    assert_eq!(snapshot.size_evictions, 2);
+    assert_eq!(snapshot.expirations, 1);
+  }
+}
@@ -1,8 +1,14 @@
+mod history;
 mod ics;
+mod metrics;
 mod student;
 mod token;
 
-pub use ics::{IcsCache, IcsCacheKey};
+pub use history::{
+  CancelledItem, EventHistory, EventStamp, FeedRevisions, SeriesSlot, TrackedItem,
+};
+pub use ics::{CachedCalendar, FeedFormat, FeedVersion, IcsCache, IcsCacheKey};
+pub use metrics::{CacheSnapshot, EvictionCounters};
 pub use student::{StudentContext, StudentContextCache};
 pub use token::{TokenCache, TokenCacheEntry};
 
@@ -1,38 +1,59 @@
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
 use moka::future::Cache;
 use tracing::{debug, warn};
 
-use crate::api::ApiClient;
-use crate::cache::{CredentialKey, credential_key};
-use crate::models::StudentIndex;
+use crate::api::{ApiClient, ApiError};
+use crate::cache::{CacheSnapshot, CredentialKey, EvictionCounters, credential_key};
+use crate::config::IndexSelector;
+use crate::models::{ExamIndex, StudentIndex};
 
 const STUDENT_CONTEXT_CACHE_TTL_SECONDS: u64 = 21_600;
 
 #[derive(Clone, Debug)]
 pub struct StudentContext {
   pub student_id: i64,
-  pub index_id: Option<i64>,
-  pub section_name: Option<String>,
+  /// Empty when exams are disabled or no indeks could be resolved
+  pub exam_indexes: Vec<ExamIndex>,
 }
 
 /// Per-user student metadata cache, keyed by the full credential pair
 pub struct StudentContextCache {
   inner: Cache<CredentialKey, StudentContext>,
+  max_entries: u64,
+  evictions: Arc<EvictionCounters>,
 }
 
-impl Default for StudentContextCache {
-  fn default() -> Self {
+impl StudentContextCache {
+  /// Creates a cache holding at most `max_entries` student contexts
+  #[must_use]
+  pub fn new(max_entries: u64) -> Self {
+    let evictions = Arc::new(EvictionCounters::default());
     Self {
       inner: Cache::builder()
+        .max_capacity(max_entries)
         .time_to_live(Duration::from_secs(STUDENT_CONTEXT_CACHE_TTL_SECONDS))
+        .eviction_listener(EvictionCounters::listener(&evictions))
         .build(),
+      max_entries,
+      evictions,
     }
   }
-}
 
-impl StudentContextCache {
+  #[must_use]
+  pub fn snapshot(&self) -> CacheSnapshot {
+    let entries = self.inner.entry_count();
+    CacheSnapshot::new(
+      "student_context",
+      entries,
+      entries,
+      self.max_entries,
+      &self.evictions,
+    )
+  }
+
   /// Returns cached student metadata for the given user, fetching from API when needed
   ///
   /// # Errors
@@ -43,6 +64,7 @@ impl StudentContextCache {
     username: &str,
     password: &str,
     exams_enabled: bool,
+    selectors: &[IndexSelector],
     api: &ApiClient,
     access_token: &str,
   ) -> Result<StudentContext> {
@@ -54,56 +76,181 @@ impl StudentContextCache {
       return Ok(ctx);
     }
 
-    debug!("student context cache miss, fetching from API");
-    let student_data = api.get_student_data(access_token).await?;
-    let student_id = student_data.student_id;
+    // Concurrent misses for the same credentials share one lookup
+    let ctx = self
+      .inner
+      .try_get_with(
+        key,
+        fetch_student_context(api, access_token, exams_enabled, selectors),
+      )
+      .await
+      .map_err(Arc::unwrap_or_clone)?;
 
-    let mut index_id = if exams_enabled {
-      student_data.index_id
-    } else {
-      None
-    };
-    let mut section_name = None;
-
-    if exams_enabled && index_id.is_none() {
-      match api.get_student_indexes(access_token).await {
-        Ok(indexes) => {
-          if let Some(index) = pick_index(&indexes) {
-            index_id = Some(index.index_id);
-            section_name.clone_from(&index.section_name);
-            debug!(
-              student_id,
-              index_id = index.index_id,
-              "IndeksID resolved from indeks list"
-            );
-          } else {
-            warn!(student_id, "student indeks list is empty, skipping exams");
-          }
-        }
-        Err(error) => {
-          warn!(student_id, error = %error, "failed to fetch indeks list, skipping exams");
-        }
-      }
-    }
+    Ok(ctx)
+  }
+}
+
+async fn fetch_student_context(
+  api: &ApiClient,
+  access_token: &str,
+  exams_enabled: bool,
+  selectors: &[IndexSelector],
+) -> Result<StudentContext, ApiError> {
+  debug!("student context cache miss, fetching from API");
+  let student_data = api.get_student_data(access_token).await?;
+  let student_id = student_data.student_id;
 
-    let ctx = StudentContext {
+  if !exams_enabled {
+    return Ok(StudentContext {
       student_id,
-      index_id,
-      section_name,
-    };
-    self.inner.insert(key, ctx.clone()).await;
+      exam_indexes: Vec::new(),
+    });
+  }
 
-    Ok(ctx)
+  let mut exam_indexes = match api.get_student_indexes(access_token).await {
+    Ok(indexes) => select_indexes(&indexes, selectors),
+    Err(error) => {
+      warn!(student_id, error = %error, "failed to fetch indeks list");
+      Vec::new()
+    }
+  };
+
+  if exam_indexes.is_empty() {
+    if !selectors.is_empty() {
+      warn!(
+        student_id,
+        "no indeks matches AHE_CAL_EXAM_INDEXES, skipping exams"
+      );
+    } else if let Some(index_id) = student_data.index_id {
+      // The student record names one indeks even when the list is unavailable
+      exam_indexes.push(ExamIndex {
+        index_id,
+        section_name: None,
+        programme: None,
+      });
+    } else {
+      warn!(student_id, "student indeks list is empty, skipping exams");
+    }
   }
+
+  debug!(
+    student_id,
+    indexes = ?exam_indexes.iter().map(|index| index.index_id).collect::<Vec<_>>(),
+    "exam indexes resolved"
+  );
+  Ok(StudentContext {
+    student_id,
+    exam_indexes,
+  })
+}
+
+/// Indexes to fetch exams for: those matching a configured selector, otherwise
+/// every active one, otherwise the most recent one.
+fn select_indexes(indexes: &[StudentIndex], selectors: &[IndexSelector]) -> Vec<ExamIndex> {
+  let selected: Vec<&StudentIndex> = if selectors.is_empty() {
+    let active: Vec<&StudentIndex> = indexes.iter().filter(|item| is_active(item)).collect();
+    if active.is_empty() {
+      pick_index(indexes).into_iter().collect()
+    } else {
+      active
+    }
+  } else {
+    indexes
+      .iter()
+      .filter(|item| {
+        selectors
+          .iter()
+          .any(|selector| selector.matches(item.index_id, item.section_name.as_deref()))
+      })
+      .collect()
+  };
+
+  selected
+    .into_iter()
+    .map(|item| ExamIndex {
+      index_id: item.index_id,
+      section_name: item.section_name.clone(),
+      programme: item
+        .programme_name
+        .clone()
+        .or_else(|| item.section_name.clone()),
+    })
+    .collect()
+}
+
+fn is_active(index: &StudentIndex) -> bool {
+  index.status_symbol.as_deref() == Some("S")
 }
 
 fn pick_index(indexes: &[StudentIndex]) -> Option<&StudentIndex> {
   indexes.iter().max_by_key(|item| {
     (
-      item.status_symbol.as_deref() == Some("S"),
+      is_active(item),
       item.year.unwrap_or_default(),
       item.semester.unwrap_or_default(),
       item.index_id,
     )
   })
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn index(index_id: i64, status: &str, section: &str, programme: Option<&str>) -> StudentIndex {
+    StudentIndex {
+      index_id,
+      status_symbol: Some(status.to_string()),
+      year: Some(1),
+      semester: Some(1),
+      section_name: Some(section.to_string()),
+      programme_name: programme.map(str::to_string),
+    }
+  }
+
+  fn ids(selected: &[ExamIndex]) -> Vec<i64> {
+    selected.iter().map(|item| item.index_id).collect()
+  }
+
+  #[test]
+  fn every_active_index_is_selected_by_default() {
+    let indexes = [
+      index(1, "S", "IN1", Some("Informatyka")),
+      index(2, "A", "ZA1", Some("Zarządzanie")),
+      index(3, "S", "PD1", None),
+    ];
+
+    let selected = select_indexes(&indexes, &[]);
+
+    assert_eq!(ids(&selected), vec![1, 3]);
+    assert_eq!(selected[0].programme.as_deref(), Some("Informatyka"));
+    // Without a programme name the section is the best label we have
+    assert_eq!(selected[1].programme.as_deref(), Some("PD1"));
+  }
+
+  #[test]
+  fn the_latest_index_is_used_when_none_is_active() {
+    let mut older = index(1, "A", "IN1", None);
+    older.year = Some(1);
+    let mut newer = index(2, "A", "IN2", None);
+    newer.year = Some(3);
+
+    assert_eq!(ids(&select_indexes(&[older, newer], &[])), vec![2]);
+  }
+
+  #[test]
+  fn selectors_pick_by_id_or_section_regardless_of_status() {
+    let indexes = [
+      index(1, "S", "IN1", None),
+      index(2, "A", "ZA1", None),
+      index(3, "S", "PD1", None),
+    ];
+    let selectors = [
+      IndexSelector::Id(2),
+      IndexSelector::Section("pd1".to_string()),
+    ];
+
+    assert_eq!(ids(&select_indexes(&indexes, &selectors)), vec![2, 3]);
+    assert!(select_indexes(&indexes, &[IndexSelector::Id(9)]).is_empty());
+  }
+}
@@ -1,4 +1,5 @@
 use std::fmt;
+use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::Result;
@@ -6,8 +7,8 @@ use chrono::{DateTime, Utc};
 use moka::future::Cache;
 use tracing::debug;
 
-use crate::api::ApiClient;
-use crate::cache::{CredentialKey, credential_key};
+use crate::api::{ApiClient, ApiError};
+use crate::cache::{CacheSnapshot, CredentialKey, EvictionCounters, credential_key};
 
 const TOKEN_REFRESH_GRACE_SECONDS: u64 = 30;
 
@@ -30,19 +31,32 @@ impl fmt::Debug for TokenCacheEntry {
 /// Per-user WPS access token cache, keyed by the full credential pair
 pub struct TokenCache {
   inner: Cache<CredentialKey, TokenCacheEntry>,
+  max_entries: u64,
+  evictions: Arc<EvictionCounters>,
 }
 
-impl Default for TokenCache {
-  fn default() -> Self {
+impl TokenCache {
+  /// Creates a cache holding at most `max_entries` tokens
+  #[must_use]
+  pub fn new(max_entries: u64) -> Self {
+    let evictions = Arc::new(EvictionCounters::default());
     Self {
       inner: Cache::builder()
+        .max_capacity(max_entries)
         .time_to_live(Duration::from_secs(86400))
+        .eviction_listener(EvictionCounters::listener(&evictions))
         .build(),
+      max_entries,
+      evictions,
     }
   }
-}
 
-impl TokenCache {
+  #[must_use]
+  pub fn snapshot(&self) -> CacheSnapshot {
+    let entries = self.inner.entry_count();
+    CacheSnapshot::new("token", entries, entries, self.max_entries, &self.evictions)
+  }
+
   /// Returns a valid WPS access token for the given credentials, logging in only when needed
   ///
   /// # Errors
@@ -64,21 +78,27 @@ impl TokenCache {
       self.inner.invalidate(&key).await;
     }
 
-    debug!("token cache miss, logging in");
-    let token_resp = api.login(username, password).await?;
-    let refresh_grace = token_resp
-      .expires_in
-      .saturating_sub(TOKEN_REFRESH_GRACE_SECONDS);
-    let expires_at =
-      Utc::now() + chrono::Duration::seconds(i64::try_from(refresh_grace).unwrap_or(i64::MAX));
+    // Concurrent misses for the same credentials share a single WPS login
+    let entry = self
+      .inner
+      .try_get_with(key, async {
+        debug!("token cache miss, logging in");
+        let token_resp = api.login(username, password).await?;
+        let refresh_grace = token_resp
+          .expires_in
+          .saturating_sub(TOKEN_REFRESH_GRACE_SECONDS);
+        let expires_at =
+          Utc::now() + chrono::Duration::seconds(i64::try_from(refresh_grace).unwrap_or(i64::MAX));
 
-    let entry = TokenCacheEntry {
-      token: token_resp.access_token.clone(),
-      expires_at,
-    };
-    self.inner.insert(key, entry).await;
+        Ok::<_, ApiError>(TokenCacheEntry {
+          token: token_resp.access_token,
+          expires_at,
+        })
+      })
+      .await
+      .map_err(Arc::unwrap_or_clone)?;
 
-    Ok(token_resp.access_token)
+    Ok(entry.token)
   }
 }
 
@@ -0,0 +1,119 @@
+use std::io::{self, Write};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+
+/// Brotli quality and window; mid-range keeps on-the-fly JSON compression cheap
+const BROTLI_QUALITY: u32 = 6;
+const BROTLI_WINDOW_BITS: u32 = 22;
+const BROTLI_BUFFER_SIZE: usize = 4096;
+const ZSTD_LEVEL: i32 = 6;
+
+/// HTTP content codings the calendar endpoints can produce.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum ContentEncoding {
+  Gzip,
+  Brotli,
+  Zstd,
+}
+
+impl ContentEncoding {
+  /// Token used in `Accept-Encoding` / `Content-Encoding`.
+  #[must_use]
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Gzip => "gzip",
+      Self::Brotli => "br",
+      Self::Zstd => "zstd",
+    }
+  }
+
+  /// Compresses a whole body in memory.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the encoder fails to write its output.
+  pub fn compress(self, body: &[u8]) -> io::Result<Vec<u8>> {
+    match self {
+      Self::Gzip => {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body)?;
+        encoder.finish()
+      }
+      Self::Brotli => {
+        let mut output = Vec::new();
+        {
+          let mut encoder = brotli::CompressorWriter::new(
+            &mut output,
+            BROTLI_BUFFER_SIZE,
+            BROTLI_QUALITY,
+            BROTLI_WINDOW_BITS,
+          );
+          encoder.write_all(body)?;
+          encoder.flush()?;
+        }
+        Ok(output)
+      }
+      Self::Zstd => zstd::encode_all(body, ZSTD_LEVEL),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::io::Read;
+
+  use super::*;
+
+  fn sample() -> Vec<u8> {
+    "BEGIN:VEVENT\r\nSUMMARY:Algebra [Wyklad W]\r\nEND:VEVENT\r\n"
+      .repeat(200)
+      .into_bytes()
+  }
+
+  #[test]
+  fn gzip_round_trips() {
+    let compressed = ContentEncoding::Gzip
+      .compress(&sample())
+      .expect("compresses");
+    let mut decoded = Vec::new();
+    flate2::read::GzDecoder::new(compressed.as_slice())
+      .read_to_end(&mut decoded)
+      .expect("decodes");
+
+    assert_eq!(decoded, sample());
+    assert!(compressed.len() < sample().len() / 10);
+  }
+
+  #[test]
+  fn brotli_round_trips() {
+    let compressed = ContentEncoding::Brotli
+      .compress(&sample())
+      .expect("compresses");
+    let mut decoded = Vec::new();
+    brotli::Decompressor::new(compressed.as_slice(), BROTLI_BUFFER_SIZE)
+      .read_to_end(&mut decoded)
+      .expect("decodes");
+
+    assert_eq!(decoded, sample());
+    assert!(compressed.len() < sample().len() / 10);
+  }
+
+  #[test]
+  fn zstd_round_trips() {
+    let compressed = ContentEncoding::Zstd
+      .compress(&sample())
+      .expect("compresses");
+    let decoded = zstd::decode_all(compressed.as_slice()).expect("decodes");
+
+    assert_eq!(decoded, sample());
+    assert!(compressed.len() < sample().len() / 10);
+  }
+
+  #[test]
+  fn tokens_match_the_http_registry() {
+    assert_eq!(ContentEncoding::Gzip.as_str(), "gzip");
+    assert_eq!(ContentEncoding::Brotli.as_str(), "br");
+    assert_eq!(ContentEncoding::Zstd.as_str(), "zstd");
+  }
+}
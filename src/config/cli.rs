@@ -0,0 +1,105 @@
+use std::path::PathBuf;
+
+use anyhow::{Result, bail};
+
+const USAGE: &str = "usage: [--config <path>] [--print-config]";
+
+/// Command-line options shared by both binaries
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CliOptions {
+  /// TOML or YAML file with settings; environment variables still win
+  pub config_path: Option<PathBuf>,
+  /// Print the effective configuration (secrets redacted) and exit
+  pub print_config: bool,
+}
+
+impl CliOptions {
+  /// Reads the process arguments, falling back to `AHE_CONFIG` for the file.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error for unknown arguments or a `--config` without a path.
+  pub fn from_args() -> Result<Self> {
+    parse_args(
+      std::env::args().skip(1),
+      std::env::var_os("AHE_CONFIG").map(PathBuf::from),
+    )
+  }
+}
+
+fn parse_args(
+  args: impl IntoIterator<Item = String>,
+  env_config: Option<PathBuf>,
+) -> Result<CliOptions> {
+  let mut options = CliOptions::default();
+  let mut args = args.into_iter();
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--print-config" => options.print_config = true,
+      "--config" => {
+        let Some(path) = args.next() else {
+          bail!("--config requires a path; {USAGE}");
+        };
+        options.config_path = Some(path.into());
+      }
+      _ => match arg.strip_prefix("--config=") {
+        Some("") => bail!("--config requires a path; {USAGE}"),
+        Some(path) => options.config_path = Some(path.into()),
+        None => bail!("unknown argument `{arg}`; {USAGE}"),
+      },
+    }
+  }
+
+  if options.config_path.is_none() {
+    options.config_path = env_config.filter(|path| !path.as_os_str().is_empty());
+  }
+
+  Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn parse(args: &[&str], env_config: Option<&str>) -> Result<CliOptions> {
+    parse_args(
+      args.iter().map(ToString::to_string),
+      env_config.map(PathBuf::from),
+    )
+  }
+
+  #[test]
+  fn no_arguments_means_environment_only() {
+    assert_eq!(parse(&[], None).expect("valid"), CliOptions::default());
+  }
+
+  #[test]
+  fn config_path_accepts_both_spellings() {
+    let separate = parse(&["--config", "/etc/ahe-ics.toml"], None).expect("valid");
+    let joined = parse(&["--config=/etc/ahe-ics.toml", "--print-config"], None).expect("valid");
+
+    assert_eq!(separate.config_path, Some("/etc/ahe-ics.toml".into()));
+    assert_eq!(joined.config_path, Some("/etc/ahe-ics.toml".into()));
+    assert!(joined.print_config);
+  }
+
+  #[test]
+  fn flag_overrides_ahe_config() {
+    let options = parse(&["--config", "cli.toml"], Some("env.toml")).expect("valid");
+    assert_eq!(options.config_path, Some("cli.toml".into()));
+
+    let options = parse(&[], Some("env.toml")).expect("valid");
+    assert_eq!(options.config_path, Some("env.toml".into()));
+
+    let options = parse(&[], Some("")).expect("valid");
+    assert_eq!(options.config_path, None);
+  }
+
+  #[test]
+  fn malformed_arguments_are_rejected() {
+    assert!(parse(&["--config"], None).is_err());
+    assert!(parse(&["--config="], None).is_err());
+    assert!(parse(&["--verbose"], None).is_err());
+  }
+}
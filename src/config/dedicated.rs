@@ -1,91 +1,277 @@
 use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 
 use super::ServerSettings;
 use super::parse;
-use super::types::{CalendarLanguage, CalendarToken};
+use super::source::Source;
+use super::types::{
+  CacheLimits, CalendarLanguage, CalendarToken, ExamTimeDefaults, IndexSelector, Reloadable,
+  TlsSettings, UnconfirmedExamTime,
+};
+use crate::i18n::Catalogs;
+use crate::locations::LocationTable;
+use crate::templates::Templates;
+use crate::timezone::OutputZone;
 
-/// Dedicated configuration
+/// Settings only the dedicated binary understands
+const DEDICATED_KEYS: &[&str] = &[
+  "AHE_USERNAME",
+  "AHE_USERNAME_FILE",
+  "AHE_PASSWORD",
+  "AHE_PASSWORD_FILE",
+];
+
+/// WPS login of the dedicated account, reloaded as one pair so a request never
+/// sees the new username with the old password
 #[derive(Clone)]
-pub struct Config {
+pub struct Credentials {
   pub username: String,
   pub password: String,
+}
+
+/// Keeps the username visible while never printing the password.
+impl fmt::Debug for Credentials {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    formatter
+      .debug_struct("Credentials")
+      .field("username", &self.username)
+      .field("password", &"<redacted>")
+      .finish()
+  }
+}
+
+/// Dedicated configuration
+#[derive(Clone)]
+pub struct Config {
+  pub credentials: Reloadable<Credentials>,
   pub bind_addr: String,
   pub calendar_past_days: i64,
   pub calendar_future_days: i64,
-  pub calendar_token: Option<CalendarToken>,
+  pub calendar_max_range_days: i64,
+  pub calendar_cancelled_days: i64,
+  pub history_file: Option<PathBuf>,
+  pub calendar_token: Reloadable<Option<CalendarToken>>,
   pub calendar_lang: CalendarLanguage,
+  pub calendar_timezone: OutputZone,
+  pub catalogs: Catalogs,
+  pub compress_series: bool,
+  pub merge_gap_minutes: Option<i64>,
+  pub locations: LocationTable,
+  pub templates: Templates,
   pub exams_enabled: bool,
+  pub exam_indexes: Vec<IndexSelector>,
+  pub exam_unconfirmed: UnconfirmedExamTime,
+  pub exam_time_defaults: ExamTimeDefaults,
   pub json_enabled: bool,
   pub real_ip_header: Option<String>,
+  pub tls: Option<TlsSettings>,
+  pub http_redirect_addr: Option<String>,
+  pub https_public_port: Option<u16>,
+  pub shutdown_timeout: Duration,
+  pub cache_limits: CacheLimits,
+  pub config_file: Option<PathBuf>,
 }
 
 impl fmt::Debug for Config {
   fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
     formatter
       .debug_struct("Config")
-      .field("username", &self.username)
-      .field("password", &"<redacted>")
+      .field("credentials", &self.credentials)
       .field("bind_addr", &self.bind_addr)
       .field("calendar_past_days", &self.calendar_past_days)
       .field("calendar_future_days", &self.calendar_future_days)
+      .field("calendar_max_range_days", &self.calendar_max_range_days)
+      .field("calendar_cancelled_days", &self.calendar_cancelled_days)
+      .field("history_file", &self.history_file)
       .field("calendar_token", &self.calendar_token)
       .field("calendar_lang", &self.calendar_lang)
+      .field("calendar_timezone", &self.calendar_timezone)
+      .field("catalogs", &self.catalogs)
+      .field("compress_series", &self.compress_series)
+      .field("merge_gap_minutes", &self.merge_gap_minutes)
+      .field("locations", &self.locations)
+      .field("templates", &self.templates)
       .field("exams_enabled", &self.exams_enabled)
+      .field("exam_indexes", &self.exam_indexes)
+      .field("exam_unconfirmed", &self.exam_unconfirmed)
+      .field("exam_time_defaults", &self.exam_time_defaults)
       .field("json_enabled", &self.json_enabled)
       .field("real_ip_header", &self.real_ip_header)
+      .field("tls", &self.tls)
+      .field("http_redirect_addr", &self.http_redirect_addr)
+      .field("https_public_port", &self.https_public_port)
+      .field("shutdown_timeout", &self.shutdown_timeout)
+      .field("cache_limits", &self.cache_limits)
+      .field("config_file", &self.config_file)
       .finish()
   }
 }
 
 impl Config {
-  /// Loads the dedicated-mode configuration from environment variables.
+  /// Loads the dedicated-mode configuration from environment variables, falling
+  /// back to `config_file` for anything the environment does not set.
   ///
   /// # Errors
   ///
-  /// Returns an error if a required variable is missing or fails to parse.
-  pub fn from_env() -> Result<Self> {
-    let username = std::env::var("AHE_USERNAME").context("AHE_USERNAME is required")?;
-    let password = std::env::var("AHE_PASSWORD").context("AHE_PASSWORD is required")?;
+  /// Returns an error if the file cannot be read, a required setting is
+  /// missing or a value fails to parse.
+  pub fn load(config_file: Option<&Path>) -> Result<Self> {
+    let source = Source::load(config_file, &known_keys())?;
+    let credentials = credentials(&source)?;
+
+    let tls = parse::tls(&source)?;
+    let http_redirect_addr = parse::http_redirect_addr(&source, tls.is_some())?;
+    let https_public_port = parse::https_public_port(&source, http_redirect_addr.is_some())?;
+    let calendar_past_days = parse::calendar_past_days(&source)?;
+    let calendar_future_days = parse::calendar_future_days(&source)?;
+
+    let catalogs = parse::catalogs(&source)?;
 
     Ok(Self {
-      username,
-      password,
-      bind_addr: parse::bind_addr(),
-      calendar_past_days: parse::calendar_past_days()?,
-      calendar_future_days: parse::calendar_future_days()?,
-      calendar_token: parse::calendar_token()?,
-      calendar_lang: parse::calendar_lang()?,
-      exams_enabled: parse::exams_enabled()?,
-      json_enabled: parse::json_enabled()?,
-      real_ip_header: parse::real_ip_header()?,
+      credentials: Reloadable::new(credentials),
+      bind_addr: parse::bind_addr(&source),
+      calendar_past_days,
+      calendar_future_days,
+      calendar_max_range_days: parse::calendar_max_range_days(
+        &source,
+        calendar_past_days,
+        calendar_future_days,
+      )?,
+      calendar_cancelled_days: parse::calendar_cancelled_days(&source)?,
+      history_file: parse::history_file(&source)?,
+      calendar_token: Reloadable::new(parse::calendar_token(&source)?),
+      calendar_lang: parse::calendar_lang(&source, &catalogs)?,
+      calendar_timezone: parse::calendar_timezone(&source)?,
+      compress_series: parse::compress_series(&source)?,
+      merge_gap_minutes: parse::merge_gap_minutes(&source)?,
+      locations: parse::locations(&source)?,
+      templates: parse::templates(&source, &catalogs)?,
+      catalogs,
+      exams_enabled: parse::exams_enabled(&source)?,
+      exam_indexes: parse::exam_indexes(&source),
+      exam_unconfirmed: parse::exam_unconfirmed(&source)?,
+      exam_time_defaults: parse::exam_time_defaults(&source)?,
+      json_enabled: parse::json_enabled(&source)?,
+      real_ip_header: parse::real_ip_header(&source)?,
+      tls,
+      http_redirect_addr,
+      https_public_port,
+      shutdown_timeout: parse::shutdown_timeout(&source)?,
+      cache_limits: parse::cache_limits(&source)?,
+      config_file: config_file.map(Path::to_path_buf),
     })
   }
+
+  /// Current WPS username and password, as one consistent snapshot
+  #[must_use]
+  pub fn credentials(&self) -> (String, String) {
+    let Credentials { username, password } = self.credentials.get();
+    (username, password)
+  }
+}
+
+fn known_keys() -> Vec<&'static str> {
+  [parse::SERVER_KEYS, DEDICATED_KEYS].concat()
+}
+
+fn credentials(source: &Source) -> Result<Credentials> {
+  let username = parse::secret(source, "AHE_USERNAME")?
+    .context("AHE_USERNAME or AHE_USERNAME_FILE is required")?;
+  let password = parse::secret(source, "AHE_PASSWORD")?
+    .context("AHE_PASSWORD or AHE_PASSWORD_FILE is required")?;
+
+  Ok(Credentials { username, password })
 }
 
 impl ServerSettings for Config {
+  fn bind_addr(&self) -> &str {
+    &self.bind_addr
+  }
+  fn tls(&self) -> Option<&TlsSettings> {
+    self.tls.as_ref()
+  }
+  fn http_redirect_addr(&self) -> Option<&str> {
+    self.http_redirect_addr.as_deref()
+  }
+  fn https_public_port(&self) -> Option<u16> {
+    self.https_public_port
+  }
+  fn shutdown_timeout(&self) -> Duration {
+    self.shutdown_timeout
+  }
   fn calendar_past_days(&self) -> i64 {
     self.calendar_past_days
   }
   fn calendar_future_days(&self) -> i64 {
     self.calendar_future_days
   }
-  fn calendar_token(&self) -> Option<&CalendarToken> {
-    self.calendar_token.as_ref()
+  fn calendar_max_range_days(&self) -> i64 {
+    self.calendar_max_range_days
+  }
+  fn calendar_cancelled_days(&self) -> i64 {
+    self.calendar_cancelled_days
+  }
+  fn history_file(&self) -> Option<&Path> {
+    self.history_file.as_deref()
+  }
+  fn calendar_token(&self) -> Option<CalendarToken> {
+    self.calendar_token.get()
   }
   fn calendar_lang(&self) -> CalendarLanguage {
     self.calendar_lang
   }
+  fn calendar_timezone(&self) -> OutputZone {
+    self.calendar_timezone
+  }
+  fn catalogs(&self) -> &Catalogs {
+    &self.catalogs
+  }
+  fn compress_series(&self) -> bool {
+    self.compress_series
+  }
+  fn merge_gap_minutes(&self) -> Option<i64> {
+    self.merge_gap_minutes
+  }
+  fn locations(&self) -> &LocationTable {
+    &self.locations
+  }
+  fn templates(&self) -> &Templates {
+    &self.templates
+  }
   fn exams_enabled(&self) -> bool {
     self.exams_enabled
   }
+  fn exam_indexes(&self) -> &[IndexSelector] {
+    &self.exam_indexes
+  }
+  fn exam_unconfirmed(&self) -> UnconfirmedExamTime {
+    self.exam_unconfirmed
+  }
+  fn exam_time_defaults(&self) -> ExamTimeDefaults {
+    self.exam_time_defaults
+  }
   fn json_enabled(&self) -> bool {
     self.json_enabled
   }
   fn real_ip_header(&self) -> Option<&str> {
     self.real_ip_header.as_deref()
   }
+  fn cache_limits(&self) -> CacheLimits {
+    self.cache_limits
+  }
+  fn reload_secrets(&self) -> Result<()> {
+    let source = Source::load(self.config_file.as_deref(), &known_keys())?;
+    let credentials = credentials(&source)?;
+    let calendar_token = parse::calendar_token(&source)?;
+
+    // Only swap once everything parsed, so a bad edit keeps the old values
+    self.credentials.set(credentials);
+    self.calendar_token.set(calendar_token);
+    Ok(())
+  }
 }
 
 #[cfg(test)]
@@ -94,16 +280,41 @@ mod tests {
 
   fn sample() -> Config {
     Config {
-      username: "jan.kowalski".to_string(),
-      password: "super-tajne".to_string(),
+      credentials: Reloadable::new(Credentials {
+        username: "jan.kowalski".to_string(),
+        password: "super-tajne".to_string(),
+      }),
       bind_addr: "0.0.0.0:8080".to_string(),
       calendar_past_days: 60,
       calendar_future_days: 60,
-      calendar_token: Some(CalendarToken::Plain("kalendarz-token".to_string())),
-      calendar_lang: CalendarLanguage::Pl,
+      calendar_max_range_days: 366,
+      calendar_cancelled_days: 14,
+      history_file: None,
+      calendar_token: Reloadable::new(Some(CalendarToken::Plain("kalendarz-token".to_string()))),
+      calendar_lang: CalendarLanguage::PL,
+      calendar_timezone: OutputZone::Floating,
+      catalogs: Catalogs::default(),
+      compress_series: false,
+      merge_gap_minutes: None,
+      locations: LocationTable::default(),
+      templates: Templates::default(),
       exams_enabled: true,
+      exam_indexes: Vec::new(),
+      exam_unconfirmed: UnconfirmedExamTime::Tentative,
+      exam_time_defaults: ExamTimeDefaults::default(),
       json_enabled: true,
       real_ip_header: None,
+      tls: None,
+      http_redirect_addr: None,
+      https_public_port: None,
+      shutdown_timeout: Duration::from_secs(8),
+      cache_limits: CacheLimits {
+        token_max_entries: 10_000,
+        student_max_entries: 10_000,
+        ics_max_bytes: 64 * 1024 * 1024,
+        history_max_events: 200_000,
+      },
+      config_file: None,
     }
   }
 
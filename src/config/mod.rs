@@ -1,19 +1,73 @@
+mod cli;
 mod dedicated;
 mod parse;
 mod shared;
+mod source;
 mod types;
 
-pub use dedicated::Config;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::i18n::Catalogs;
+use crate::locations::LocationTable;
+use crate::templates::Templates;
+use crate::timezone::OutputZone;
+
+pub use cli::CliOptions;
+pub use dedicated::{Config, Credentials};
 pub use shared::SharedConfig;
-pub use types::{CalendarLanguage, CalendarToken};
+pub(crate) use source::parse_structured;
+pub use types::{
+  CacheLimits, CalendarLanguage, CalendarToken, ExamTimeDefaults, IndexSelector, Reloadable,
+  TlsSettings, UnconfirmedExamTime,
+};
 
 /// Shared server-level settings used by both dedicated and shared binaries.
 pub trait ServerSettings: Clone + Send + Sync + 'static {
+  fn bind_addr(&self) -> &str;
+  fn tls(&self) -> Option<&TlsSettings>;
+  fn http_redirect_addr(&self) -> Option<&str>;
+  /// Port HTTP requests are redirected to; the bound HTTPS port when `None`
+  fn https_public_port(&self) -> Option<u16>;
+  fn shutdown_timeout(&self) -> Duration;
   fn calendar_past_days(&self) -> i64;
   fn calendar_future_days(&self) -> i64;
-  fn calendar_token(&self) -> Option<&CalendarToken>;
+  /// Longest `from`..`to` span, in days, a request may ask for; `0` means no cap
+  fn calendar_max_range_days(&self) -> i64;
+  /// Days a vanished event is still served as cancelled; `0` serves none
+  fn calendar_cancelled_days(&self) -> i64;
+  /// File the event history is restored from at startup and saved to on shutdown
+  fn history_file(&self) -> Option<&Path>;
+  fn calendar_token(&self) -> Option<CalendarToken>;
   fn calendar_lang(&self) -> CalendarLanguage;
+  /// Zone calendars are written in unless a request asks for another
+  fn calendar_timezone(&self) -> OutputZone;
+  /// Translation catalogs, built-in and loaded from files
+  fn catalogs(&self) -> &Catalogs;
+  /// Fold weekly classes into recurring events
+  fn compress_series(&self) -> bool;
+  /// Longest break, in minutes, between blocks merged into one class; `None` disables merging
+  fn merge_gap_minutes(&self) -> Option<i64>;
+  /// Campus buildings rooms are mapped to
+  fn locations(&self) -> &LocationTable;
+  /// Subject aliases and event text templates
+  fn templates(&self) -> &Templates;
   fn exams_enabled(&self) -> bool;
+  /// Indexes to fetch exams for; empty means every active one
+  fn exam_indexes(&self) -> &[IndexSelector];
+  fn exam_unconfirmed(&self) -> UnconfirmedExamTime;
+  /// Start time and length assumed when an exam listing leaves them out
+  fn exam_time_defaults(&self) -> ExamTimeDefaults;
   fn json_enabled(&self) -> bool;
   fn real_ip_header(&self) -> Option<&str>;
+  fn cache_limits(&self) -> CacheLimits;
+  /// Re-reads the secret settings (and their `_FILE`s); other settings need a restart.
+  ///
+  /// # Errors
+  ///
+  /// Returns an error, leaving the current secrets in place, if a secret is
+  /// missing or fails to parse.
+  fn reload_secrets(&self) -> Result<()>;
 }
@@ -1,6 +1,17 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use anyhow::{Context, Result, bail};
 
-use super::types::{CalendarLanguage, CalendarToken};
+use super::source::Source;
+use super::types::{
+  CacheLimits, CalendarLanguage, CalendarToken, ExamTimeDefaults, IndexSelector, TlsSettings,
+  UnconfirmedExamTime,
+};
+use crate::i18n::Catalogs;
+use crate::locations::LocationTable;
+use crate::templates::Templates;
+use crate::timezone::OutputZone;
 
 const DEFAULT_BIND_ADDR: &str = "0.0.0.0:8080";
 const DEFAULT_CAL_PAST_DAYS: i64 = 60;
@@ -8,21 +19,91 @@ const DEFAULT_CAL_FUTURE_DAYS: i64 = 60;
 const DEFAULT_CAL_LANG: &str = "pl";
 const DEFAULT_EXAMS_ENABLED: bool = true;
 const DEFAULT_JSON_ENABLED: bool = true;
+const DEFAULT_COMPRESS_SERIES: bool = false;
+const DEFAULT_SHUTDOWN_TIMEOUT_SECONDS: u64 = 8;
+const DEFAULT_EXAM_UNCONFIRMED: &str = "tentative";
+const DEFAULT_CAL_MAX_RANGE_DAYS: i64 = 366;
+const DEFAULT_CAL_CANCELLED_DAYS: i64 = 14;
+const DEFAULT_CACHE_TOKEN_MAX_ENTRIES: u64 = 10_000;
+const DEFAULT_CACHE_STUDENT_MAX_ENTRIES: u64 = 10_000;
+const DEFAULT_CACHE_ICS_MAX_BYTES: u64 = 64 * 1024 * 1024;
+const DEFAULT_CACHE_HISTORY_MAX_EVENTS: u64 = 200_000;
+
+/// Settings understood by both binaries, also the keys accepted in a config file
+pub(super) const SERVER_KEYS: &[&str] = &[
+  "BIND_ADDR",
+  "AHE_CAL_PAST_DAYS",
+  "AHE_CAL_FUTURE_DAYS",
+  "AHE_CAL_MAX_RANGE_DAYS",
+  "AHE_CAL_CANCELLED_DAYS",
+  "AHE_CAL_HISTORY_FILE",
+  "AHE_CAL_TOKEN",
+  "AHE_CAL_TOKEN_FILE",
+  "AHE_CAL_LANG",
+  "AHE_CAL_TIMEZONE",
+  "AHE_CAL_TRANSLATIONS_DIR",
+  "AHE_CAL_COMPRESS_SERIES",
+  "AHE_CAL_MERGE_GAP_MINUTES",
+  "AHE_CAL_LOCATIONS_FILE",
+  "AHE_CAL_TEMPLATES_FILE",
+  "AHE_CAL_EXAMS_ENABLED",
+  "AHE_CAL_EXAM_INDEXES",
+  "AHE_CAL_EXAM_UNCONFIRMED",
+  "AHE_CAL_EXAM_DEFAULT_START",
+  "AHE_CAL_EXAM_DEFAULT_MINUTES",
+  "AHE_CAL_JSON_ENABLED",
+  "REAL_IP_HEADER",
+  "AHE_REAL_IP_HEADER",
+  "TLS_CERT_PATH",
+  "TLS_KEY_PATH",
+  "HTTP_REDIRECT_ADDR",
+  "HTTPS_PUBLIC_PORT",
+  "SHUTDOWN_TIMEOUT_SECONDS",
+  "CACHE_TOKEN_MAX_ENTRIES",
+  "CACHE_STUDENT_MAX_ENTRIES",
+  "CACHE_ICS_MAX_BYTES",
+  "CACHE_HISTORY_MAX_EVENTS",
+];
+
+pub(super) fn bind_addr(source: &Source) -> String {
+  source
+    .var("BIND_ADDR")
+    .unwrap_or_else(|| DEFAULT_BIND_ADDR.to_string())
+}
+
+pub(super) fn calendar_past_days(source: &Source) -> Result<i64> {
+  parse_days(source, "AHE_CAL_PAST_DAYS", DEFAULT_CAL_PAST_DAYS)
+}
 
-pub(super) fn bind_addr() -> String {
-  std::env::var("BIND_ADDR").unwrap_or_else(|_| DEFAULT_BIND_ADDR.to_string())
+pub(super) fn calendar_future_days(source: &Source) -> Result<i64> {
+  parse_days(source, "AHE_CAL_FUTURE_DAYS", DEFAULT_CAL_FUTURE_DAYS)
 }
 
-pub(super) fn calendar_past_days() -> Result<i64> {
-  parse_days("AHE_CAL_PAST_DAYS", DEFAULT_CAL_PAST_DAYS)
+/// Longest `from`..`to` span a request may ask for; `0` disables the cap.
+///
+/// Must cover the default window, or requests without `from`/`to` would be
+/// rejected.
+pub(super) fn calendar_max_range_days(
+  source: &Source,
+  past_days: i64,
+  future_days: i64,
+) -> Result<i64> {
+  let max_days = parse_days(source, "AHE_CAL_MAX_RANGE_DAYS", DEFAULT_CAL_MAX_RANGE_DAYS)?;
+  max_range_days_value(max_days, past_days, future_days)
 }
 
-pub(super) fn calendar_future_days() -> Result<i64> {
-  parse_days("AHE_CAL_FUTURE_DAYS", DEFAULT_CAL_FUTURE_DAYS)
+/// How long vanished events stay in the feed as cancelled; `0` drops them at once
+pub(super) fn calendar_cancelled_days(source: &Source) -> Result<i64> {
+  parse_days(source, "AHE_CAL_CANCELLED_DAYS", DEFAULT_CAL_CANCELLED_DAYS)
 }
 
-pub(super) fn calendar_token() -> Result<Option<CalendarToken>> {
-  let Some(raw) = std::env::var("AHE_CAL_TOKEN").ok() else {
+/// Unset keeps the event history in memory only
+pub(super) fn history_file(source: &Source) -> Result<Option<PathBuf>> {
+  history_file_value(source.var("AHE_CAL_HISTORY_FILE").as_deref())
+}
+
+pub(super) fn calendar_token(source: &Source) -> Result<Option<CalendarToken>> {
+  let Some(raw) = secret(source, "AHE_CAL_TOKEN")? else {
     return Ok(None);
   };
 
@@ -32,33 +113,217 @@ pub(super) fn calendar_token() -> Result<Option<CalendarToken>> {
   Ok(Some(token))
 }
 
-pub(super) fn calendar_lang() -> Result<CalendarLanguage> {
-  let value = std::env::var("AHE_CAL_LANG").unwrap_or_else(|_| DEFAULT_CAL_LANG.to_string());
-  CalendarLanguage::from_env_value(&value)
+pub(super) fn calendar_lang(source: &Source, catalogs: &Catalogs) -> Result<CalendarLanguage> {
+  let value = source
+    .var("AHE_CAL_LANG")
+    .unwrap_or_else(|| DEFAULT_CAL_LANG.to_string());
+  calendar_lang_value(&value, catalogs)
 }
 
-pub(super) fn exams_enabled() -> Result<bool> {
-  parse_bool("AHE_CAL_EXAMS_ENABLED", DEFAULT_EXAMS_ENABLED)
+fn calendar_lang_value(value: &str, catalogs: &Catalogs) -> Result<CalendarLanguage> {
+  match CalendarLanguage::from_code(value) {
+    Some(lang) if catalogs.contains(lang) => Ok(lang),
+    _ => bail!(
+      "AHE_CAL_LANG must be one of: {}",
+      catalogs
+        .languages()
+        .map(|lang| lang.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+    ),
+  }
 }
 
-pub(super) fn json_enabled() -> Result<bool> {
-  parse_bool("AHE_CAL_JSON_ENABLED", DEFAULT_JSON_ENABLED)
+/// Unset keeps the floating Warsaw times WPS publishes
+pub(super) fn calendar_timezone(source: &Source) -> Result<OutputZone> {
+  source
+    .var("AHE_CAL_TIMEZONE")
+    .map_or(Ok(OutputZone::Floating), |value| {
+      calendar_timezone_value(&value)
+    })
 }
 
-pub(super) fn real_ip_header() -> Result<Option<String>> {
-  let raw = std::env::var("REAL_IP_HEADER")
+fn calendar_timezone_value(value: &str) -> Result<OutputZone> {
+  OutputZone::from_name(value).with_context(|| {
+    format!("AHE_CAL_TIMEZONE must be floating, UTC or an IANA zone name, got {value:?}")
+  })
+}
+
+/// Unset keeps the built-in Polish, English and Ukrainian catalogs
+pub(super) fn catalogs(source: &Source) -> Result<Catalogs> {
+  match source.var("AHE_CAL_TRANSLATIONS_DIR") {
+    Some(path) => Catalogs::load_dir(Path::new(path.trim())),
+    None => Ok(Catalogs::default()),
+  }
+}
+
+pub(super) fn compress_series(source: &Source) -> Result<bool> {
+  parse_bool(source, "AHE_CAL_COMPRESS_SERIES", DEFAULT_COMPRESS_SERIES)
+}
+
+/// Unset leaves WPS blocks alone; `0` only merges blocks that touch
+pub(super) fn merge_gap_minutes(source: &Source) -> Result<Option<i64>> {
+  merge_gap_minutes_value(source.var("AHE_CAL_MERGE_GAP_MINUTES").as_deref())
+}
+
+/// Unset leaves every class without a building
+pub(super) fn locations(source: &Source) -> Result<LocationTable> {
+  match source.var("AHE_CAL_LOCATIONS_FILE") {
+    Some(path) => LocationTable::load(Path::new(path.trim())),
+    None => Ok(LocationTable::default()),
+  }
+}
+
+/// Unset keeps the built-in event texts
+pub(super) fn templates(source: &Source, catalogs: &Catalogs) -> Result<Templates> {
+  match source.var("AHE_CAL_TEMPLATES_FILE") {
+    Some(path) => Templates::load(Path::new(path.trim()), catalogs),
+    None => Ok(Templates::default()),
+  }
+}
+
+pub(super) fn exams_enabled(source: &Source) -> Result<bool> {
+  parse_bool(source, "AHE_CAL_EXAMS_ENABLED", DEFAULT_EXAMS_ENABLED)
+}
+
+/// Comma-separated indeks ids or section names; empty means every active indeks
+pub(super) fn exam_indexes(source: &Source) -> Vec<IndexSelector> {
+  exam_indexes_value(source.var("AHE_CAL_EXAM_INDEXES").as_deref())
+}
+
+pub(super) fn exam_unconfirmed(source: &Source) -> Result<UnconfirmedExamTime> {
+  let value = source
+    .var("AHE_CAL_EXAM_UNCONFIRMED")
+    .unwrap_or_else(|| DEFAULT_EXAM_UNCONFIRMED.to_string());
+  UnconfirmedExamTime::from_env_value(&value)
+}
+
+pub(super) fn exam_time_defaults(source: &Source) -> Result<ExamTimeDefaults> {
+  let defaults = ExamTimeDefaults::default();
+  let start = match source.var("AHE_CAL_EXAM_DEFAULT_START") {
+    Some(raw) => exam_start_value(&raw)?,
+    None => defaults.start,
+  };
+  let minutes = parse_limit_value(
+    "AHE_CAL_EXAM_DEFAULT_MINUTES",
+    source.var("AHE_CAL_EXAM_DEFAULT_MINUTES").as_deref(),
+    defaults.duration.num_minutes().unsigned_abs(),
+  )?;
+  let minutes = i64::try_from(minutes)
     .ok()
-    .or_else(|| std::env::var("AHE_REAL_IP_HEADER").ok());
+    .filter(|minutes| *minutes < 24 * 60)
+    .context("AHE_CAL_EXAM_DEFAULT_MINUTES must be shorter than a day")?;
+
+  Ok(ExamTimeDefaults {
+    start,
+    duration: chrono::Duration::minutes(minutes),
+  })
+}
+
+pub(super) fn json_enabled(source: &Source) -> Result<bool> {
+  parse_bool(source, "AHE_CAL_JSON_ENABLED", DEFAULT_JSON_ENABLED)
+}
+
+pub(super) fn real_ip_header(source: &Source) -> Result<Option<String>> {
+  let raw = source
+    .var("REAL_IP_HEADER")
+    .or_else(|| source.var("AHE_REAL_IP_HEADER"));
 
   normalize_real_ip_header(raw.as_deref())
 }
 
-fn parse_days(key: &str, default_value: i64) -> Result<i64> {
-  parse_days_value(key, std::env::var(key).ok().as_deref(), default_value)
+pub(super) fn tls(source: &Source) -> Result<Option<TlsSettings>> {
+  tls_value(
+    source.var("TLS_CERT_PATH").as_deref(),
+    source.var("TLS_KEY_PATH").as_deref(),
+  )
+}
+
+pub(super) fn http_redirect_addr(source: &Source, tls_enabled: bool) -> Result<Option<String>> {
+  http_redirect_addr_value(source.var("HTTP_REDIRECT_ADDR").as_deref(), tls_enabled)
+}
+
+/// Unset redirects to the port the HTTPS listener is bound to
+pub(super) fn https_public_port(source: &Source, redirect_enabled: bool) -> Result<Option<u16>> {
+  https_public_port_value(source.var("HTTPS_PUBLIC_PORT").as_deref(), redirect_enabled)
+}
+
+pub(super) fn shutdown_timeout(source: &Source) -> Result<Duration> {
+  let seconds = parse_seconds_value(
+    "SHUTDOWN_TIMEOUT_SECONDS",
+    source.var("SHUTDOWN_TIMEOUT_SECONDS").as_deref(),
+    DEFAULT_SHUTDOWN_TIMEOUT_SECONDS,
+  )?;
+
+  Ok(Duration::from_secs(seconds))
+}
+
+pub(super) fn cache_limits(source: &Source) -> Result<CacheLimits> {
+  Ok(CacheLimits {
+    token_max_entries: parse_limit(
+      source,
+      "CACHE_TOKEN_MAX_ENTRIES",
+      DEFAULT_CACHE_TOKEN_MAX_ENTRIES,
+    )?,
+    student_max_entries: parse_limit(
+      source,
+      "CACHE_STUDENT_MAX_ENTRIES",
+      DEFAULT_CACHE_STUDENT_MAX_ENTRIES,
+    )?,
+    ics_max_bytes: parse_limit(source, "CACHE_ICS_MAX_BYTES", DEFAULT_CACHE_ICS_MAX_BYTES)?,
+    history_max_events: parse_limit(
+      source,
+      "CACHE_HISTORY_MAX_EVENTS",
+      DEFAULT_CACHE_HISTORY_MAX_EVENTS,
+    )?,
+  })
+}
+
+/// Reads a secret from `KEY` or from the file named by `KEY_FILE`, never both
+/// in the same place; either one in the environment overrides the config file.
+///
+/// The file is read on every call, which is what lets SIGHUP pick up a
+/// rotated value.
+pub(super) fn secret(source: &Source, key: &str) -> Result<Option<String>> {
+  let file_key = format!("{key}_FILE");
+  let [direct, path] = source.vars([key, &file_key]);
+  secret_value(key, direct, path.as_deref())
+}
+
+fn parse_days(source: &Source, key: &str, default_value: i64) -> Result<i64> {
+  parse_days_value(key, source.var(key).as_deref(), default_value)
+}
+
+fn parse_limit(source: &Source, key: &str, default_value: u64) -> Result<u64> {
+  parse_limit_value(key, source.var(key).as_deref(), default_value)
 }
 
-fn parse_bool(key: &str, default_value: bool) -> Result<bool> {
-  parse_bool_value(key, std::env::var(key).ok().as_deref(), default_value)
+fn parse_bool(source: &Source, key: &str, default_value: bool) -> Result<bool> {
+  parse_bool_value(key, source.var(key).as_deref(), default_value)
+}
+
+fn exam_start_value(raw: &str) -> Result<chrono::NaiveTime> {
+  chrono::NaiveTime::parse_from_str(raw.trim(), "%H:%M")
+    .context("AHE_CAL_EXAM_DEFAULT_START must be a time in HH:MM format")
+}
+
+fn merge_gap_minutes_value(raw: Option<&str>) -> Result<Option<i64>> {
+  if raw.is_none() {
+    return Ok(None);
+  }
+  let minutes = parse_days_value("AHE_CAL_MERGE_GAP_MINUTES", raw, 0)?;
+  if minutes >= 24 * 60 {
+    bail!("AHE_CAL_MERGE_GAP_MINUTES must be shorter than a day");
+  }
+  Ok(Some(minutes))
+}
+
+fn exam_indexes_value(raw: Option<&str>) -> Vec<IndexSelector> {
+  raw
+    .unwrap_or_default()
+    .split(',')
+    .filter_map(IndexSelector::from_env_value)
+    .collect()
 }
 
 /// Normalizes the configured real-ip header name
@@ -75,6 +340,94 @@ fn normalize_real_ip_header(raw: Option<&str>) -> Result<Option<String>> {
   Ok(Some(value.to_ascii_lowercase()))
 }
 
+fn history_file_value(raw: Option<&str>) -> Result<Option<PathBuf>> {
+  match raw.map(str::trim) {
+    None => Ok(None),
+    Some("") => bail!("AHE_CAL_HISTORY_FILE cannot be empty"),
+    Some(path) => Ok(Some(path.into())),
+  }
+}
+
+/// Certificate and key only make sense together, so a half-configured pair is an error
+fn tls_value(cert: Option<&str>, key: Option<&str>) -> Result<Option<TlsSettings>> {
+  let cert = cert.map(str::trim);
+  let key = key.map(str::trim);
+
+  match (cert, key) {
+    (None, None) => Ok(None),
+    (Some(""), _) => bail!("TLS_CERT_PATH cannot be empty"),
+    (_, Some("")) => bail!("TLS_KEY_PATH cannot be empty"),
+    (Some(cert), Some(key)) => Ok(Some(TlsSettings {
+      cert_path: cert.into(),
+      key_path: key.into(),
+    })),
+    (Some(_), None) => bail!("TLS_KEY_PATH is required when TLS_CERT_PATH is set"),
+    (None, Some(_)) => bail!("TLS_CERT_PATH is required when TLS_KEY_PATH is set"),
+  }
+}
+
+fn http_redirect_addr_value(raw: Option<&str>, tls_enabled: bool) -> Result<Option<String>> {
+  let Some(raw) = raw else {
+    return Ok(None);
+  };
+
+  let value = raw.trim();
+  if value.is_empty() {
+    bail!("HTTP_REDIRECT_ADDR cannot be empty");
+  }
+  if !tls_enabled {
+    bail!("HTTP_REDIRECT_ADDR requires TLS_CERT_PATH and TLS_KEY_PATH");
+  }
+
+  Ok(Some(value.to_string()))
+}
+
+/// Behind Docker port mapping or a proxy the bound port is not the one
+/// browsers reach, so redirects may name another
+fn https_public_port_value(raw: Option<&str>, redirect_enabled: bool) -> Result<Option<u16>> {
+  let Some(raw) = raw else {
+    return Ok(None);
+  };
+
+  let port: u16 = raw
+    .trim()
+    .parse()
+    .context("HTTPS_PUBLIC_PORT must be a port number")?;
+  if port == 0 {
+    bail!("HTTPS_PUBLIC_PORT must be a port number");
+  }
+  if !redirect_enabled {
+    bail!("HTTPS_PUBLIC_PORT requires HTTP_REDIRECT_ADDR");
+  }
+
+  Ok(Some(port))
+}
+
+fn secret_value(key: &str, direct: Option<String>, path: Option<&str>) -> Result<Option<String>> {
+  let Some(path) = path else {
+    return Ok(direct);
+  };
+
+  if direct.is_some() {
+    bail!("{key} and {key}_FILE are both set; use only one of them");
+  }
+
+  let path = path.trim();
+  if path.is_empty() {
+    bail!("{key}_FILE cannot be empty");
+  }
+
+  let contents =
+    std::fs::read_to_string(path).with_context(|| format!("failed to read {key}_FILE ({path})"))?;
+  // Editors and `echo` leave a trailing newline that is not part of the secret
+  let value = contents.trim_end_matches(['\n', '\r']);
+  if value.is_empty() {
+    bail!("{key}_FILE ({path}) is empty");
+  }
+
+  Ok(Some(value.to_string()))
+}
+
 fn parse_days_value(key: &str, raw: Option<&str>, default_value: i64) -> Result<i64> {
   let Some(raw) = raw else {
     return Ok(default_value);
@@ -91,6 +444,40 @@ fn parse_days_value(key: &str, raw: Option<&str>, default_value: i64) -> Result<
   Ok(value)
 }
 
+/// The default window includes today as well as the past and future days
+fn max_range_days_value(max_days: i64, past_days: i64, future_days: i64) -> Result<i64> {
+  let default_window = past_days.saturating_add(future_days).saturating_add(1);
+  if max_days > 0 && default_window > max_days {
+    bail!(
+      "AHE_CAL_MAX_RANGE_DAYS ({max_days}) must be at least AHE_CAL_PAST_DAYS + AHE_CAL_FUTURE_DAYS + 1 ({default_window})"
+    );
+  }
+
+  Ok(max_days)
+}
+
+/// A cache bound of zero would disable caching entirely, which is never intended
+fn parse_limit_value(key: &str, raw: Option<&str>, default_value: u64) -> Result<u64> {
+  let Some(raw) = raw else {
+    return Ok(default_value);
+  };
+
+  match raw.trim().parse::<u64>() {
+    Ok(value) if value > 0 => Ok(value),
+    _ => bail!("{key} must be a positive integer"),
+  }
+}
+
+fn parse_seconds_value(key: &str, raw: Option<&str>, default_value: u64) -> Result<u64> {
+  let Some(raw) = raw else {
+    return Ok(default_value);
+  };
+
+  raw
+    .parse()
+    .with_context(|| format!("{key} must be a non-negative number of seconds"))
+}
+
 fn parse_bool_value(key: &str, raw: Option<&str>, default_value: bool) -> Result<bool> {
   let Some(raw) = raw else {
     return Ok(default_value);
@@ -109,6 +496,43 @@ mod tests {
 
   const KEY: &str = "AHE_TEST_KEY";
 
+  fn secret_file(name: &str, contents: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("ahe-ics-{}-{name}", std::process::id()));
+    std::fs::write(&path, contents).expect("write secret file");
+    path
+  }
+
+  #[test]
+  fn secret_comes_from_the_variable_or_the_file() {
+    assert_eq!(
+      secret_value(KEY, Some("direct".to_string()), None).expect("direct"),
+      Some("direct".to_string())
+    );
+    assert_eq!(secret_value(KEY, None, None).expect("unset"), None);
+
+    let path = secret_file("password", "from-file\n");
+    let value = secret_value(KEY, None, path.to_str()).expect("file");
+    std::fs::remove_file(&path).ok();
+
+    assert_eq!(value, Some("from-file".to_string()));
+  }
+
+  #[test]
+  fn secret_rejects_both_forms_and_unusable_files() {
+    let error = secret_value(KEY, Some("direct".to_string()), Some("/run/secrets/x"))
+      .expect_err("both forms are rejected");
+    assert!(error.to_string().contains("AHE_TEST_KEY_FILE"), "{error}");
+
+    assert!(secret_value(KEY, None, Some("  ")).is_err());
+    assert!(secret_value(KEY, None, Some("/nonexistent/ahe-ics/secret")).is_err());
+
+    let path = secret_file("empty", "\n");
+    let result = secret_value(KEY, None, path.to_str());
+    std::fs::remove_file(&path).ok();
+
+    assert!(result.is_err());
+  }
+
   #[test]
   fn days_fall_back_to_default_when_unset() {
     assert_eq!(parse_days_value(KEY, None, 60).expect("default"), 60);
@@ -136,6 +560,105 @@ mod tests {
     assert!(error.to_string().contains(KEY));
   }
 
+  #[test]
+  fn seconds_accept_non_negative_integers_only() {
+    assert_eq!(parse_seconds_value(KEY, None, 10).expect("default"), 10);
+    assert_eq!(parse_seconds_value(KEY, Some("0"), 10).expect("zero"), 0);
+    assert_eq!(parse_seconds_value(KEY, Some("45"), 10).expect("value"), 45);
+    assert!(parse_seconds_value(KEY, Some("-1"), 10).is_err());
+    assert!(parse_seconds_value(KEY, Some("1.5"), 10).is_err());
+    assert!(parse_seconds_value(KEY, Some(""), 10).is_err());
+  }
+
+  #[test]
+  fn limits_must_be_positive_integers() {
+    assert_eq!(parse_limit_value(KEY, None, 5).expect("default"), 5);
+    assert_eq!(
+      parse_limit_value(KEY, Some(" 1024 "), 5).expect("valid"),
+      1024
+    );
+    assert!(parse_limit_value(KEY, Some("0"), 5).is_err());
+    assert!(parse_limit_value(KEY, Some("-1"), 5).is_err());
+    assert!(parse_limit_value(KEY, Some("64MiB"), 5).is_err());
+  }
+
+  #[test]
+  fn max_range_must_cover_the_default_window() {
+    assert_eq!(max_range_days_value(366, 60, 60).expect("fits"), 366);
+    assert_eq!(max_range_days_value(121, 60, 60).expect("exact"), 121);
+    assert_eq!(max_range_days_value(0, 400, 400).expect("unlimited"), 0);
+    assert!(max_range_days_value(120, 60, 60).is_err());
+
+    let error = max_range_days_value(90, 60, 60).expect_err("too small");
+    assert!(
+      error.to_string().contains("AHE_CAL_MAX_RANGE_DAYS"),
+      "{error}"
+    );
+  }
+
+  #[test]
+  fn language_needs_a_catalog() {
+    let catalogs = Catalogs::default();
+
+    assert_eq!(
+      calendar_lang_value(" UK ", &catalogs).expect("built-in"),
+      CalendarLanguage::UK
+    );
+    let error = calendar_lang_value("de", &catalogs).expect_err("no catalog");
+    assert!(error.to_string().contains("pl, en, uk"), "{error}");
+    assert!(calendar_lang_value("", &catalogs).is_err());
+  }
+
+  #[test]
+  fn timezone_is_a_mode_or_an_iana_name() {
+    assert_eq!(
+      calendar_timezone_value("floating").expect("mode"),
+      OutputZone::Floating
+    );
+    assert_eq!(
+      calendar_timezone_value("utc").expect("mode"),
+      OutputZone::Utc
+    );
+    assert_eq!(
+      calendar_timezone_value("Europe/Lisbon").expect("zone"),
+      OutputZone::Zone(chrono_tz::Europe::Lisbon)
+    );
+    let error = calendar_timezone_value("CEST+1").expect_err("unknown zone");
+    assert!(error.to_string().contains("AHE_CAL_TIMEZONE"), "{error}");
+  }
+
+  #[test]
+  fn merge_gap_is_optional_and_shorter_than_a_day() {
+    assert_eq!(merge_gap_minutes_value(None).expect("unset"), None);
+    assert_eq!(merge_gap_minutes_value(Some("0")).expect("zero"), Some(0));
+    assert_eq!(merge_gap_minutes_value(Some("15")).expect("gap"), Some(15));
+    assert!(merge_gap_minutes_value(Some("-5")).is_err());
+    assert!(merge_gap_minutes_value(Some("1440")).is_err());
+  }
+
+  #[test]
+  fn exam_indexes_mix_ids_and_section_names() {
+    assert!(exam_indexes_value(None).is_empty());
+    assert!(exam_indexes_value(Some(" , ")).is_empty());
+    assert_eq!(
+      exam_indexes_value(Some("12345, Informatyka IN1 ,")),
+      vec![
+        IndexSelector::Id(12345),
+        IndexSelector::Section("informatyka in1".to_string()),
+      ]
+    );
+  }
+
+  #[test]
+  fn exam_default_start_is_hours_and_minutes() {
+    assert_eq!(
+      exam_start_value(" 08:30 ").expect("valid"),
+      chrono::NaiveTime::from_hms_opt(8, 30, 0).expect("valid time")
+    );
+    assert!(exam_start_value("8").is_err());
+    assert!(exam_start_value("25:00").is_err());
+  }
+
   #[test]
   fn bool_falls_back_to_default_when_unset() {
     assert!(parse_bool_value(KEY, None, true).expect("default"));
@@ -186,4 +709,60 @@ mod tests {
     assert!(normalize_real_ip_header(Some("   ")).is_err());
     assert!(normalize_real_ip_header(Some("")).is_err());
   }
+
+  #[test]
+  fn history_file_is_optional_but_never_blank() {
+    assert_eq!(history_file_value(None).expect("unset"), None);
+    assert_eq!(
+      history_file_value(Some(" /data/history.json ")).expect("valid"),
+      Some(PathBuf::from("/data/history.json"))
+    );
+    assert!(history_file_value(Some("  ")).is_err());
+  }
+
+  #[test]
+  fn tls_is_disabled_when_neither_path_is_set() {
+    assert!(tls_value(None, None).expect("unset").is_none());
+  }
+
+  #[test]
+  fn tls_accepts_a_complete_pair() {
+    let tls = tls_value(Some(" /certs/fullchain.pem "), Some("/certs/privkey.pem"))
+      .expect("valid pair")
+      .expect("tls enabled");
+
+    assert_eq!(tls.cert_path, std::path::Path::new("/certs/fullchain.pem"));
+    assert_eq!(tls.key_path, std::path::Path::new("/certs/privkey.pem"));
+  }
+
+  #[test]
+  fn tls_rejects_half_configured_or_blank_pairs() {
+    assert!(tls_value(Some("/certs/fullchain.pem"), None).is_err());
+    assert!(tls_value(None, Some("/certs/privkey.pem")).is_err());
+    assert!(tls_value(Some("  "), Some("/certs/privkey.pem")).is_err());
+    assert!(tls_value(Some("/certs/fullchain.pem"), Some("")).is_err());
+  }
+
+  #[test]
+  fn http_redirect_requires_tls() {
+    assert_eq!(http_redirect_addr_value(None, false).expect("unset"), None);
+    assert!(http_redirect_addr_value(Some("0.0.0.0:80"), false).is_err());
+    assert_eq!(
+      http_redirect_addr_value(Some(" 0.0.0.0:80 "), true).expect("valid"),
+      Some("0.0.0.0:80".to_string())
+    );
+    assert!(http_redirect_addr_value(Some("  "), true).is_err());
+  }
+
+  #[test]
+  fn public_https_port_needs_the_redirect() {
+    assert_eq!(https_public_port_value(None, false).expect("unset"), None);
+    assert_eq!(
+      https_public_port_value(Some(" 443 "), true).expect("valid"),
+      Some(443)
+    );
+    assert!(https_public_port_value(Some("443"), false).is_err());
+    assert!(https_public_port_value(Some("0"), true).is_err());
+    assert!(https_public_port_value(Some("https"), true).is_err());
+  }
 }
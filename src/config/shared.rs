@@ -1,8 +1,19 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
 use anyhow::Result;
 
 use super::ServerSettings;
 use super::parse;
-use super::types::{CalendarLanguage, CalendarToken};
+use super::source::Source;
+use super::types::{
+  CacheLimits, CalendarLanguage, CalendarToken, ExamTimeDefaults, IndexSelector, Reloadable,
+  TlsSettings, UnconfirmedExamTime,
+};
+use crate::i18n::Catalogs;
+use crate::locations::LocationTable;
+use crate::templates::Templates;
+use crate::timezone::OutputZone;
 
 /// Shared configuration
 #[derive(Clone, Debug)]
@@ -10,53 +21,163 @@ pub struct SharedConfig {
   pub bind_addr: String,
   pub calendar_past_days: i64,
   pub calendar_future_days: i64,
-  pub calendar_token: Option<CalendarToken>,
+  pub calendar_max_range_days: i64,
+  pub calendar_cancelled_days: i64,
+  pub history_file: Option<PathBuf>,
+  pub calendar_token: Reloadable<Option<CalendarToken>>,
   pub calendar_lang: CalendarLanguage,
+  pub calendar_timezone: OutputZone,
+  pub catalogs: Catalogs,
+  pub compress_series: bool,
+  pub merge_gap_minutes: Option<i64>,
+  pub locations: LocationTable,
+  pub templates: Templates,
   pub exams_enabled: bool,
+  pub exam_indexes: Vec<IndexSelector>,
+  pub exam_unconfirmed: UnconfirmedExamTime,
+  pub exam_time_defaults: ExamTimeDefaults,
   pub json_enabled: bool,
   pub real_ip_header: Option<String>,
+  pub tls: Option<TlsSettings>,
+  pub http_redirect_addr: Option<String>,
+  pub https_public_port: Option<u16>,
+  pub shutdown_timeout: Duration,
+  pub cache_limits: CacheLimits,
+  pub config_file: Option<PathBuf>,
 }
 
 impl SharedConfig {
-  /// Loads the shared-mode configuration from environment variables.
+  /// Loads the shared-mode configuration from environment variables, falling
+  /// back to `config_file` for anything the environment does not set.
   ///
   /// # Errors
   ///
-  /// Returns an error if a required variable is missing or fails to parse.
-  pub fn from_env() -> Result<Self> {
+  /// Returns an error if the file cannot be read, a required setting is
+  /// missing or a value fails to parse.
+  pub fn load(config_file: Option<&Path>) -> Result<Self> {
+    let source = Source::load(config_file, parse::SERVER_KEYS)?;
+    let tls = parse::tls(&source)?;
+    let http_redirect_addr = parse::http_redirect_addr(&source, tls.is_some())?;
+    let https_public_port = parse::https_public_port(&source, http_redirect_addr.is_some())?;
+    let calendar_past_days = parse::calendar_past_days(&source)?;
+    let calendar_future_days = parse::calendar_future_days(&source)?;
+
+    let catalogs = parse::catalogs(&source)?;
+
     Ok(Self {
-      bind_addr: parse::bind_addr(),
-      calendar_past_days: parse::calendar_past_days()?,
-      calendar_future_days: parse::calendar_future_days()?,
-      calendar_token: parse::calendar_token()?,
-      calendar_lang: parse::calendar_lang()?,
-      exams_enabled: parse::exams_enabled()?,
-      json_enabled: parse::json_enabled()?,
-      real_ip_header: parse::real_ip_header()?,
+      bind_addr: parse::bind_addr(&source),
+      calendar_past_days,
+      calendar_future_days,
+      calendar_max_range_days: parse::calendar_max_range_days(
+        &source,
+        calendar_past_days,
+        calendar_future_days,
+      )?,
+      calendar_cancelled_days: parse::calendar_cancelled_days(&source)?,
+      history_file: parse::history_file(&source)?,
+      calendar_token: Reloadable::new(parse::calendar_token(&source)?),
+      calendar_lang: parse::calendar_lang(&source, &catalogs)?,
+      calendar_timezone: parse::calendar_timezone(&source)?,
+      compress_series: parse::compress_series(&source)?,
+      merge_gap_minutes: parse::merge_gap_minutes(&source)?,
+      locations: parse::locations(&source)?,
+      templates: parse::templates(&source, &catalogs)?,
+      catalogs,
+      exams_enabled: parse::exams_enabled(&source)?,
+      exam_indexes: parse::exam_indexes(&source),
+      exam_unconfirmed: parse::exam_unconfirmed(&source)?,
+      exam_time_defaults: parse::exam_time_defaults(&source)?,
+      json_enabled: parse::json_enabled(&source)?,
+      real_ip_header: parse::real_ip_header(&source)?,
+      tls,
+      http_redirect_addr,
+      https_public_port,
+      shutdown_timeout: parse::shutdown_timeout(&source)?,
+      cache_limits: parse::cache_limits(&source)?,
+      config_file: config_file.map(Path::to_path_buf),
     })
   }
 }
 
 impl ServerSettings for SharedConfig {
+  fn bind_addr(&self) -> &str {
+    &self.bind_addr
+  }
+  fn tls(&self) -> Option<&TlsSettings> {
+    self.tls.as_ref()
+  }
+  fn http_redirect_addr(&self) -> Option<&str> {
+    self.http_redirect_addr.as_deref()
+  }
+  fn https_public_port(&self) -> Option<u16> {
+    self.https_public_port
+  }
+  fn shutdown_timeout(&self) -> Duration {
+    self.shutdown_timeout
+  }
   fn calendar_past_days(&self) -> i64 {
     self.calendar_past_days
   }
   fn calendar_future_days(&self) -> i64 {
     self.calendar_future_days
   }
-  fn calendar_token(&self) -> Option<&CalendarToken> {
-    self.calendar_token.as_ref()
+  fn calendar_max_range_days(&self) -> i64 {
+    self.calendar_max_range_days
+  }
+  fn calendar_cancelled_days(&self) -> i64 {
+    self.calendar_cancelled_days
+  }
+  fn history_file(&self) -> Option<&Path> {
+    self.history_file.as_deref()
+  }
+  fn calendar_token(&self) -> Option<CalendarToken> {
+    self.calendar_token.get()
   }
   fn calendar_lang(&self) -> CalendarLanguage {
     self.calendar_lang
   }
+  fn calendar_timezone(&self) -> OutputZone {
+    self.calendar_timezone
+  }
+  fn catalogs(&self) -> &Catalogs {
+    &self.catalogs
+  }
+  fn compress_series(&self) -> bool {
+    self.compress_series
+  }
+  fn merge_gap_minutes(&self) -> Option<i64> {
+    self.merge_gap_minutes
+  }
+  fn locations(&self) -> &LocationTable {
+    &self.locations
+  }
+  fn templates(&self) -> &Templates {
+    &self.templates
+  }
   fn exams_enabled(&self) -> bool {
     self.exams_enabled
   }
+  fn exam_indexes(&self) -> &[IndexSelector] {
+    &self.exam_indexes
+  }
+  fn exam_unconfirmed(&self) -> UnconfirmedExamTime {
+    self.exam_unconfirmed
+  }
+  fn exam_time_defaults(&self) -> ExamTimeDefaults {
+    self.exam_time_defaults
+  }
   fn json_enabled(&self) -> bool {
     self.json_enabled
   }
   fn real_ip_header(&self) -> Option<&str> {
     self.real_ip_header.as_deref()
   }
+  fn cache_limits(&self) -> CacheLimits {
+    self.cache_limits
+  }
+  fn reload_secrets(&self) -> Result<()> {
+    let source = Source::load(self.config_file.as_deref(), parse::SERVER_KEYS)?;
+    self.calendar_token.set(parse::calendar_token(&source)?);
+    Ok(())
+  }
 }
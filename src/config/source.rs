@@ -0,0 +1,196 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+
+/// Settings lookup: environment variables first, then the optional config file.
+///
+/// File keys are the environment variable names, matched case-insensitively,
+/// so `ahe_cal_past_days = 30` and `AHE_CAL_PAST_DAYS=30` mean the same thing
+/// and go through the same validation.
+#[derive(Debug, Default)]
+pub(super) struct Source {
+  file: HashMap<String, String>,
+}
+
+impl Source {
+  /// Reads `path` (if any), rejecting keys that are not in `known_keys`
+  pub(super) fn load(path: Option<&Path>, known_keys: &[&str]) -> Result<Self> {
+    let Some(path) = path else {
+      return Ok(Self::default());
+    };
+
+    let contents = std::fs::read_to_string(path)
+      .with_context(|| format!("failed to read config file {}", path.display()))?;
+
+    Ok(Self {
+      file: file_values(path, &contents, known_keys)?,
+    })
+  }
+
+  pub(super) fn var(&self, key: &str) -> Option<String> {
+    std::env::var(key)
+      .ok()
+      .or_else(|| self.file.get(key).cloned())
+  }
+
+  /// Settings that only make sense together, such as `KEY` and `KEY_FILE`,
+  /// taken as a whole from the environment when it sets any of them
+  pub(super) fn vars<const N: usize>(&self, keys: [&str; N]) -> [Option<String>; N] {
+    layered(
+      keys.map(|key| std::env::var(key).ok()),
+      keys.map(|key| self.file.get(key).cloned()),
+    )
+  }
+}
+
+fn layered<const N: usize>(
+  env: [Option<String>; N],
+  file: [Option<String>; N],
+) -> [Option<String>; N] {
+  if env.iter().any(Option::is_some) {
+    env
+  } else {
+    file
+  }
+}
+
+/// Parses a TOML or YAML data file, such as the locations table, picking the
+/// format from its extension; `what` names the file in errors
+pub(crate) fn parse_structured<T: DeserializeOwned>(
+  path: &Path,
+  contents: &str,
+  what: &str,
+) -> Result<T> {
+  let extension = path
+    .extension()
+    .and_then(|extension| extension.to_str())
+    .map(str::to_ascii_lowercase);
+
+  match extension.as_deref() {
+    Some("toml") => {
+      toml::from_str(contents).with_context(|| format!("failed to parse {what} {}", path.display()))
+    }
+    Some("yaml" | "yml") => serde_yaml_ng::from_str(contents)
+      .with_context(|| format!("failed to parse {what} {}", path.display())),
+    _ => bail!(
+      "{what} {} must have a .toml, .yaml or .yml extension",
+      path.display()
+    ),
+  }
+}
+
+fn file_values(
+  path: &Path,
+  contents: &str,
+  known_keys: &[&str],
+) -> Result<HashMap<String, String>> {
+  let extension = path
+    .extension()
+    .and_then(|extension| extension.to_str())
+    .map(str::to_ascii_lowercase);
+
+  let table: BTreeMap<String, Value> = match extension.as_deref() {
+    Some("toml") => toml::from_str(contents)
+      .with_context(|| format!("failed to parse config file {}", path.display()))?,
+    Some("yaml" | "yml") => {
+      // An empty YAML document is `null`, which means "no settings"
+      serde_yaml_ng::from_str::<Option<BTreeMap<String, Value>>>(contents)
+        .with_context(|| format!("failed to parse config file {}", path.display()))?
+        .unwrap_or_default()
+    }
+    _ => bail!(
+      "config file {} must have a .toml, .yaml or .yml extension",
+      path.display()
+    ),
+  };
+
+  let mut values = HashMap::with_capacity(table.len());
+  for (name, value) in table {
+    let key = name.to_ascii_uppercase();
+    if !known_keys.contains(&key.as_str()) {
+      bail!("unknown setting `{name}` in config file {}", path.display());
+    }
+
+    let value = match value {
+      Value::Null => continue,
+      Value::String(value) => value,
+      Value::Bool(value) => value.to_string(),
+      Value::Number(value) => value.to_string(),
+      Value::Array(_) | Value::Object(_) => bail!(
+        "{key} in config file {} must be a string, number or boolean",
+        path.display()
+      ),
+    };
+    values.insert(key, value);
+  }
+
+  Ok(values)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const KNOWN: &[&str] = &["BIND_ADDR", "AHE_CAL_PAST_DAYS", "AHE_CAL_EXAMS_ENABLED"];
+
+  #[test]
+  fn toml_scalars_become_env_style_strings() {
+    let values = file_values(
+      Path::new("ahe-ics.toml"),
+      "bind_addr = \"127.0.0.1:9000\"\nahe_cal_past_days = 30\nAHE_CAL_EXAMS_ENABLED = false\n",
+      KNOWN,
+    )
+    .expect("valid file");
+
+    assert_eq!(values["BIND_ADDR"], "127.0.0.1:9000");
+    assert_eq!(values["AHE_CAL_PAST_DAYS"], "30");
+    assert_eq!(values["AHE_CAL_EXAMS_ENABLED"], "false");
+  }
+
+  #[test]
+  fn yaml_is_accepted_and_may_be_empty() {
+    let values =
+      file_values(Path::new("ahe-ics.yml"), "ahe_cal_past_days: 14\n", KNOWN).expect("valid file");
+    assert_eq!(values["AHE_CAL_PAST_DAYS"], "14");
+
+    assert!(
+      file_values(Path::new("ahe-ics.yaml"), "", KNOWN)
+        .expect("empty file")
+        .is_empty()
+    );
+  }
+
+  #[test]
+  fn unknown_keys_and_nested_values_are_rejected() {
+    let error = file_values(Path::new("ahe-ics.toml"), "bind_adr = \"x\"\n", KNOWN)
+      .expect_err("typo is rejected");
+    assert!(error.to_string().contains("bind_adr"), "{error}");
+
+    let error = file_values(Path::new("ahe-ics.toml"), "bind_addr = [1, 2]\n", KNOWN)
+      .expect_err("array is rejected");
+    assert!(error.to_string().contains("BIND_ADDR"), "{error}");
+  }
+
+  #[test]
+  fn the_environment_overrides_a_group_as_a_whole() {
+    let set = |value: &str| Some(value.to_string());
+
+    assert_eq!(
+      layered([set("env"), None], [None, set("/run/secrets/x")]),
+      [set("env"), None]
+    );
+    assert_eq!(
+      layered([None, None], [None, set("/run/secrets/x")]),
+      [None, set("/run/secrets/x")]
+    );
+  }
+
+  #[test]
+  fn unsupported_extensions_are_rejected() {
+    assert!(file_values(Path::new("ahe-ics.json"), "{}", KNOWN).is_err());
+    assert!(file_values(Path::new("ahe-ics"), "", KNOWN).is_err());
+  }
+}
@@ -1,27 +1,209 @@
 use std::fmt;
+use std::path::PathBuf;
+use std::sync::{Arc, PoisonError, RwLock};
 
 use anyhow::{Result, anyhow, bail};
 use argon2::password_hash::PasswordHash;
 use argon2::{Argon2, PasswordVerifier};
+use chrono::{Duration, NaiveTime};
 use sha2::{Digest, Sha256};
 use subtle::ConstantTimeEq;
 
-#[derive(Clone, Copy, Debug)]
-pub enum CalendarLanguage {
-  Pl,
-  En,
+/// Longest language code accepted, enough for a region such as `pt-br`
+const LANGUAGE_CODE_MAX_LEN: usize = 8;
+
+/// Language of the calendar texts, as a lowercase code such as `pl` or `uk`.
+///
+/// Any well-formed code is accepted here; whether there is a translation
+/// catalog for it is up to [`crate::i18n::Catalogs`].
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct CalendarLanguage {
+  code: [u8; LANGUAGE_CODE_MAX_LEN],
+  len: u8,
 }
 
 impl CalendarLanguage {
+  pub const PL: Self = Self::builtin(*b"pl");
+  pub const EN: Self = Self::builtin(*b"en");
+  pub const UK: Self = Self::builtin(*b"uk");
+
+  const fn builtin(code: [u8; 2]) -> Self {
+    let mut bytes = [0; LANGUAGE_CODE_MAX_LEN];
+    bytes[0] = code[0];
+    bytes[1] = code[1];
+    Self {
+      code: bytes,
+      len: 2,
+    }
+  }
+
+  /// Parses a language code such as `pl` or `pt_BR`, in any case; `_` is
+  /// read as `-`
+  #[must_use]
+  pub fn from_code(code: &str) -> Option<Self> {
+    let code = code.trim();
+    let well_formed = (2..=LANGUAGE_CODE_MAX_LEN).contains(&code.len())
+      && code.starts_with(|ch: char| ch.is_ascii_alphabetic())
+      && code
+        .chars()
+        .all(|ch| ch.is_ascii_alphanumeric() || matches!(ch, '-' | '_'));
+    if !well_formed {
+      return None;
+    }
+
+    let mut bytes = [0; LANGUAGE_CODE_MAX_LEN];
+    for (slot, byte) in bytes.iter_mut().zip(code.bytes()) {
+      *slot = match byte {
+        b'_' => b'-',
+        byte => byte.to_ascii_lowercase(),
+      };
+    }
+    Some(Self {
+      code: bytes,
+      len: u8::try_from(code.len()).ok()?,
+    })
+  }
+
+  #[must_use]
+  pub fn code(&self) -> &str {
+    // Only ASCII is ever stored
+    std::str::from_utf8(&self.code[..usize::from(self.len)]).unwrap_or_default()
+  }
+}
+
+impl fmt::Debug for CalendarLanguage {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(formatter, "CalendarLanguage({})", self.code())
+  }
+}
+
+impl fmt::Display for CalendarLanguage {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    formatter.write_str(self.code())
+  }
+}
+
+/// How exams whose start time WPS has not published yet are shown
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum UnconfirmedExamTime {
+  /// At the guessed time, marked `STATUS:TENTATIVE`
+  Tentative,
+  /// As an all-day event on the exam date
+  AllDay,
+}
+
+impl UnconfirmedExamTime {
   pub(super) fn from_env_value(value: &str) -> Result<Self> {
     match value.trim().to_ascii_lowercase().as_str() {
-      "pl" => Ok(Self::Pl),
-      "en" => Ok(Self::En),
-      _ => bail!("AHE_CAL_LANG must be one of: pl, en"),
+      "tentative" => Ok(Self::Tentative),
+      "all-day" | "allday" => Ok(Self::AllDay),
+      _ => bail!("AHE_CAL_EXAM_UNCONFIRMED must be one of: tentative, all-day"),
+    }
+  }
+}
+
+/// Times assumed when an exam's listing leaves them out
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ExamTimeDefaults {
+  pub start: NaiveTime,
+  pub duration: Duration,
+}
+
+impl Default for ExamTimeDefaults {
+  fn default() -> Self {
+    Self {
+      start: NaiveTime::from_hms_opt(9, 0, 0).unwrap_or(NaiveTime::MIN),
+      duration: Duration::minutes(90),
+    }
+  }
+}
+
+/// Picks an indeks to fetch exams for, by its id or its section name
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IndexSelector {
+  Id(i64),
+  /// Stored lowercased; matched case-insensitively
+  Section(String),
+}
+
+impl IndexSelector {
+  pub(super) fn from_env_value(value: &str) -> Option<Self> {
+    let value = value.trim();
+    if value.is_empty() {
+      return None;
+    }
+
+    Some(match value.parse() {
+      Ok(index_id) => Self::Id(index_id),
+      Err(_) => Self::Section(value.to_lowercase()),
+    })
+  }
+
+  #[must_use]
+  pub fn matches(&self, index_id: i64, section_name: Option<&str>) -> bool {
+    match self {
+      Self::Id(id) => *id == index_id,
+      Self::Section(name) => {
+        section_name.is_some_and(|section| section.trim().to_lowercase() == *name)
+      }
     }
   }
 }
 
+/// Certificate chain and private key served by the native HTTPS listener.
+#[derive(Clone, Debug)]
+pub struct TlsSettings {
+  pub cert_path: PathBuf,
+  pub key_path: PathBuf,
+}
+
+/// Upper bounds for the in-memory caches.
+///
+/// The token and student caches hold one small entry per login, so they are
+/// bounded by count; rendered calendars vary in size and are bounded by bytes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct CacheLimits {
+  pub token_max_entries: u64,
+  pub student_max_entries: u64,
+  pub ics_max_bytes: u64,
+  /// Events remembered across all calendars to detect cancellations
+  pub history_max_events: u64,
+}
+
+/// A setting that can be swapped at runtime; clones share the current value.
+///
+/// Used for secrets so a SIGHUP can re-read their `_FILE`s without a restart.
+#[derive(Clone, Default)]
+pub struct Reloadable<T>(Arc<RwLock<T>>);
+
+impl<T: Clone> Reloadable<T> {
+  pub fn new(value: T) -> Self {
+    Self(Arc::new(RwLock::new(value)))
+  }
+
+  /// Snapshot of the current value
+  #[must_use]
+  pub fn get(&self) -> T {
+    self
+      .0
+      .read()
+      .unwrap_or_else(PoisonError::into_inner)
+      .clone()
+  }
+
+  pub(super) fn set(&self, value: T) {
+    *self.0.write().unwrap_or_else(PoisonError::into_inner) = value;
+  }
+}
+
+/// Delegates to the inner value, so redaction stays with the wrapped type.
+impl<T: fmt::Debug> fmt::Debug for Reloadable<T> {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let value = self.0.read().unwrap_or_else(PoisonError::into_inner);
+    fmt::Debug::fmt(&*value, formatter)
+  }
+}
+
 #[derive(Clone)]
 pub enum CalendarToken {
   Plain(String),
@@ -126,21 +308,24 @@ mod tests {
   }
 
   #[test]
-  fn language_accepts_supported_values() {
-    assert!(matches!(
-      CalendarLanguage::from_env_value("  PL  "),
-      Ok(CalendarLanguage::Pl)
-    ));
-    assert!(matches!(
-      CalendarLanguage::from_env_value("en"),
-      Ok(CalendarLanguage::En)
-    ));
+  fn language_codes_are_normalized() {
+    assert_eq!(
+      CalendarLanguage::from_code("  PL  "),
+      Some(CalendarLanguage::PL)
+    );
+    assert_eq!(
+      CalendarLanguage::from_code("pt_BR").map(|lang| lang.to_string()),
+      Some("pt-br".to_string())
+    );
   }
 
   #[test]
-  fn language_rejects_unknown_values() {
-    assert!(CalendarLanguage::from_env_value("de").is_err());
-    assert!(CalendarLanguage::from_env_value("").is_err());
+  fn language_rejects_malformed_codes() {
+    assert_eq!(CalendarLanguage::from_code(""), None);
+    assert_eq!(CalendarLanguage::from_code("e"), None);
+    assert_eq!(CalendarLanguage::from_code("english-uk"), None);
+    assert_eq!(CalendarLanguage::from_code("1a"), None);
+    assert_eq!(CalendarLanguage::from_code("p l"), None);
   }
 
   #[test]
@@ -250,4 +435,16 @@ mod tests {
     let token = CalendarToken::from_env_value(truncated).expect("parses");
     assert!(!token.verify("s3cret"));
   }
+
+  #[test]
+  fn reloaded_value_is_visible_through_clones() {
+    let token = Reloadable::new(Some(CalendarToken::Plain("old".to_string())));
+    let shared = token.clone();
+
+    token.set(Some(CalendarToken::Plain("rotated".to_string())));
+
+    let current = shared.get().expect("token set");
+    assert!(current.verify("rotated"));
+    assert!(!format!("{shared:?}").contains("rotated"));
+  }
 }
@@ -0,0 +1,37 @@
+use serde_json::{Map, Value};
+
+use super::{Component, Property};
+
+/// RFC 7265 form of a calendar: `[name, [properties], [components]]`
+#[must_use]
+pub fn to_jcal(component: &Component) -> Value {
+  Value::Array(vec![
+    Value::String(component.name.clone()),
+    Value::Array(component.properties.iter().map(property).collect()),
+    Value::Array(component.components.iter().map(to_jcal).collect()),
+  ])
+}
+
+/// `[name, {params}, type, values...]`, with a parameter of several values
+/// as an array
+fn property(property: &Property) -> Value {
+  let params: Map<String, Value> = property
+    .params
+    .iter()
+    .map(|(name, values)| {
+      let value = match values.as_slice() {
+        [single] => Value::String(single.clone()),
+        several => Value::Array(several.iter().cloned().map(Value::String).collect()),
+      };
+      (name.clone(), value)
+    })
+    .collect();
+
+  let mut out = vec![
+    Value::String(property.name.clone()),
+    Value::Object(params),
+    Value::String(property.value_type.as_str().to_string()),
+  ];
+  out.extend(property.values.iter().cloned());
+  Value::Array(out)
+}
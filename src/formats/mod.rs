@@ -0,0 +1,852 @@
+pub mod jcal;
+pub mod xcal;
+
+use anyhow::{Context, Result, bail};
+use serde_json::{Map, Number, Value};
+
+/// Value types of RFC 5545 as jCal and xCal name them
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ValueType {
+  Text,
+  Date,
+  DateTime,
+  Duration,
+  Integer,
+  Float,
+  Boolean,
+  Uri,
+  UtcOffset,
+  Recur,
+  /// Extension properties without a `VALUE` parameter
+  Unknown,
+}
+
+impl ValueType {
+  #[must_use]
+  pub fn as_str(self) -> &'static str {
+    match self {
+      Self::Text => "text",
+      Self::Date => "date",
+      Self::DateTime => "date-time",
+      Self::Duration => "duration",
+      Self::Integer => "integer",
+      Self::Float => "float",
+      Self::Boolean => "boolean",
+      Self::Uri => "uri",
+      Self::UtcOffset => "utc-offset",
+      Self::Recur => "recur",
+      Self::Unknown => "unknown",
+    }
+  }
+
+  fn from_value_param(value: &str) -> Self {
+    match value.to_ascii_uppercase().as_str() {
+      "TEXT" => Self::Text,
+      "DATE" => Self::Date,
+      "DATE-TIME" => Self::DateTime,
+      "DURATION" => Self::Duration,
+      "INTEGER" => Self::Integer,
+      "FLOAT" => Self::Float,
+      "BOOLEAN" => Self::Boolean,
+      "URI" => Self::Uri,
+      "UTC-OFFSET" => Self::UtcOffset,
+      "RECUR" => Self::Recur,
+      _ => Self::Unknown,
+    }
+  }
+
+  /// Default type of a property, per RFC 5545 and RFC 7986
+  fn of_property(name: &str, value: &str) -> Self {
+    match name {
+      "DTSTART" | "DTEND" | "DTSTAMP" | "CREATED" | "LAST-MODIFIED" | "RECURRENCE-ID"
+      | "EXDATE" | "RDATE" | "DUE" | "COMPLETED" => {
+        // A date-only list has no `T` in any of its values
+        if value.contains('T') {
+          Self::DateTime
+        } else {
+          Self::Date
+        }
+      }
+      "TRIGGER" | "DURATION" | "REFRESH-INTERVAL" => Self::Duration,
+      "SEQUENCE" | "PRIORITY" | "PERCENT-COMPLETE" | "REPEAT" => Self::Integer,
+      "GEO" => Self::Float,
+      "URL" | "SOURCE" | "TZURL" | "ATTACH" | "IMAGE" | "CONFERENCE" => Self::Uri,
+      "TZOFFSETFROM" | "TZOFFSETTO" => Self::UtcOffset,
+      "RRULE" | "EXRULE" => Self::Recur,
+      name if name.starts_with("X-") => Self::Unknown,
+      _ => Self::Text,
+    }
+  }
+}
+
+/// A calendar component with typed values, read back from rendered ICS so
+/// jCal and xCal carry exactly what the ICS feed does
+#[derive(Clone, Debug, PartialEq)]
+pub struct Component {
+  /// Lowercase, such as `vevent`
+  pub name: String,
+  pub properties: Vec<Property>,
+  pub components: Vec<Component>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Property {
+  /// Lowercase, such as `dtstart`
+  pub name: String,
+  /// Lowercase names with unquoted values; `VALUE` becomes `value_type`
+  pub params: Vec<(String, Vec<String>)>,
+  pub value_type: ValueType,
+  /// Unescaped values in their jCal form; more than one for lists such as `EXDATE`
+  pub values: Vec<Value>,
+}
+
+impl Component {
+  /// Reads a rendered ICS calendar
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the text is not a single well-formed calendar or a
+  /// value does not match its type.
+  pub fn parse(ics: &str) -> Result<Self> {
+    let unfolded = ics.replace("\r\n ", "").replace("\r\n\t", "");
+    let mut open: Vec<Self> = Vec::new();
+    let mut root = None;
+    for line in unfolded.split("\r\n").filter(|line| !line.is_empty()) {
+      let (name, params, value) = split_line(line)?;
+      match name.as_str() {
+        "BEGIN" => open.push(Self {
+          name: value.to_ascii_lowercase(),
+          properties: Vec::new(),
+          components: Vec::new(),
+        }),
+        "END" => {
+          let done = open.pop().context("END without BEGIN")?;
+          if !done.name.eq_ignore_ascii_case(value) {
+            bail!("END:{value} closes {}", done.name.to_ascii_uppercase());
+          }
+          match open.last_mut() {
+            Some(parent) => parent.components.push(done),
+            None if root.is_none() => root = Some(done),
+            None => bail!("more than one top-level component"),
+          }
+        }
+        _ => open
+          .last_mut()
+          .with_context(|| format!("{name} outside of a component"))?
+          .properties
+          .push(Property::parse(&name, params, value)?),
+      }
+    }
+    if let Some(unclosed) = open.last() {
+      bail!("{} is never closed", unclosed.name.to_ascii_uppercase());
+    }
+    root.context("no calendar component")
+  }
+}
+
+impl Property {
+  fn parse(name: &str, params: Params, raw: &str) -> Result<Self> {
+    let mut value_type = None;
+    let mut kept = Vec::new();
+    for (param, values) in params {
+      if param.eq_ignore_ascii_case("VALUE") {
+        value_type = values
+          .first()
+          .map(|value| ValueType::from_value_param(value));
+      } else {
+        kept.push((param.to_ascii_lowercase(), values));
+      }
+    }
+    let value_type = value_type.unwrap_or_else(|| ValueType::of_property(name, raw));
+
+    let parts = match name {
+      "EXDATE" | "RDATE" | "CATEGORIES" | "RESOURCES" => split_unescaped(raw, ','),
+      _ => vec![raw],
+    };
+    let values = parts
+      .into_iter()
+      .map(|part| typed_value(name, value_type, part))
+      .collect::<Result<_>>()
+      .with_context(|| format!("invalid {name} value {raw:?}"))?;
+
+    Ok(Self {
+      name: name.to_ascii_lowercase(),
+      params: kept,
+      value_type,
+      values,
+    })
+  }
+}
+
+/// Parameter names with their values, in the order they are written
+type Params = Vec<(String, Vec<String>)>;
+
+/// Splits `NAME;PARAM=a,"b";OTHER=c:value`, honouring quoted parameter values
+fn split_line(line: &str) -> Result<(String, Params, &str)> {
+  let mut quoted = false;
+  let mut separators = Vec::new();
+  let mut value_at = None;
+  for (index, ch) in line.char_indices() {
+    match ch {
+      '"' => quoted = !quoted,
+      ';' if !quoted => separators.push(index),
+      ':' if !quoted => {
+        value_at = Some(index);
+        break;
+      }
+      _ => {}
+    }
+  }
+  let value_at = value_at.with_context(|| format!("line without a value: {line:?}"))?;
+
+  let head = &line[..value_at];
+  let mut fields = Vec::new();
+  let mut start = 0;
+  for separator in separators {
+    fields.push(&head[start..separator]);
+    start = separator + 1;
+  }
+  fields.push(&head[start..]);
+
+  let name = fields[0].to_ascii_uppercase();
+  if name.is_empty() {
+    bail!("line without a name: {line:?}");
+  }
+  let params = fields[1..]
+    .iter()
+    .map(|field| {
+      let (param, values) = field
+        .split_once('=')
+        .with_context(|| format!("parameter without a value in {name}: {field:?}"))?;
+      let values = split_quoted(values)
+        .into_iter()
+        .map(|value| value.trim_matches('"').to_string())
+        .collect();
+      Ok((param.to_string(), values))
+    })
+    .collect::<Result<_>>()?;
+  Ok((name, params, &line[value_at + 1..]))
+}
+
+/// Parameter values are separated by commas outside quotes
+fn split_quoted(value: &str) -> Vec<&str> {
+  let mut quoted = false;
+  let mut parts = Vec::new();
+  let mut start = 0;
+  for (index, ch) in value.char_indices() {
+    match ch {
+      '"' => quoted = !quoted,
+      ',' if !quoted => {
+        parts.push(&value[start..index]);
+        start = index + 1;
+      }
+      _ => {}
+    }
+  }
+  parts.push(&value[start..]);
+  parts
+}
+
+/// Property values are separated by delimiters without a backslash before them
+fn split_unescaped(value: &str, delimiter: char) -> Vec<&str> {
+  let mut escaped = false;
+  let mut parts = Vec::new();
+  let mut start = 0;
+  for (index, ch) in value.char_indices() {
+    match ch {
+      _ if escaped => escaped = false,
+      '\\' => escaped = true,
+      ch if ch == delimiter => {
+        parts.push(&value[start..index]);
+        start = index + 1;
+      }
+      _ => {}
+    }
+  }
+  parts.push(&value[start..]);
+  parts
+}
+
+fn unescape_text(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  let mut chars = value.chars();
+  while let Some(ch) = chars.next() {
+    if ch != '\\' {
+      out.push(ch);
+      continue;
+    }
+    match chars.next() {
+      Some('n' | 'N') => out.push('\n'),
+      Some(other) => out.push(other),
+      None => out.push('\\'),
+    }
+  }
+  out
+}
+
+fn typed_value(name: &str, value_type: ValueType, raw: &str) -> Result<Value> {
+  Ok(match value_type {
+    ValueType::Text => Value::String(unescape_text(raw)),
+    ValueType::Date => Value::String(date(raw)?),
+    ValueType::DateTime => Value::String(date_time(raw)?),
+    ValueType::Integer => Value::Number(raw.parse::<i64>()?.into()),
+    ValueType::Float if name == "GEO" => {
+      let (latitude, longitude) = raw.split_once(';').context("GEO needs two numbers")?;
+      Value::Array(vec![float(latitude)?, float(longitude)?])
+    }
+    ValueType::Float => float(raw)?,
+    ValueType::Boolean => Value::Bool(raw.eq_ignore_ascii_case("TRUE")),
+    ValueType::UtcOffset => Value::String(utc_offset(raw)?),
+    ValueType::Recur => recur(raw)?,
+    ValueType::Duration | ValueType::Uri | ValueType::Unknown => Value::String(raw.to_string()),
+  })
+}
+
+fn float(raw: &str) -> Result<Value> {
+  Number::from_f64(raw.trim().parse()?)
+    .map(Value::Number)
+    .context("not a finite number")
+}
+
+/// `20260115` as `2026-01-15`
+fn date(raw: &str) -> Result<String> {
+  if raw.len() != 8 || !raw.bytes().all(|byte| byte.is_ascii_digit()) {
+    bail!("not a date");
+  }
+  Ok(format!("{}-{}-{}", &raw[..4], &raw[4..6], &raw[6..]))
+}
+
+/// `20260115T100000Z` as `2026-01-15T10:00:00Z`
+fn date_time(raw: &str) -> Result<String> {
+  let (day, time) = raw.split_once('T').context("not a date-time")?;
+  let (time, utc) = match time.strip_suffix('Z') {
+    Some(time) => (time, "Z"),
+    None => (time, ""),
+  };
+  if time.len() != 6 || !time.bytes().all(|byte| byte.is_ascii_digit()) {
+    bail!("not a date-time");
+  }
+  Ok(format!(
+    "{}T{}:{}:{}{utc}",
+    date(day)?,
+    &time[..2],
+    &time[2..4],
+    &time[4..]
+  ))
+}
+
+/// `+0130` as `+01:30`, keeping seconds when there are some
+fn utc_offset(raw: &str) -> Result<String> {
+  let digits = raw.get(1..).unwrap_or_default();
+  if !(raw.starts_with('+') || raw.starts_with('-'))
+    || !matches!(digits.len(), 4 | 6)
+    || !digits.bytes().all(|byte| byte.is_ascii_digit())
+  {
+    bail!("not a UTC offset");
+  }
+  let mut out = format!("{}{}:{}", &raw[..1], &digits[..2], &digits[2..4]);
+  if digits.len() == 6 {
+    out.push(':');
+    out.push_str(&digits[4..]);
+  }
+  Ok(out)
+}
+
+/// `FREQ=WEEKLY;COUNT=8` as `{"freq": "WEEKLY", "count": 8}`
+fn recur(raw: &str) -> Result<Value> {
+  let mut rule = Map::new();
+  for part in raw.split(';') {
+    let (key, value) = part.split_once('=').context("rule part without a value")?;
+    let key = key.to_ascii_lowercase();
+    let values = value
+      .split(',')
+      .map(|value| match key.as_str() {
+        "count" | "interval" | "bysecond" | "byminute" | "byhour" | "bymonthday" | "byyearday"
+        | "byweekno" | "bymonth" | "bysetpos" => Ok(Value::Number(value.parse::<i64>()?.into())),
+        "until" if value.contains('T') => date_time(value).map(Value::String),
+        "until" => date(value).map(Value::String),
+        _ => Ok(Value::String(value.to_string())),
+      })
+      .collect::<Result<Vec<_>>>()?;
+    let value = match <[Value; 1]>::try_from(values) {
+      Ok([single]) => single,
+      Err(several) => Value::Array(several),
+    };
+    rule.insert(key, value);
+  }
+  Ok(Value::Object(rule))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::collections::HashMap;
+
+  use chrono::{DateTime, Duration, NaiveDate};
+  use icalendar::parser::{self as ics_parser, read_calendar, unfold};
+  use serde_json::json;
+
+  use super::jcal::to_jcal;
+  use super::xcal::to_xcal;
+  use super::*;
+  use crate::cache::{CancelledItem, EventStamp, FeedRevisions, TrackedItem};
+  use crate::config::{CalendarLanguage, UnconfirmedExamTime};
+  use crate::i18n::Catalogs;
+  use crate::ics::{RenderOptions, class_uid, render_calendar, series_uid};
+  use crate::models::{ExamEvent, Instructor, Place, PlanItem};
+  use crate::recurrence::{self, PlanEntry};
+  use crate::templates::Templates;
+  use crate::timezone::OutputZone;
+
+  const ZONES: [OutputZone; 3] = [
+    OutputZone::Floating,
+    OutputZone::Utc,
+    OutputZone::Zone(chrono_tz::America::New_York),
+  ];
+
+  fn plan_item(week: i64) -> PlanItem {
+    let starts_at = NaiveDate::from_ymd_opt(2026, 3, 19)
+      .and_then(|day| day.and_hms_opt(10, 0, 0))
+      .expect("valid time")
+      + Duration::weeks(week);
+    PlanItem {
+      starts_at,
+      ends_at: starts_at + Duration::minutes(90),
+      subject_name: "Algebra; liniowa, \"A\" <B> & C".to_string(),
+      class_type: "Wyklad".to_string(),
+      class_type_short: "W".to_string(),
+      room_number: Some("A12".to_string()),
+      room_address: Some("Sterlinga 26".to_string()),
+      webinar: false,
+      instructors: vec![Instructor {
+        full_name: "Jan Kowalski".to_string(),
+      }],
+      schedule_item_id: 100 + week,
+      form_color: Some("#123456".to_string()),
+      meeting_url: Some("https://teams.microsoft.com/l/meetup-join/1".to_string()),
+      place: Some(Place {
+        name: "Budynek A".to_string(),
+        address: "Sterlinga 26, Łódź".to_string(),
+        latitude: 51.776,
+        longitude: 19.463,
+      }),
+      merged_from: Vec::new(),
+    }
+  }
+
+  fn exam() -> ExamEvent {
+    ExamEvent {
+      published_data_id: 777,
+      subject: "Algebra".to_string(),
+      notes: Some("Pisemny\nz kalkulatorem".to_string()),
+      location: None,
+      lecturer: None,
+      details: None,
+      starts: plan_item(5).starts_at,
+      ends: plan_item(5).ends_at,
+      is_retake: true,
+      time_confirmed: false,
+      end_estimated: false,
+      programmes: Vec::new(),
+      meeting_url: None,
+    }
+  }
+
+  /// A series with a moved and a cancelled occurrence, plus an exam, with
+  /// alarms, colours, links and coordinates
+  fn rich_feed(zone: OutputZone) -> String {
+    let mut items: Vec<PlanItem> = (0..5).map(plan_item).collect();
+    items[3].room_number = Some("B7".to_string());
+    let stamp = EventStamp {
+      sequence: 2,
+      created: DateTime::from_timestamp(1_767_225_600, 0).expect("valid timestamp"),
+      last_modified: DateTime::from_timestamp(1_768_000_000, 0).expect("valid timestamp"),
+    };
+    let mut stamps: HashMap<_, _> = items
+      .iter()
+      .map(|item| (class_uid("a1b2c3d4e5f60718", item), stamp))
+      .collect();
+    let cancelled = CancelledItem {
+      item: TrackedItem::Class(items.remove(1)),
+      stamp,
+      series: None,
+    };
+    stamps.extend(
+      recurrence::group(&items)
+        .iter()
+        .filter_map(|entry| match entry {
+          PlanEntry::Series(series) => Some((series_uid("a1b2c3d4e5f60718", series), stamp)),
+          PlanEntry::Single(_) => None,
+        }),
+    );
+    let revisions = FeedRevisions {
+      stamps,
+      cancelled: vec![cancelled],
+    };
+    render_calendar(
+      "a1b2c3d4e5f60718",
+      &items,
+      &[exam()],
+      &revisions,
+      &RenderOptions {
+        lang: CalendarLanguage::PL,
+        unconfirmed_exams: UnconfirmedExamTime::Tentative,
+        compress_series: true,
+        zone,
+        catalogs: &Catalogs::default(),
+        templates: &Templates::default(),
+      },
+    )
+    .expect("render succeeds")
+  }
+
+  /// One property in ICS terms: component path, name, parameters and value
+  type Line = (String, String, Vec<(String, String)>, String);
+
+  /// Rule parts may come back in another order
+  fn normalized(mut lines: Vec<Line>) -> Vec<Line> {
+    for (.., value) in &mut lines {
+      if value.starts_with("FREQ=") || value.contains(";FREQ=") {
+        let mut parts: Vec<&str> = value.split(';').collect();
+        parts.sort_unstable();
+        *value = parts.join(";");
+      }
+    }
+    lines.sort();
+    lines
+  }
+
+  /// The feed as the icalendar crate's own parser reads it
+  fn ics_lines(ics: &str) -> Vec<Line> {
+    let unfolded = unfold(ics);
+    let calendar = read_calendar(&unfolded).expect("feed parses");
+    let mut out = Vec::new();
+    push_ics_properties("VCALENDAR", &calendar.properties, &mut out);
+    for component in &calendar.components {
+      push_ics_component("VCALENDAR", component, &mut out);
+    }
+    normalized(out)
+  }
+
+  fn push_ics_component(path: &str, component: &ics_parser::Component, out: &mut Vec<Line>) {
+    let path = format!("{path}/{}", component.name.as_str());
+    push_ics_properties(&path, &component.properties, out);
+    for child in &component.components {
+      push_ics_component(&path, child, out);
+    }
+  }
+
+  fn push_ics_properties(path: &str, properties: &[ics_parser::Property], out: &mut Vec<Line>) {
+    for property in properties {
+      let mut params: Vec<(String, String)> = property
+        .params
+        .iter()
+        .filter(|param| !param.key.as_str().eq_ignore_ascii_case("VALUE"))
+        .map(|param| {
+          let value = param.val.as_ref().map_or("", |value| value.as_str());
+          (
+            param.key.as_str().to_ascii_uppercase(),
+            value.trim_matches('"').to_string(),
+          )
+        })
+        .collect();
+      params.sort();
+      out.push((
+        path.to_string(),
+        property.name.as_str().to_ascii_uppercase(),
+        params,
+        property.val.as_str().to_string(),
+      ));
+    }
+  }
+
+  /// A jCal document turned back into ICS terms
+  fn jcal_lines(jcal: &Value) -> Vec<Line> {
+    let mut out = Vec::new();
+    push_jcal_component("", jcal, &mut out);
+    normalized(out)
+  }
+
+  fn push_jcal_component(path: &str, component: &Value, out: &mut Vec<Line>) {
+    let name = component[0].as_str().expect("name").to_ascii_uppercase();
+    let path = if path.is_empty() {
+      name
+    } else {
+      format!("{path}/{name}")
+    };
+    for property in component[1].as_array().expect("properties") {
+      let name = property[0].as_str().expect("name").to_ascii_uppercase();
+      let mut params: Vec<(String, String)> = property[1]
+        .as_object()
+        .expect("params")
+        .iter()
+        .map(|(key, value)| {
+          let value = match value {
+            Value::Array(values) => values
+              .iter()
+              .map(|value| value.as_str().expect("text"))
+              .collect::<Vec<_>>()
+              .join(","),
+            value => value.as_str().expect("text").to_string(),
+          };
+          (key.to_ascii_uppercase(), value)
+        })
+        .collect();
+      params.sort();
+      let value_type = property[2].as_str().expect("type");
+      let values: Vec<String> = property.as_array().expect("property")[3..]
+        .iter()
+        .map(|value| ics_value(&name, value_type, value))
+        .collect();
+      out.push((path.clone(), name, params, values.join(",")));
+    }
+    for child in component[2].as_array().expect("components") {
+      push_jcal_component(&path, child, out);
+    }
+  }
+
+  /// A typed value back in ICS syntax, written independently of the reader
+  fn ics_value(name: &str, value_type: &str, value: &Value) -> String {
+    let compact = |text: &str| text.replace(['-', ':'], "");
+    match (value_type, value) {
+      ("date" | "date-time", Value::String(text)) => compact(text),
+      ("utc-offset", Value::String(text)) => format!("{}{}", &text[..1], compact(&text[1..])),
+      ("float", Value::Array(pair)) if name == "GEO" => format!("{};{}", pair[0], pair[1]),
+      ("recur", Value::Object(rule)) => rule
+        .iter()
+        .map(|(key, value)| {
+          let value = match value {
+            Value::Array(values) => values
+              .iter()
+              .map(|value| ics_value(name, "unknown", value))
+              .collect::<Vec<_>>()
+              .join(","),
+            value => ics_value(name, "unknown", value),
+          };
+          format!("{}={value}", key.to_ascii_uppercase())
+        })
+        .collect::<Vec<_>>()
+        .join(";"),
+      (_, Value::String(text)) => text.clone(),
+      (_, other) => other.to_string(),
+    }
+  }
+
+  /// An element of the xCal output, which has no attributes past the root
+  /// and no text next to child elements
+  #[derive(Debug, Default)]
+  struct Element {
+    name: String,
+    text: String,
+    children: Vec<Element>,
+  }
+
+  fn read_xml(xml: &str) -> Element {
+    let (_, body) = xml.split_once("?>").expect("XML declaration");
+    let mut open = vec![Element::default()];
+    let mut rest = body.trim();
+    while !rest.is_empty() {
+      if let Some(tag) = rest.strip_prefix("</") {
+        let end = tag.find('>').expect("closed tag");
+        let done = open.pop().expect("open element");
+        assert_eq!(done.name, tag[..end], "balanced tags");
+        open.last_mut().expect("parent").children.push(done);
+        rest = &tag[end + 1..];
+      } else if let Some(tag) = rest.strip_prefix('<') {
+        let end = tag.find('>').expect("closed tag");
+        let name = tag[..end].split(' ').next().unwrap_or_default();
+        open.push(Element {
+          name: name.to_string(),
+          ..Element::default()
+        });
+        rest = &tag[end + 1..];
+      } else {
+        let end = rest.find('<').unwrap_or(rest.len());
+        let text = rest[..end]
+          .replace("&lt;", "<")
+          .replace("&gt;", ">")
+          .replace("&quot;", "\"")
+          .replace("&amp;", "&");
+        open.last_mut().expect("element").text.push_str(&text);
+        rest = &rest[end..];
+      }
+    }
+    let mut document = open.pop().expect("document");
+    assert!(open.is_empty(), "unclosed elements");
+    document.children.remove(0)
+  }
+
+  /// An xCal document turned back into ICS terms
+  fn xcal_lines(xml: &str) -> Vec<Line> {
+    let root = read_xml(xml);
+    assert_eq!(root.name, "icalendar");
+    let mut out = Vec::new();
+    push_xcal_component("", &root.children[0], &mut out);
+    normalized(out)
+  }
+
+  fn child<'a>(element: &'a Element, name: &str) -> Option<&'a Element> {
+    element.children.iter().find(|child| child.name == name)
+  }
+
+  fn push_xcal_component(path: &str, component: &Element, out: &mut Vec<Line>) {
+    let name = component.name.to_ascii_uppercase();
+    let path = if path.is_empty() {
+      name
+    } else {
+      format!("{path}/{name}")
+    };
+    let properties = child(component, "properties").expect("properties");
+    for property in &properties.children {
+      let name = property.name.to_ascii_uppercase();
+      let mut params: Vec<(String, String)> = child(property, "parameters")
+        .map(|parameters| {
+          parameters
+            .children
+            .iter()
+            .map(|param| {
+              let values: Vec<&str> = param
+                .children
+                .iter()
+                .map(|value| value.text.as_str())
+                .collect();
+              (param.name.to_ascii_uppercase(), values.join(","))
+            })
+            .collect()
+        })
+        .unwrap_or_default();
+      params.sort();
+
+      let values: Vec<&Element> = property
+        .children
+        .iter()
+        .filter(|value| value.name != "parameters")
+        .collect();
+      let value = match values.as_slice() {
+        [latitude, longitude] if latitude.name == "latitude" => {
+          assert_eq!(longitude.name, "longitude");
+          format!("{};{}", latitude.text, longitude.text)
+        }
+        [recur] if recur.name == "recur" => {
+          let mut parts: Vec<(String, Vec<&str>)> = Vec::new();
+          for part in &recur.children {
+            match parts.last_mut() {
+              Some((name, values)) if *name == part.name.to_ascii_uppercase() => {
+                values.push(&part.text);
+              }
+              _ => parts.push((part.name.to_ascii_uppercase(), vec![&part.text])),
+            }
+          }
+          parts
+            .iter()
+            .map(|(name, values)| format!("{name}={}", values.join(",")))
+            .collect::<Vec<_>>()
+            .join(";")
+        }
+        values => values
+          .iter()
+          .map(|value| ics_value(&name, &value.name, &Value::String(value.text.clone())))
+          .collect::<Vec<_>>()
+          .join(","),
+      };
+      out.push((path.clone(), name, params, value));
+    }
+    if let Some(components) = child(component, "components") {
+      for nested in &components.children {
+        push_xcal_component(&path, nested, out);
+      }
+    }
+  }
+
+  #[test]
+  fn jcal_and_xcal_carry_everything_in_the_ics_feed() {
+    for zone in ZONES {
+      let ics = rich_feed(zone);
+      let calendar = Component::parse(&ics).expect("feed parses");
+      let expected = ics_lines(&ics);
+
+      assert!(expected.len() > 50, "{zone}: {}", expected.len());
+      assert_eq!(jcal_lines(&to_jcal(&calendar)), expected, "{zone}");
+      assert_eq!(xcal_lines(&to_xcal(&calendar)), expected, "{zone}");
+    }
+  }
+
+  fn find<'a>(component: &'a Value, name: &str) -> &'a Value {
+    component[1]
+      .as_array()
+      .expect("properties")
+      .iter()
+      .find(|property| property[0] == name)
+      .unwrap_or(&Value::Null)
+  }
+
+  #[test]
+  fn jcal_values_follow_rfc_7265() {
+    let calendar = Component::parse(&rich_feed(OutputZone::Utc)).expect("feed parses");
+    let jcal = to_jcal(&calendar);
+    let components = jcal[2].as_array().expect("components");
+    let master = components
+      .iter()
+      .find(|component| !find(component, "rrule").is_null())
+      .expect("series master");
+
+    assert_eq!(jcal[0], "vcalendar");
+    assert_eq!(components[0][0], "vtimezone");
+    assert_eq!(
+      find(master, "rrule"),
+      &json!(["rrule", {}, "recur", {"freq": "WEEKLY", "interval": 2, "count": 3}])
+    );
+    assert_eq!(
+      find(master, "dtstart"),
+      &json!(["dtstart", {"tzid": "Europe/Warsaw"}, "date-time", "2026-03-19T10:00:00"])
+    );
+    assert_eq!(
+      find(master, "geo"),
+      &json!(["geo", {}, "float", [51.776, 19.463]])
+    );
+    assert_eq!(
+      find(master, "conference")[1],
+      json!({"feature": ["AUDIO", "VIDEO"], "label": "Spotkanie online"})
+    );
+    assert_eq!(find(master, "sequence")[3], 2);
+    assert_eq!(find(master, "color")[3], "#123456");
+    assert!(
+      find(master, "summary")[3]
+        .as_str()
+        .is_some_and(|summary| summary.starts_with("Algebra; liniowa, \"A\" <B> & C"))
+    );
+    let alarm = &master[2][0];
+    assert_eq!(alarm[0], "valarm");
+    assert_eq!(find(alarm, "trigger")[2], "duration");
+    assert_eq!(
+      find(&components[0][2][0], "tzoffsetto"),
+      &json!(["tzoffsetto", {}, "utc-offset", "+01:00"])
+    );
+  }
+
+  #[test]
+  fn xcal_spells_out_structured_values() {
+    let calendar = Component::parse(&rich_feed(OutputZone::Floating)).expect("feed parses");
+    let xml = to_xcal(&calendar);
+
+    assert!(xml.starts_with(
+      "<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<icalendar xmlns=\"urn:ietf:params:xml:ns:icalendar-2.0\"><vcalendar><properties>"
+    ));
+    assert!(xml.contains(
+      "<rrule><recur><count>3</count><freq>WEEKLY</freq><interval>2</interval></recur></rrule>"
+    ));
+    assert!(xml.contains("<geo><latitude>51.776</latitude><longitude>19.463</longitude></geo>"));
+    assert!(xml.contains("<dtstart><date-time>2026-03-19T10:00:00</date-time></dtstart>"));
+    assert!(xml.contains("<feature><text>AUDIO</text><text>VIDEO</text></feature>"));
+    assert!(xml.contains("<x-address><unknown>Sterlinga 26, Łódź</unknown></x-address>"));
+    assert!(xml.contains("&quot;A&quot; &lt;B&gt; &amp; C"));
+  }
+
+  #[test]
+  fn malformed_calendars_are_rejected() {
+    assert!(Component::parse("BEGIN:VCALENDAR\r\n").is_err());
+    assert!(Component::parse("BEGIN:VCALENDAR\r\nEND:VEVENT\r\n").is_err());
+    assert!(Component::parse("BEGIN:VCALENDAR\r\nDTSTART:soon\r\nEND:VCALENDAR\r\n").is_err());
+    assert!(Component::parse("BEGIN:VCALENDAR\r\nX-A\r\nEND:VCALENDAR\r\n").is_err());
+    assert!(Component::parse("").is_err());
+  }
+}
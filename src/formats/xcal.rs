@@ -0,0 +1,113 @@
+use std::fmt::Write;
+
+use serde_json::Value;
+
+use super::{Component, Property, ValueType};
+
+const NAMESPACE: &str = "urn:ietf:params:xml:ns:icalendar-2.0";
+
+/// RFC 6321 document of a calendar
+#[must_use]
+pub fn to_xcal(calendar: &Component) -> String {
+  let mut out =
+    format!("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n<icalendar xmlns=\"{NAMESPACE}\">");
+  component(&mut out, calendar);
+  out.push_str("</icalendar>\n");
+  out
+}
+
+fn component(out: &mut String, component: &Component) {
+  let _ = write!(out, "<{}><properties>", component.name);
+  for property in &component.properties {
+    self::property(out, property);
+  }
+  out.push_str("</properties>");
+  if !component.components.is_empty() {
+    out.push_str("<components>");
+    for child in &component.components {
+      self::component(out, child);
+    }
+    out.push_str("</components>");
+  }
+  let _ = write!(out, "</{}>", component.name);
+}
+
+fn property(out: &mut String, property: &Property) {
+  let _ = write!(out, "<{}>", property.name);
+  if !property.params.is_empty() {
+    out.push_str("<parameters>");
+    for (name, values) in &property.params {
+      // Extension parameters have no registered type
+      let kind = if name.starts_with("x-") {
+        "unknown"
+      } else {
+        "text"
+      };
+      let _ = write!(out, "<{name}>");
+      for value in values {
+        let _ = write!(out, "<{kind}>{}</{kind}>", escape(value));
+      }
+      let _ = write!(out, "</{name}>");
+    }
+    out.push_str("</parameters>");
+  }
+  for value in &property.values {
+    self::value(out, property.value_type, value);
+  }
+  let _ = write!(out, "</{}>", property.name);
+}
+
+/// Values as their type's element, except `GEO` and `RRULE`, which RFC 6321
+/// spells out part by part
+fn value(out: &mut String, value_type: ValueType, value: &Value) {
+  match (value_type, value) {
+    (ValueType::Float, Value::Array(pair)) if pair.len() == 2 => {
+      let _ = write!(
+        out,
+        "<latitude>{}</latitude><longitude>{}</longitude>",
+        pair[0], pair[1]
+      );
+    }
+    (ValueType::Recur, Value::Object(rule)) => {
+      out.push_str("<recur>");
+      for (part, values) in rule {
+        let values = match values {
+          Value::Array(values) => values.as_slice(),
+          single => std::slice::from_ref(single),
+        };
+        for value in values {
+          let _ = write!(out, "<{part}>{}</{part}>", escape(&text(value)));
+        }
+      }
+      out.push_str("</recur>");
+    }
+    (value_type, value) => {
+      let kind = value_type.as_str();
+      let _ = write!(out, "<{kind}>{}</{kind}>", escape(&text(value)));
+    }
+  }
+}
+
+fn text(value: &Value) -> String {
+  match value {
+    Value::String(text) => text.clone(),
+    other => other.to_string(),
+  }
+}
+
+/// Markup characters as entities; control characters XML cannot carry are dropped
+fn escape(value: &str) -> String {
+  let mut out = String::with_capacity(value.len());
+  for ch in value.chars() {
+    match ch {
+      '&' => out.push_str("&amp;"),
+      '<' => out.push_str("&lt;"),
+      '>' => out.push_str("&gt;"),
+      '"' => out.push_str("&quot;"),
+      '\t' | '\n' | '\r' => out.push(ch),
+      ch if ch.is_control() => {}
+      ch => out.push(ch),
+    }
+  }
+  out
+}
@@ -1,14 +1,38 @@
-use super::IcsTexts;
+use std::borrow::Cow::Borrowed;
 
-pub static EN: IcsTexts = IcsTexts {
-  calendar_name: "AHE Schedule",
-  location_webinar: "Webinar",
-  location_default: "Room",
-  label_exam: "Exam",
-  label_exam_retake: "Resit exam",
-  label_exam_type: "Type",
-  label_details: "Details",
-  label_instructors: "Instructors",
-  label_type: "Class type",
-  missing_data: "(no data)",
+use super::{Catalog, ErrorTexts, IcsTexts};
+
+pub static EN: Catalog = Catalog {
+  ics: IcsTexts {
+    calendar_name: Borrowed("AHE Schedule"),
+    location_webinar: Borrowed("Webinar"),
+    location_default: Borrowed("Room"),
+    label_blocks: Borrowed("Blocks"),
+    label_cancelled: Borrowed("CANCELLED"),
+    label_exam: Borrowed("Exam"),
+    label_exam_retake: Borrowed("Resit exam"),
+    label_exam_type: Borrowed("Type"),
+    label_details: Borrowed("Details"),
+    label_instructors: Borrowed("Instructors"),
+    label_map: Borrowed("Map"),
+    label_meeting: Borrowed("Online meeting"),
+    label_programme: Borrowed("Programme"),
+    label_type: Borrowed("Class type"),
+    missing_data: Borrowed("(no data)"),
+    note_time_unconfirmed: Borrowed("Time not yet published"),
+    note_end_estimated: Borrowed("End time estimated"),
+  },
+  errors: ErrorTexts {
+    invalid_token: Borrowed("invalid calendar token"),
+    invalid_range: Borrowed("to must be >= from"),
+    invalid_timezone: Borrowed("tz must be floating, UTC or an IANA zone name"),
+    range_too_long: Borrowed("date range must not exceed {days} days"),
+    invalid_credentials: Borrowed("invalid WPS username or password"),
+    account_locked: Borrowed("WPS account is locked"),
+    upstream_credentials: Borrowed("upstream rejected the configured credentials"),
+    upstream_unavailable: Borrowed("upstream unavailable"),
+    upstream_timeout: Borrowed("upstream timed out"),
+    upstream_unexpected: Borrowed("unexpected upstream response"),
+    internal_error: Borrowed("internal server error"),
+  },
 };
@@ -1,25 +1,277 @@
 pub mod en;
 pub mod pl;
+pub mod uk;
 
-use crate::config::CalendarLanguage;
+use std::borrow::Cow;
+use std::path::Path;
 
+use anyhow::{Context, Result, bail};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+use crate::config::{CalendarLanguage, parse_structured};
+use crate::templates::Template;
+
+type Text = Cow<'static, str>;
+
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
 pub struct IcsTexts {
-  pub calendar_name: &'static str,
-  pub location_webinar: &'static str,
-  pub location_default: &'static str,
-  pub label_exam: &'static str,
-  pub label_exam_retake: &'static str,
-  pub label_exam_type: &'static str,
-  pub label_details: &'static str,
-  pub label_instructors: &'static str,
-  pub label_type: &'static str,
-  pub missing_data: &'static str,
-}
-
-#[must_use]
-pub fn ics_texts(lang: CalendarLanguage) -> &'static IcsTexts {
-  match lang {
-    CalendarLanguage::Pl => &pl::PL,
-    CalendarLanguage::En => &en::EN,
+  pub calendar_name: Text,
+  pub location_webinar: Text,
+  pub location_default: Text,
+  pub label_blocks: Text,
+  pub label_cancelled: Text,
+  pub label_exam: Text,
+  pub label_exam_retake: Text,
+  pub label_exam_type: Text,
+  pub label_details: Text,
+  pub label_instructors: Text,
+  pub label_map: Text,
+  pub label_meeting: Text,
+  pub label_programme: Text,
+  pub label_type: Text,
+  pub missing_data: Text,
+  pub note_time_unconfirmed: Text,
+  pub note_end_estimated: Text,
+}
+
+/// Response bodies of failed calendar requests
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ErrorTexts {
+  pub invalid_token: Text,
+  pub invalid_range: Text,
+  pub invalid_timezone: Text,
+  /// May use `{days}` for the configured limit
+  pub range_too_long: Text,
+  pub invalid_credentials: Text,
+  pub account_locked: Text,
+  pub upstream_credentials: Text,
+  pub upstream_unavailable: Text,
+  pub upstream_timeout: Text,
+  pub upstream_unexpected: Text,
+  pub internal_error: Text,
+}
+
+/// Placeholders `range_too_long` may use
+pub const RANGE_FIELDS: &[&str] = &["days"];
+
+/// Every text of one language
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Catalog {
+  pub ics: IcsTexts,
+  pub errors: ErrorTexts,
+}
+
+impl Catalog {
+  /// Reads a catalog from TOML or YAML, reporting every missing or unknown
+  /// key at once rather than the first one serde trips over
+  fn parse(path: &Path, contents: &str) -> Result<Self> {
+    let value: Value = parse_structured(path, contents, "translation catalog")?;
+    let reference = serde_json::to_value(&en::EN).context("built-in catalog serializes")?;
+
+    let mut expected = Vec::new();
+    let mut found = Vec::new();
+    keys(&reference, "", &mut expected);
+    keys(&value, "", &mut found);
+    let missing: Vec<_> = expected.iter().filter(|key| !found.contains(key)).collect();
+    let unknown: Vec<_> = found.iter().filter(|key| !expected.contains(key)).collect();
+    if !missing.is_empty() || !unknown.is_empty() {
+      bail!(
+        "translation catalog {} is incomplete; missing keys: [{}], unknown keys: [{}]",
+        path.display(),
+        join(&missing),
+        join(&unknown)
+      );
+    }
+
+    let catalog: Self = serde_json::from_value(value)
+      .with_context(|| format!("invalid translation catalog {}", path.display()))?;
+    Template::parse(&catalog.errors.range_too_long, RANGE_FIELDS)
+      .with_context(|| format!("invalid errors.range_too_long in {}", path.display()))?;
+    Ok(catalog)
+  }
+}
+
+/// Dotted paths of every leaf of a catalog, such as `ics.label_exam`
+fn keys(value: &Value, prefix: &str, out: &mut Vec<String>) {
+  match value {
+    Value::Object(map) => {
+      for (key, value) in map {
+        keys(value, &format!("{prefix}{key}."), out);
+      }
+    }
+    _ => out.push(prefix.trim_end_matches('.').to_string()),
+  }
+}
+
+fn join(keys: &[&String]) -> String {
+  keys
+    .iter()
+    .map(|key| key.as_str())
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// Translation catalogs by language: the built-in Polish, English and
+/// Ukrainian ones plus any loaded from files
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Catalogs {
+  entries: Vec<(CalendarLanguage, Cow<'static, Catalog>)>,
+}
+
+impl Default for Catalogs {
+  fn default() -> Self {
+    Self {
+      entries: vec![
+        (CalendarLanguage::PL, Cow::Borrowed(&pl::PL)),
+        (CalendarLanguage::EN, Cow::Borrowed(&en::EN)),
+        (CalendarLanguage::UK, Cow::Borrowed(&uk::UK)),
+      ],
+    }
+  }
+}
+
+impl Catalogs {
+  /// Adds every `<code>.toml`, `<code>.yaml` or `<code>.yml` file in `dir` to
+  /// the built-in catalogs; a file for a built-in language replaces it
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the directory cannot be read, a file name is not a
+  /// language code, or a catalog is missing keys or has unknown ones.
+  pub fn load_dir(dir: &Path) -> Result<Self> {
+    let mut paths = std::fs::read_dir(dir)
+      .with_context(|| format!("failed to read translations directory {}", dir.display()))?
+      .map(|entry| entry.map(|entry| entry.path()))
+      .collect::<std::io::Result<Vec<_>>>()
+      .with_context(|| format!("failed to read translations directory {}", dir.display()))?;
+    paths.sort();
+
+    let mut catalogs = Self::default();
+    for path in paths {
+      let is_catalog = path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| {
+          matches!(
+            extension.to_ascii_lowercase().as_str(),
+            "toml" | "yaml" | "yml"
+          )
+        });
+      if !is_catalog {
+        continue;
+      }
+
+      let stem = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .unwrap_or_default();
+      let Some(lang) = CalendarLanguage::from_code(stem) else {
+        bail!(
+          "translation catalog {} must be named after a language code, e.g. uk.toml",
+          path.display()
+        );
+      };
+      let contents = std::fs::read_to_string(&path)
+        .with_context(|| format!("failed to read translation catalog {}", path.display()))?;
+      catalogs.insert(lang, Catalog::parse(&path, &contents)?);
+    }
+    Ok(catalogs)
+  }
+
+  fn insert(&mut self, lang: CalendarLanguage, catalog: Catalog) {
+    match self.entries.iter_mut().find(|(code, _)| *code == lang) {
+      Some((_, existing)) => *existing = Cow::Owned(catalog),
+      None => self.entries.push((lang, Cow::Owned(catalog))),
+    }
+  }
+
+  #[must_use]
+  pub fn contains(&self, lang: CalendarLanguage) -> bool {
+    self.entries.iter().any(|(code, _)| *code == lang)
+  }
+
+  /// Catalog of a language, or the English one if there is none for it
+  #[must_use]
+  pub fn get(&self, lang: CalendarLanguage) -> &Catalog {
+    self
+      .entries
+      .iter()
+      .find(|(code, _)| *code == lang)
+      .map_or(&en::EN, |(_, catalog)| catalog)
+  }
+
+  /// Languages with a catalog, built-in ones first
+  pub fn languages(&self) -> impl Iterator<Item = CalendarLanguage> + '_ {
+    self.entries.iter().map(|(code, _)| *code)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn builtin_catalogs_have_every_key() {
+    for catalog in [&pl::PL, &en::EN, &uk::UK] {
+      let toml = toml::to_string(catalog).expect("catalog serializes");
+      let parsed = Catalog::parse(Path::new("builtin.toml"), &toml).expect("complete catalog");
+      assert_eq!(&parsed, catalog);
+    }
+  }
+
+  #[test]
+  fn incomplete_catalogs_name_every_bad_key() {
+    let mut toml = toml::to_string(&en::EN).expect("catalog serializes");
+    toml = toml
+      .replace("label_map = \"Map\"\n", "")
+      .replace("label_meeting =", "label_meting =");
+
+    let error = Catalog::parse(Path::new("de.toml"), &toml).expect_err("incomplete");
+    let message = error.to_string();
+    assert!(message.contains("ics.label_map"), "{message}");
+    assert!(message.contains("ics.label_meeting"), "{message}");
+    assert!(message.contains("ics.label_meting"), "{message}");
+  }
+
+  #[test]
+  fn range_message_may_only_use_days() {
+    let toml = toml::to_string(&en::EN)
+      .expect("catalog serializes")
+      .replace("{days}", "{limit}");
+
+    assert!(Catalog::parse(Path::new("de.toml"), &toml).is_err());
+  }
+
+  #[test]
+  fn directory_catalogs_add_and_replace_languages() {
+    let dir = std::env::temp_dir().join(format!("ahe-ics-{}-translations", std::process::id()));
+    std::fs::create_dir_all(&dir).expect("create translations dir");
+    let english = toml::to_string(&en::EN).expect("catalog serializes");
+    std::fs::write(dir.join("de.toml"), english.replace("\"Map\"", "\"Karte\"")).expect("write");
+    std::fs::write(dir.join("uk.toml"), &english).expect("write");
+    std::fs::write(dir.join("README.md"), "not a catalog").expect("write");
+
+    let catalogs = Catalogs::load_dir(&dir);
+    std::fs::remove_dir_all(&dir).expect("clean up");
+    let catalogs = catalogs.expect("valid catalogs");
+
+    let german = CalendarLanguage::from_code("de").expect("valid code");
+    assert_eq!(catalogs.get(german).ics.label_map, "Karte");
+    assert_eq!(catalogs.get(CalendarLanguage::UK), &en::EN);
+    assert_eq!(catalogs.languages().count(), 4);
+  }
+
+  #[test]
+  fn catalogs_fall_back_to_english() {
+    let catalogs = Catalogs::default();
+    let german = CalendarLanguage::from_code("de").expect("valid code");
+
+    assert!(!catalogs.contains(german));
+    assert_eq!(catalogs.get(german), &en::EN);
+    assert_eq!(catalogs.get(CalendarLanguage::UK).ics.label_exam, "Іспит");
   }
 }
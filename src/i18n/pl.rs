@@ -1,14 +1,38 @@
-use super::IcsTexts;
+use std::borrow::Cow::Borrowed;
 
-pub static PL: IcsTexts = IcsTexts {
-  calendar_name: "Plan AHE",
-  location_webinar: "Webinar",
-  location_default: "Sala",
-  label_exam: "Egzamin",
-  label_exam_retake: "Egzamin poprawkowy",
-  label_exam_type: "Rodzaj",
-  label_details: "Szczegoly",
-  label_instructors: "Prowadzacy",
-  label_type: "Typ",
-  missing_data: "(brak danych)",
+use super::{Catalog, ErrorTexts, IcsTexts};
+
+pub static PL: Catalog = Catalog {
+  ics: IcsTexts {
+    calendar_name: Borrowed("Plan AHE"),
+    location_webinar: Borrowed("Webinar"),
+    location_default: Borrowed("Sala"),
+    label_blocks: Borrowed("Bloki"),
+    label_cancelled: Borrowed("ODWOLANE"),
+    label_exam: Borrowed("Egzamin"),
+    label_exam_retake: Borrowed("Egzamin poprawkowy"),
+    label_exam_type: Borrowed("Rodzaj"),
+    label_details: Borrowed("Szczegoly"),
+    label_instructors: Borrowed("Prowadzacy"),
+    label_map: Borrowed("Mapa"),
+    label_meeting: Borrowed("Spotkanie online"),
+    label_programme: Borrowed("Kierunek"),
+    label_type: Borrowed("Typ"),
+    missing_data: Borrowed("(brak danych)"),
+    note_time_unconfirmed: Borrowed("Godzina nie zostala jeszcze opublikowana"),
+    note_end_estimated: Borrowed("Godzina zakonczenia szacowana"),
+  },
+  errors: ErrorTexts {
+    invalid_token: Borrowed("nieprawidlowy token kalendarza"),
+    invalid_range: Borrowed("to musi byc >= from"),
+    invalid_timezone: Borrowed("tz musi byc floating, UTC albo nazwa strefy IANA"),
+    range_too_long: Borrowed("zakres dat nie moze przekraczac {days} dni"),
+    invalid_credentials: Borrowed("nieprawidlowa nazwa uzytkownika lub haslo WPS"),
+    account_locked: Borrowed("konto WPS jest zablokowane"),
+    upstream_credentials: Borrowed("WPS odrzucil skonfigurowane dane logowania"),
+    upstream_unavailable: Borrowed("WPS jest niedostepny"),
+    upstream_timeout: Borrowed("WPS nie odpowiedzial na czas"),
+    upstream_unexpected: Borrowed("nieoczekiwana odpowiedz WPS"),
+    internal_error: Borrowed("wewnetrzny blad serwera"),
+  },
 };
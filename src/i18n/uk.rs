@@ -0,0 +1,38 @@
+use std::borrow::Cow::Borrowed;
+
+use super::{Catalog, ErrorTexts, IcsTexts};
+
+pub static UK: Catalog = Catalog {
+  ics: IcsTexts {
+    calendar_name: Borrowed("Розклад AHE"),
+    location_webinar: Borrowed("Вебінар"),
+    location_default: Borrowed("Аудиторія"),
+    label_blocks: Borrowed("Блоки"),
+    label_cancelled: Borrowed("СКАСОВАНО"),
+    label_exam: Borrowed("Іспит"),
+    label_exam_retake: Borrowed("Перескладання іспиту"),
+    label_exam_type: Borrowed("Вид"),
+    label_details: Borrowed("Подробиці"),
+    label_instructors: Borrowed("Викладачі"),
+    label_map: Borrowed("Карта"),
+    label_meeting: Borrowed("Онлайн-зустріч"),
+    label_programme: Borrowed("Напрям"),
+    label_type: Borrowed("Тип заняття"),
+    missing_data: Borrowed("(немає даних)"),
+    note_time_unconfirmed: Borrowed("Час ще не опубліковано"),
+    note_end_estimated: Borrowed("Час завершення орієнтовний"),
+  },
+  errors: ErrorTexts {
+    invalid_token: Borrowed("недійсний токен календаря"),
+    invalid_range: Borrowed("to має бути >= from"),
+    invalid_timezone: Borrowed("tz має бути floating, UTC або назвою зони IANA"),
+    range_too_long: Borrowed("діапазон дат не може перевищувати {days} дн."),
+    invalid_credentials: Borrowed("неправильне ім'я користувача або пароль WPS"),
+    account_locked: Borrowed("обліковий запис WPS заблоковано"),
+    upstream_credentials: Borrowed("WPS відхилив налаштовані облікові дані"),
+    upstream_unavailable: Borrowed("WPS недоступний"),
+    upstream_timeout: Borrowed("WPS не відповів вчасно"),
+    upstream_unexpected: Borrowed("неочікувана відповідь WPS"),
+    internal_error: Borrowed("внутрішня помилка сервера"),
+  },
+};
@@ -1,16 +1,19 @@
 use std::fmt::Write;
 
 use anyhow::Result;
-use chrono::Duration;
-use icalendar::{Alarm, Calendar, Component, Event, EventLike, EventStatus, Property, Trigger};
+use chrono::{Duration, NaiveDateTime};
+use icalendar::{
+  Alarm, Calendar, CalendarDateTime, Component, Event, EventLike, EventStatus, Property, Trigger,
+};
 use sha2::{Digest, Sha256};
 
-use crate::config::CalendarLanguage;
-use crate::i18n::{IcsTexts, ics_texts};
-use crate::models::{ExamEvent, PlanItem};
-
-/// IANA timezone all calendar events are expressed in.
-const CALENDAR_TZ: &str = "Europe/Warsaw";
+use crate::cache::{CancelledItem, EventStamp, FeedRevisions, TrackedItem};
+use crate::config::{CalendarLanguage, UnconfirmedExamTime};
+use crate::i18n::{Catalogs, IcsTexts};
+use crate::models::{ExamEvent, Place, PlanItem};
+use crate::recurrence::{self, PlanEntry, Series};
+use crate::templates::Templates;
+use crate::timezone::{self, OutputZone, SOURCE_ZONE};
 
 /// Fallback event colours (RFC 7986 `COLOR`). Classes reuse the WPS `FormaKolor`;
 /// exams carry no colour in the feed, so these fixed values are used instead.
@@ -22,6 +25,12 @@ const CLASS_REMINDER_MINUTES: i64 = 15;
 const EXAM_REMINDER_MINUTES: i64 = 60;
 const EXAM_REMINDER_EARLY_MINUTES: i64 = 24 * 60;
 
+/// How often subscribers are asked to re-poll (`REFRESH-INTERVAL` / `X-PUBLISHED-TTL`).
+const REFRESH_INTERVAL_MINUTES: i64 = 60;
+
+/// How close to the building Apple Maps considers the event reached, in metres.
+const PLACE_RADIUS_METRES: u32 = 70;
+
 /// WPS site pages linked from calendar events via the `URL` property.
 const WPS_PLAN_URL: &str = "https://wps.ahe.lodz.pl/plan-kalendarzowy";
 const WPS_EXAM_URL: &str = "https://wps.ahe.lodz.pl/egzaminy";
@@ -42,8 +51,143 @@ pub fn calendar_id(credential_digest: &[u8], student_id: i64) -> String {
     })
 }
 
+/// Presentation settings applied to every event of a calendar
+#[derive(Clone, Copy, Debug)]
+pub struct RenderOptions<'a> {
+  pub lang: CalendarLanguage,
+  pub unconfirmed_exams: UnconfirmedExamTime,
+  /// Fold weekly classes into `RRULE` series
+  pub compress_series: bool,
+  pub zone: OutputZone,
+  pub catalogs: &'a Catalogs,
+  pub templates: &'a Templates,
+}
+
+/// Labels of one language together with the user's templates and aliases;
+/// everything that decides how an event reads
+#[derive(Clone, Copy, Debug)]
+pub struct Wording<'a> {
+  lang: CalendarLanguage,
+  zone: OutputZone,
+  texts: &'a IcsTexts,
+  templates: &'a Templates,
+}
+
+impl<'a> Wording<'a> {
+  #[must_use]
+  pub fn new(
+    lang: CalendarLanguage,
+    zone: OutputZone,
+    catalogs: &'a Catalogs,
+    templates: &'a Templates,
+  ) -> Self {
+    Self {
+      lang,
+      zone,
+      texts: &catalogs.get(lang).ics,
+      templates,
+    }
+  }
+
+  /// Zone the event times and the times in event texts are written in
+  #[must_use]
+  pub fn zone(&self) -> OutputZone {
+    self.zone
+  }
+}
+
+/// Title of a class event, from the summary template if one is set
+#[must_use]
+pub fn class_summary(item: &PlanItem, wording: &Wording) -> String {
+  match wording.templates.summary(wording.lang) {
+    Some(template) => template.render(|name| class_field(item, name, wording)),
+    None => build_summary(item, wording.templates.alias(&item.subject_name)),
+  }
+}
+
+/// Notes of a class event, from the description template if one is set
+#[must_use]
+pub fn class_description(item: &PlanItem, wording: &Wording) -> String {
+  match wording.templates.description(wording.lang) {
+    Some(template) => template.render(|name| class_field(item, name, wording)),
+    None => build_description(item, wording),
+  }
+}
+
+/// Title of an exam event, from the exam summary template if one is set
+#[must_use]
+pub fn exam_summary(exam: &ExamEvent, wording: &Wording) -> String {
+  match wording.templates.exam_summary(wording.lang) {
+    Some(template) => template.render(|name| exam_field(exam, name, wording)),
+    None => build_exam_summary(
+      exam,
+      wording.templates.alias(exam.subject.trim()),
+      wording.texts,
+    ),
+  }
+}
+
+/// Notes of an exam event, from the exam description template if one is set.
+///
+/// A template is always followed by the note that the time is not yet
+/// published or the end is estimated, so it cannot make a guess look final.
+#[must_use]
+pub fn exam_description(exam: &ExamEvent, wording: &Wording) -> String {
+  match wording.templates.exam_description(wording.lang) {
+    Some(template) => {
+      let mut description = template.render(|name| exam_field(exam, name, wording));
+      if let Some(note) = exam_time_note(exam, wording.texts) {
+        let _ = write!(description, "\n{note}");
+      }
+      description
+    }
+    None => build_exam_description(exam, wording.texts),
+  }
+}
+
+/// UID of a class event; stable for as long as WPS keeps the schedule item,
+/// or every block of a merged one
+#[must_use]
+pub fn class_uid(calendar_id: &str, item: &PlanItem) -> String {
+  if item.merged_from.is_empty() {
+    return format!(
+      "ahe-{calendar_id}-{}@wpsapi.ahe.lodz.pl",
+      item.schedule_item_id
+    );
+  }
+
+  let ids = item
+    .merged_from
+    .iter()
+    .map(|block| block.schedule_item_id.to_string())
+    .collect::<Vec<_>>()
+    .join("-");
+  format!("ahe-{calendar_id}-merged-{ids}@wpsapi.ahe.lodz.pl")
+}
+
+/// UID of an exam event; a rescheduled exam keeps it and is served as a
+/// new revision instead
+#[must_use]
+pub fn exam_uid(calendar_id: &str, exam: &ExamEvent) -> String {
+  format!(
+    "ahe-{calendar_id}-exam-{}@wpsapi.ahe.lodz.pl",
+    exam.published_data_id
+  )
+}
+
+/// UID shared by the master event of a series and its overrides
+#[must_use]
+pub fn series_uid(calendar_id: &str, series: &Series) -> String {
+  format!("ahe-{calendar_id}-series-{}@wpsapi.ahe.lodz.pl", series.key)
+}
+
 /// Renders a list of plan items into a single ICS calendar string.
 ///
+/// Events found in `revisions` carry its stamps, so an unchanged event
+/// renders identically on every poll. Its cancelled events are emitted with
+/// `STATUS:CANCELLED`, so subscribers see a class or exam was called off
+/// instead of it silently disappearing.
+///
 /// # Errors
 ///
 /// Returns an error if the calendar cannot be serialized into ICS form.
@@ -51,109 +195,606 @@ pub fn render_calendar(
   calendar_id: &str,
   items: &[PlanItem],
   exams: &[ExamEvent],
-  lang: CalendarLanguage,
+  revisions: &FeedRevisions,
+  options: &RenderOptions,
 ) -> Result<String> {
-  let texts = ics_texts(lang);
+  let wording = Wording::new(
+    options.lang,
+    options.zone,
+    options.catalogs,
+    options.templates,
+  );
+  let texts = wording.texts;
 
   let mut calendar = Calendar::new();
-  calendar.name(texts.calendar_name);
-  calendar.timezone(CALENDAR_TZ);
+  calendar.name(&texts.calendar_name);
+  calendar.timezone(options.zone.tz_name());
+  calendar.ttl(&Duration::minutes(REFRESH_INTERVAL_MINUTES));
 
-  for item in items {
-    let uid = format!(
-      "ahe-{calendar_id}-{}@wpsapi.ahe.lodz.pl",
-      item.schedule_item_id
-    );
-    let summary = build_summary(item);
-    let location = build_location(item, texts);
-    let description = build_description(item, texts);
-
-    let mut event = Event::new();
-    event
-      .uid(&uid)
-      .summary(&summary)
-      .location(&location)
-      .description(&description)
-      .starts(item.starts_at)
-      .ends(item.ends_at)
-      .status(EventStatus::Confirmed)
-      .append_property(Property::new("TRANSP", "OPAQUE"))
-      .append_property(Property::new("URL", WPS_PLAN_URL))
-      .alarm(Alarm::display(
-        &summary,
-        Trigger::before_start(Duration::minutes(CLASS_REMINDER_MINUTES)),
-      ));
-
-    let category = item.class_type.trim();
-    if !category.is_empty() {
-      event.append_property(Property::new("CATEGORIES", category));
-    }
-    if let Some(color) = item
-      .form_color
-      .as_deref()
-      .map(str::trim)
-      .filter(|value| !value.is_empty())
-    {
-      event.append_property(Property::new("COLOR", color));
+  let mut classes = if options.compress_series {
+    recurrence::group(items)
+  } else {
+    items.iter().map(PlanEntry::Single).collect()
+  };
+  let (occurrences, cancelled_alone) = place_cancelled(calendar_id, &mut classes, revisions);
+
+  for entry in &classes {
+    match entry {
+      PlanEntry::Single(item) => {
+        let uid = class_uid(calendar_id, item);
+        calendar.push(class_instance(
+          &uid,
+          item,
+          revisions.stamps.get(&uid),
+          &wording,
+        ));
+      }
+      PlanEntry::Series(series) => {
+        let uid = series_uid(calendar_id, series);
+        let cancelled: Vec<_> = occurrences
+          .iter()
+          .filter(|occurrence| occurrence.series_uid == uid)
+          .collect();
+        push_series(
+          &mut calendar,
+          calendar_id,
+          series,
+          &cancelled,
+          revisions,
+          &wording,
+        );
+      }
     }
+  }
+  // Overrides of a series that dissolved still cancel the occurrence
+  // subscribers hold under its UID
+  for occurrence in occurrences.iter().filter(|occurrence| !occurrence.in_feed) {
+    calendar.push(cancelled_occurrence(occurrence, &wording));
+  }
 
+  for exam in exams {
+    let summary = exam_summary(exam, &wording);
+    let uid = exam_uid(calendar_id, exam);
+    let stamp = revisions.stamps.get(&uid);
+    let mut event = exam_event(&uid, exam, &wording, options.unconfirmed_exams);
+    stamp_event(&mut event, stamp);
+    event
+      .alarm(reminder(&uid, &summary, EXAM_REMINDER_EARLY_MINUTES, stamp))
+      .alarm(reminder(&uid, &summary, EXAM_REMINDER_MINUTES, stamp));
     calendar.push(event.done());
   }
 
-  for exam in exams {
-    let uid = format!(
-      "ahe-{calendar_id}-exam-{}-{}@wpsapi.ahe.lodz.pl",
-      exam.published_data_id,
-      exam.starts.and_utc().timestamp()
-    );
-    let summary = build_exam_summary(exam, texts);
-    let location = build_exam_location(exam, texts);
-    let description = build_exam_description(exam, texts);
-    let category = if exam.is_retake {
-      texts.label_exam_retake
-    } else {
-      texts.label_exam
+  for cancelled in cancelled_alone {
+    let (mut event, summary) = match &cancelled.item {
+      TrackedItem::Class(item) => (
+        class_event(&class_uid(calendar_id, item), item, &wording),
+        class_summary(item, &wording),
+      ),
+      TrackedItem::Exam(exam) => (
+        exam_event(
+          &exam_uid(calendar_id, exam),
+          exam,
+          &wording,
+          options.unconfirmed_exams,
+        ),
+        exam_summary(exam, &wording),
+      ),
     };
-    let color = if exam.is_retake {
-      EXAM_RETAKE_COLOR
-    } else {
-      EXAM_COLOR
+    cancel(&mut event, &summary, &cancelled.stamp, texts);
+    calendar.push(event.done());
+  }
+
+  let has_series = classes
+    .iter()
+    .any(|entry| matches!(entry, PlanEntry::Series(_)));
+  let definitions = timezone_definitions(
+    options.zone,
+    has_series,
+    time_window(items, exams, revisions),
+  );
+  Ok(with_timezones(calendar.to_string(), &definitions))
+}
+
+/// A cancelled class served in a series, emitted as an override of it
+struct CancelledOccurrence<'a> {
+  series_uid: &'a str,
+  item: &'a PlanItem,
+  stamp: &'a EventStamp,
+  recurrence_id: NaiveDateTime,
+  /// Whether the series is still in the feed
+  in_feed: bool,
+}
+
+/// Splits cancelled events into occurrences of the series they were served
+/// in and events of their own.
+///
+/// A class stays with its series even when the recomputed series no longer
+/// covers it, e.g. when it was the last occurrence, since subscribers hold it
+/// under the series' UID and would otherwise keep it from the old rule.
+fn place_cancelled<'a>(
+  calendar_id: &str,
+  classes: &mut [PlanEntry],
+  revisions: &'a FeedRevisions,
+) -> (Vec<CancelledOccurrence<'a>>, Vec<&'a CancelledItem>) {
+  let mut occurrences = Vec::new();
+  let mut alone = Vec::new();
+  for cancelled in &revisions.cancelled {
+    let (TrackedItem::Class(item), Some(slot)) = (&cancelled.item, &cancelled.series) else {
+      alone.push(cancelled);
+      continue;
     };
+    let series = classes.iter_mut().find_map(|entry| match entry {
+      PlanEntry::Series(series) if series_uid(calendar_id, series) == slot.uid => Some(series),
+      _ => None,
+    });
+    let in_feed = series.is_some();
+    if let Some(series) = series {
+      // Emitted as a cancelled override rather than next to an `EXDATE`
+      series.take_exdate(slot.recurrence_id);
+    }
+    occurrences.push(CancelledOccurrence {
+      series_uid: &slot.uid,
+      item,
+      stamp: &cancelled.stamp,
+      recurrence_id: slot.recurrence_id,
+      in_feed,
+    });
+  }
+  (occurrences, alone)
+}
+
+/// A series master with its `RRULE` and `EXDATE`s, followed by the overrides
+/// of its moved and cancelled occurrences
+fn push_series(
+  calendar: &mut Calendar,
+  calendar_id: &str,
+  series: &Series,
+  cancelled: &[&CancelledOccurrence],
+  revisions: &FeedRevisions,
+  wording: &Wording,
+) {
+  let zone = wording.zone;
+  let uid = series_uid(calendar_id, series);
+  let mut master = class_instance(&uid, series.master, revisions.stamps.get(&uid), wording);
+  master
+    .starts(series_time(series.master.starts_at, zone))
+    .ends(series_time(series.master.ends_at, zone))
+    .add_property("RRULE", series.rrule());
+  for exdate in &series.exdates {
+    master.append_multi_property(time_property("EXDATE", &series_time(*exdate, zone)));
+  }
+  calendar.push(master);
+
+  for moved in &series.overrides {
+    let stamp = revisions.stamps.get(&class_uid(calendar_id, moved.item));
+    let mut event = class_instance(&uid, moved.item, stamp, wording);
+    event.append_property(time_property(
+      "RECURRENCE-ID",
+      &series_time(moved.recurrence_id, zone),
+    ));
+    calendar.push(event);
+  }
+  for occurrence in cancelled {
+    calendar.push(cancelled_occurrence(occurrence, wording));
+  }
+}
+
+fn cancelled_occurrence(occurrence: &CancelledOccurrence, wording: &Wording) -> Event {
+  let mut event = class_event(occurrence.series_uid, occurrence.item, wording);
+  cancel(
+    &mut event,
+    &class_summary(occurrence.item, wording),
+    occurrence.stamp,
+    wording.texts,
+  );
+  event.append_property(time_property(
+    "RECURRENCE-ID",
+    &series_time(occurrence.recurrence_id, wording.zone),
+  ));
+  event.done()
+}
+
+/// `VTIMEZONE`s for every `TZID` the feed uses: the output zone's, and
+/// Warsaw's for series, covering the span of its events
+fn timezone_definitions(
+  zone: OutputZone,
+  has_series: bool,
+  window: Option<(NaiveDateTime, NaiveDateTime)>,
+) -> String {
+  let Some((from, to)) = window else {
+    return String::new();
+  };
+  let mut zones = Vec::new();
+  if let OutputZone::Zone(tz) = zone {
+    zones.push(tz);
+  }
+  if zone != OutputZone::Floating && has_series && !zones.contains(&SOURCE_ZONE) {
+    zones.push(SOURCE_ZONE);
+  }
+  zones
+    .into_iter()
+    .map(|tz| timezone::vtimezone(tz, timezone::instant(from), timezone::instant(to)))
+    .collect()
+}
 
-    let mut event = Event::new();
+/// Earliest and latest time of any event in the feed
+fn time_window(
+  items: &[PlanItem],
+  exams: &[ExamEvent],
+  revisions: &FeedRevisions,
+) -> Option<(NaiveDateTime, NaiveDateTime)> {
+  let cancelled = revisions
+    .cancelled
+    .iter()
+    .map(|cancelled| match &cancelled.item {
+      TrackedItem::Class(item) => (item.starts_at, item.ends_at),
+      TrackedItem::Exam(exam) => (exam.starts, exam.ends),
+    });
+  items
+    .iter()
+    .map(|item| (item.starts_at, item.ends_at))
+    .chain(exams.iter().map(|exam| (exam.starts, exam.ends)))
+    .chain(cancelled)
+    .reduce(|(from, to), (starts, ends)| (from.min(starts), to.max(ends)))
+}
+
+/// The serializer has no `VTIMEZONE` component (it would stamp one with a
+/// `DTSTAMP` and a random `UID`), so the definitions are spliced in ahead of
+/// the first event
+fn with_timezones(mut ics: String, definitions: &str) -> String {
+  if definitions.is_empty() {
+    return ics;
+  }
+  let at = ics
+    .find("\r\nBEGIN:")
+    .or_else(|| ics.rfind("\r\nEND:VCALENDAR"))
+    .map_or(ics.len(), |index| index + 2);
+  ics.insert_str(at, definitions);
+  ics
+}
+
+/// A live class with its stamp and reminder
+fn class_instance(
+  uid: &str,
+  item: &PlanItem,
+  stamp: Option<&EventStamp>,
+  wording: &Wording,
+) -> Event {
+  let mut event = class_event(uid, item, wording);
+  stamp_event(&mut event, stamp);
+  event.alarm(reminder(
+    uid,
+    &class_summary(item, wording),
+    CLASS_REMINDER_MINUTES,
+    stamp,
+  ));
+  event.done()
+}
+
+/// Restyles an event as called off: no alarms, and the slot no longer counts as busy
+fn cancel(event: &mut Event, summary: &str, stamp: &EventStamp, texts: &IcsTexts) {
+  stamp_event(event, Some(stamp));
+  event
+    .summary(&format!("{}: {summary}", texts.label_cancelled))
+    .status(EventStatus::Cancelled)
+    .append_property(Property::new("TRANSP", "TRANSPARENT"));
+}
+
+/// Local time without a zone, matching how a floating `DTSTART` is written
+fn floating(value: NaiveDateTime) -> String {
+  value.format("%Y%m%dT%H%M%S").to_string()
+}
+
+/// A WPS time as written in `zone`
+fn event_time(value: NaiveDateTime, zone: OutputZone) -> CalendarDateTime {
+  match zone {
+    OutputZone::Floating => CalendarDateTime::Floating(value),
+    OutputZone::Utc => CalendarDateTime::Utc(timezone::instant(value)),
+    OutputZone::Zone(tz) => CalendarDateTime::WithTimezone {
+      date_time: zone.wall_clock(value),
+      tzid: tz.name().to_string(),
+    },
+  }
+}
+
+/// Series stay in Warsaw time whatever the output zone, since a weekly class
+/// keeps its hour there across daylight saving changes, which an `RRULE` in
+/// UTC or another zone would not
+fn series_time(value: NaiveDateTime, zone: OutputZone) -> CalendarDateTime {
+  match zone {
+    OutputZone::Floating => CalendarDateTime::Floating(value),
+    OutputZone::Utc | OutputZone::Zone(_) => CalendarDateTime::WithTimezone {
+      date_time: value,
+      tzid: SOURCE_ZONE.name().to_string(),
+    },
+  }
+}
+
+/// `EXDATE` and `RECURRENCE-ID` in the form the serializer uses for `DTSTART`
+fn time_property(key: &str, value: &CalendarDateTime) -> Property {
+  match value {
+    CalendarDateTime::Floating(time) => Property::new(key, floating(*time)),
+    CalendarDateTime::Utc(time) => Property::new(key, time.format("%Y%m%dT%H%M%SZ").to_string()),
+    CalendarDateTime::WithTimezone { date_time, tzid } => Property::new(key, floating(*date_time))
+      .add_parameter("TZID", tzid)
+      .done(),
+  }
+}
+
+/// Alarms get a UID derived from their event and share its `DTSTAMP`, since
+/// the serializer would otherwise make both up on every render
+fn reminder(uid: &str, summary: &str, minutes: i64, stamp: Option<&EventStamp>) -> Alarm {
+  let mut alarm = Alarm::display(summary, Trigger::before_start(Duration::minutes(minutes)));
+  alarm.uid(&format!("{uid}-alarm-{minutes}"));
+  if let Some(stamp) = stamp {
+    alarm.timestamp(stamp.last_modified);
+  }
+  alarm
+}
+
+/// Without a stamp the serializer falls back to the current time for `DTSTAMP`
+fn stamp_event(event: &mut Event, stamp: Option<&EventStamp>) {
+  if let Some(stamp) = stamp {
     event
-      .uid(&uid)
-      .summary(&summary)
-      .location(&location)
-      .description(&description)
-      .starts(exam.starts)
-      .ends(exam.ends)
-      .status(EventStatus::Confirmed)
-      .append_property(Property::new("TRANSP", "OPAQUE"))
-      .append_property(Property::new("URL", WPS_EXAM_URL))
-      .append_property(Property::new("CATEGORIES", category))
-      .append_property(Property::new("COLOR", color))
-      .alarm(Alarm::display(
-        &summary,
-        Trigger::before_start(Duration::minutes(EXAM_REMINDER_EARLY_MINUTES)),
-      ))
-      .alarm(Alarm::display(
-        &summary,
-        Trigger::before_start(Duration::minutes(EXAM_REMINDER_MINUTES)),
-      ));
+      .sequence(stamp.sequence)
+      .timestamp(stamp.last_modified)
+      .created(stamp.created)
+      .last_modified(stamp.last_modified);
+  }
+}
 
-    calendar.push(event.done());
+fn class_event(uid: &str, item: &PlanItem, wording: &Wording) -> Event {
+  let texts = wording.texts;
+  let mut event = Event::new();
+  event
+    .uid(uid)
+    .summary(&class_summary(item, wording))
+    .location(&build_location(item, texts))
+    .description(&class_description(item, wording))
+    .starts(event_time(item.starts_at, wording.zone))
+    .ends(event_time(item.ends_at, wording.zone))
+    .status(EventStatus::Confirmed)
+    .append_property(Property::new("TRANSP", "OPAQUE"))
+    .append_property(Property::new("URL", WPS_PLAN_URL));
+  if let Some(url) = &item.meeting_url {
+    add_meeting_link(&mut event, url, texts);
+  }
+  if let Some(place) = &item.place {
+    add_place(&mut event, place, &build_location(item, texts));
+  }
+
+  let category = item.class_type.trim();
+  if !category.is_empty() {
+    event.append_property(Property::new("CATEGORIES", category));
+  }
+  if let Some(color) = item
+    .form_color
+    .as_deref()
+    .map(str::trim)
+    .filter(|value| !value.is_empty())
+  {
+    event.append_property(Property::new("COLOR", color));
+  }
+
+  event
+}
+
+fn exam_event(
+  uid: &str,
+  exam: &ExamEvent,
+  wording: &Wording,
+  unconfirmed: UnconfirmedExamTime,
+) -> Event {
+  let texts = wording.texts;
+  let category = if exam.is_retake {
+    &texts.label_exam_retake
+  } else {
+    &texts.label_exam
+  };
+  let color = if exam.is_retake {
+    EXAM_RETAKE_COLOR
+  } else {
+    EXAM_COLOR
+  };
+
+  let mut event = Event::new();
+  event
+    .uid(uid)
+    .summary(&exam_summary(exam, wording))
+    .location(&build_exam_location(exam, texts))
+    .description(&exam_description(exam, wording))
+    .append_property(Property::new("TRANSP", "OPAQUE"))
+    .append_property(Property::new("URL", WPS_EXAM_URL))
+    .append_property(Property::new("CATEGORIES", category.as_ref()))
+    .append_property(Property::new("COLOR", color));
+  if let Some(url) = &exam.meeting_url {
+    add_meeting_link(&mut event, url, texts);
   }
+  set_exam_time(&mut event, exam, unconfirmed, wording.zone);
+
+  event
+}
 
-  Ok(calendar.to_string())
+/// Puts the join link everywhere clients look for one: RFC 7986 `CONFERENCE`,
+/// `URL` in place of the WPS page, and the properties Outlook and Google
+/// Calendar show a "Join" button for
+fn add_meeting_link(event: &mut Event, url: &str, texts: &IcsTexts) {
+  event
+    .append_property(
+      Property::new("CONFERENCE", url)
+        .add_parameter("VALUE", "URI")
+        .add_parameter("FEATURE", "AUDIO,VIDEO")
+        .add_parameter("LABEL", &texts.label_meeting)
+        .done(),
+    )
+    .append_property(Property::new("URL", url))
+    .append_property(Property::new("X-MICROSOFT-SKYPETEAMSMEETINGURL", url))
+    .append_property(Property::new("X-GOOGLE-CONFERENCE", url));
+}
+
+/// Coordinates for maps and travel time: standard `GEO`, plus the structured
+/// location Apple Calendar reads
+fn add_place(event: &mut Event, place: &Place, location: &str) {
+  let geo_uri = format!("geo:{},{}", place.latitude, place.longitude);
+  event
+    .append_property(Property::new(
+      "GEO",
+      format!("{};{}", place.latitude, place.longitude),
+    ))
+    .append_property(
+      Property::new("X-APPLE-STRUCTURED-LOCATION", geo_uri)
+        .add_parameter("VALUE", "URI")
+        .add_parameter("X-ADDRESS", &quoted(&place.address))
+        .add_parameter("X-APPLE-RADIUS", &PLACE_RADIUS_METRES.to_string())
+        .add_parameter("X-TITLE", &quoted(location))
+        .done(),
+    );
 }
 
-fn build_summary(item: &PlanItem) -> String {
+/// Parameter values with commas must be quoted, which the serializer only
+/// does for colons and semicolons
+fn quoted(value: &str) -> String {
+  format!("\"{}\"", value.replace('"', "'"))
+}
+
+fn maps_url(place: &Place) -> String {
+  format!(
+    "https://www.google.com/maps/search/?api=1&query={},{}",
+    place.latitude, place.longitude
+  )
+}
+
+/// Published times are authoritative; a guessed start is either flagged as
+/// tentative or replaced by the whole day, so it never looks confirmed.
+fn set_exam_time(
+  event: &mut Event,
+  exam: &ExamEvent,
+  unconfirmed: UnconfirmedExamTime,
+  zone: OutputZone,
+) {
+  if exam.time_confirmed {
+    event
+      .starts(event_time(exam.starts, zone))
+      .ends(event_time(exam.ends, zone))
+      .status(EventStatus::Confirmed);
+    return;
+  }
+
+  match unconfirmed {
+    UnconfirmedExamTime::Tentative => {
+      event
+        .starts(event_time(exam.starts, zone))
+        .ends(event_time(exam.ends, zone))
+        .status(EventStatus::Tentative);
+    }
+    UnconfirmedExamTime::AllDay => {
+      // The day of the exam in Warsaw; dates carry no zone to convert
+      let day = exam.starts.date();
+      // DTEND of a date-only event is exclusive
+      event
+        .starts(day)
+        .ends(day.succ_opt().unwrap_or(day))
+        .status(EventStatus::Confirmed);
+    }
+  }
+}
+
+/// Values of the class template placeholders; missing data renders as empty text
+fn class_field(item: &PlanItem, name: &str, wording: &Wording) -> String {
+  let zone = wording.zone;
+  let optional = |value: &Option<String>| value.as_deref().unwrap_or_default().trim().to_string();
+  match name {
+    "subject" => wording.templates.alias(&item.subject_name).to_string(),
+    "subject_full" => item.subject_name.clone(),
+    "type" => item.class_type.clone(),
+    "type_short" => item.class_type_short.trim().to_string(),
+    "room_number" => optional(&item.room_number),
+    "room_address" => optional(&item.room_address),
+    "location" => build_location(item, wording.texts),
+    "instructors" => instructor_names(item),
+    "date" => zone
+      .wall_clock(item.starts_at)
+      .format("%Y-%m-%d")
+      .to_string(),
+    "starts" => zone.wall_clock(item.starts_at).format("%H:%M").to_string(),
+    "ends" => zone.wall_clock(item.ends_at).format("%H:%M").to_string(),
+    "blocks" => block_times(item, zone),
+    "meeting_url" => optional(&item.meeting_url),
+    "schedule_item_id" => item.schedule_item_id.to_string(),
+    "webinar" => item.webinar.to_string(),
+    "form_color" => optional(&item.form_color),
+    "place" => item
+      .place
+      .as_ref()
+      .map(|place| format!("{}, {}", place.name, place.address))
+      .unwrap_or_default(),
+    "map_url" => item.place.as_ref().map(maps_url).unwrap_or_default(),
+    _ => String::new(),
+  }
+}
+
+/// Values of the exam template placeholders; missing data renders as empty text
+fn exam_field(exam: &ExamEvent, name: &str, wording: &Wording) -> String {
+  let texts = wording.texts;
+  let zone = wording.zone;
+  let optional = |value: &Option<String>| value.as_deref().unwrap_or_default().to_string();
+  match name {
+    "subject" => wording.templates.alias(exam.subject.trim()).to_string(),
+    "subject_full" => exam.subject.trim().to_string(),
+    "kind" => if exam.is_retake {
+      &texts.label_exam_retake
+    } else {
+      &texts.label_exam
+    }
+    .to_string(),
+    "notes" => optional(&exam.notes),
+    "location" => build_exam_location(exam, texts),
+    "lecturer" => optional(&exam.lecturer),
+    "details" => optional(&exam.details),
+    "date" => zone.wall_clock(exam.starts).format("%Y-%m-%d").to_string(),
+    "starts" => zone.wall_clock(exam.starts).format("%H:%M").to_string(),
+    "ends" => zone.wall_clock(exam.ends).format("%H:%M").to_string(),
+    "programmes" => exam.programmes.join(", "),
+    "meeting_url" => optional(&exam.meeting_url),
+    "published_data_id" => exam.published_data_id.to_string(),
+    "is_retake" => exam.is_retake.to_string(),
+    "time_confirmed" => exam.time_confirmed.to_string(),
+    "end_estimated" => exam.end_estimated.to_string(),
+    _ => String::new(),
+  }
+}
+
+fn build_summary(item: &PlanItem, subject: &str) -> String {
   let typ = match item.class_type_short.trim() {
     "" => item.class_type.clone(),
     short => format!("{} {short}", item.class_type),
   };
-  format!("{} [{typ}]", item.subject_name)
+  format!("{subject} [{typ}]")
+}
+
+fn instructor_names(item: &PlanItem) -> String {
+  item
+    .instructors
+    .iter()
+    .map(|instructor| instructor.full_name.as_str())
+    .collect::<Vec<_>>()
+    .join(", ")
+}
+
+/// Times of the WPS blocks a merged class covers; empty for a single block
+fn block_times(item: &PlanItem, zone: OutputZone) -> String {
+  item
+    .merged_from
+    .iter()
+    .map(|block| {
+      format!(
+        "{}-{}",
+        zone.wall_clock(block.starts_at).format("%H:%M"),
+        zone.wall_clock(block.ends_at).format("%H:%M")
+      )
+    })
+    .collect::<Vec<_>>()
+    .join(", ")
 }
 
 fn build_location(item: &PlanItem, texts: &IcsTexts) -> String {
@@ -167,14 +808,16 @@ fn build_location(item: &PlanItem, texts: &IcsTexts) -> String {
     .as_ref()
     .filter(|value| !value.trim().is_empty())
   {
-    parts.push(value.trim());
+    parts.push(value.trim().to_string());
   }
-  if let Some(value) = item
+  if let Some(place) = &item.place {
+    parts.push(format!("{}, {}", place.name, place.address));
+  } else if let Some(value) = item
     .room_address
     .as_ref()
     .filter(|value| !value.trim().is_empty())
   {
-    parts.push(value.trim());
+    parts.push(value.trim().to_string());
   }
 
   if parts.is_empty() {
@@ -184,34 +827,45 @@ fn build_location(item: &PlanItem, texts: &IcsTexts) -> String {
   }
 }
 
-fn build_description(item: &PlanItem, texts: &IcsTexts) -> String {
+fn build_description(item: &PlanItem, wording: &Wording) -> String {
+  let texts = wording.texts;
   let instructors = if item.instructors.is_empty() {
     texts.missing_data.to_string()
   } else {
-    item
-      .instructors
-      .iter()
-      .map(|instructor| instructor.full_name.as_str())
-      .collect::<Vec<_>>()
-      .join(", ")
+    instructor_names(item)
   };
 
-  format!(
+  let mut description = format!(
     "{}: {instructors}\n{}: {}",
     texts.label_instructors, texts.label_type, item.class_type
-  )
+  );
+  if !item.merged_from.is_empty() {
+    let _ = write!(
+      description,
+      "\n{}: {}",
+      texts.label_blocks,
+      block_times(item, wording.zone)
+    );
+  }
+  if let Some(url) = &item.meeting_url {
+    let _ = write!(description, "\n{}: {url}", texts.label_meeting);
+  }
+  if let Some(place) = &item.place {
+    let _ = write!(description, "\n{}: {}", texts.label_map, maps_url(place));
+  }
+  description
 }
 
-fn build_exam_summary(item: &ExamEvent, texts: &IcsTexts) -> String {
-  let subject = if item.subject.trim().is_empty() {
-    texts.missing_data.to_string()
+fn build_exam_summary(item: &ExamEvent, subject: &str, texts: &IcsTexts) -> String {
+  let subject = if subject.is_empty() {
+    &texts.missing_data
   } else {
-    item.subject.trim().to_string()
+    subject
   };
   let label = if item.is_retake {
-    texts.label_exam_retake
+    &texts.label_exam_retake
   } else {
-    texts.label_exam
+    &texts.label_exam
   };
   format!("{label}: {subject}")
 }
@@ -222,26 +876,56 @@ fn build_exam_location(item: &ExamEvent, texts: &IcsTexts) -> String {
     .as_ref()
     .map(|value| value.trim())
     .filter(|value| !value.is_empty())
-    .unwrap_or(texts.location_default)
+    .unwrap_or(&texts.location_default)
     .to_string()
 }
 
+/// Warns that an exam time is provisional
+fn exam_time_note<'a>(item: &ExamEvent, texts: &'a IcsTexts) -> Option<&'a str> {
+  if !item.time_confirmed {
+    Some(&texts.note_time_unconfirmed)
+  } else if item.end_estimated {
+    Some(&texts.note_end_estimated)
+  } else {
+    None
+  }
+}
+
 fn build_exam_description(item: &ExamEvent, texts: &IcsTexts) -> String {
-  let notes = item.notes.as_deref().unwrap_or(texts.missing_data);
-  let lecturer = item.lecturer.as_deref().unwrap_or(texts.missing_data);
-  let details = item.details.as_deref().unwrap_or(texts.missing_data);
+  let notes = item.notes.as_deref().unwrap_or(&texts.missing_data);
+  let lecturer = item.lecturer.as_deref().unwrap_or(&texts.missing_data);
+  let details = item.details.as_deref().unwrap_or(&texts.missing_data);
 
-  format!(
+  let mut description = format!(
     "{}: {notes}\n{}: {lecturer}\n{}: {details}",
     texts.label_exam_type, texts.label_instructors, texts.label_details
-  )
+  );
+  if let Some(note) = exam_time_note(item, texts) {
+    let _ = write!(description, "\n{note}");
+  }
+  if !item.programmes.is_empty() {
+    let _ = write!(
+      description,
+      "\n{}: {}",
+      texts.label_programme,
+      item.programmes.join(", ")
+    );
+  }
+  if let Some(url) = &item.meeting_url {
+    let _ = write!(description, "\n{}: {url}", texts.label_meeting);
+  }
+  description
 }
 
 #[cfg(test)]
 mod tests {
-  use chrono::NaiveDate;
+  use std::collections::HashMap;
+  use std::sync::LazyLock;
+
+  use chrono::{DateTime, NaiveDate};
 
   use super::*;
+  use crate::cache::{CancelledItem, SeriesSlot};
   use crate::i18n::{en::EN, pl::PL};
   use crate::models::Instructor;
 
@@ -267,6 +951,9 @@ mod tests {
       }],
       schedule_item_id: 555,
       form_color: Some("#123456".to_string()),
+      meeting_url: None,
+      place: None,
+      merged_from: Vec::new(),
     }
   }
 
@@ -281,14 +968,43 @@ mod tests {
       starts: datetime(9, 0),
       ends: datetime(10, 30),
       is_retake: false,
+      time_confirmed: true,
+      end_estimated: false,
+      programmes: Vec::new(),
+      meeting_url: None,
     }
   }
 
   /// Stand-in for the opaque id the web layer derives from the credentials.
   const TEST_CALENDAR_ID: &str = "a1b2c3d4e5f60718";
 
+  static BUILTIN: LazyLock<Catalogs> = LazyLock::new(Catalogs::default);
+  static NO_TEMPLATES: LazyLock<Templates> = LazyLock::new(Templates::default);
+
+  fn options(lang: CalendarLanguage) -> RenderOptions<'static> {
+    RenderOptions {
+      lang,
+      unconfirmed_exams: UnconfirmedExamTime::Tentative,
+      compress_series: false,
+      zone: OutputZone::Floating,
+      catalogs: &BUILTIN,
+      templates: &NO_TEMPLATES,
+    }
+  }
+
+  fn wording(lang: CalendarLanguage) -> Wording<'static> {
+    Wording::new(lang, OutputZone::Floating, &BUILTIN, &NO_TEMPLATES)
+  }
+
   fn render(items: &[PlanItem], exams: &[ExamEvent]) -> String {
-    render_calendar(TEST_CALENDAR_ID, items, exams, CalendarLanguage::Pl).expect("render succeeds")
+    render_calendar(
+      TEST_CALENDAR_ID,
+      items,
+      exams,
+      &FeedRevisions::default(),
+      &options(CalendarLanguage::PL),
+    )
+    .expect("render succeeds")
   }
 
   fn count(haystack: &str, needle: &str) -> usize {
@@ -304,6 +1020,14 @@ mod tests {
     assert_eq!(count(&ics, "BEGIN:VEVENT"), 0);
   }
 
+  #[test]
+  fn calendar_advertises_a_refresh_interval() {
+    let ics = render(&[], &[]);
+
+    assert!(ics.contains("REFRESH-INTERVAL;VALUE=DURATION:PT3600S"));
+    assert!(ics.contains("X-PUBLISHED-TTL:PT3600S"));
+  }
+
   #[test]
   fn plan_item_uid_is_stable_and_scoped_to_student() {
     let ics = render(&[plan_item()], &[]);
@@ -314,14 +1038,15 @@ mod tests {
   }
 
   #[test]
-  fn exam_uid_includes_start_timestamp() {
+  fn exam_uid_survives_a_reschedule() {
     let exam = exam_event();
-    let timestamp = exam.starts.and_utc().timestamp();
-    let ics = render(&[], &[exam]);
+    let mut moved = exam.clone();
+    moved.starts += chrono::Duration::days(2);
+    moved.ends += chrono::Duration::days(2);
 
-    assert!(ics.contains(&format!(
-      "UID:ahe-{TEST_CALENDAR_ID}-exam-777-{timestamp}@wpsapi.ahe.lodz.pl"
-    )));
+    let uid = format!("UID:ahe-{TEST_CALENDAR_ID}-exam-777@wpsapi.ahe.lodz.pl");
+    assert!(render(&[], &[exam]).contains(&uid));
+    assert!(render(&[], &[moved]).contains(&uid));
   }
 
   #[test]
@@ -345,10 +1070,283 @@ mod tests {
     assert_eq!(count(&ics, "END:VEVENT"), 2);
   }
 
+  fn stamp(sequence: u32) -> EventStamp {
+    EventStamp {
+      sequence,
+      created: DateTime::from_timestamp(1_767_225_600, 0).expect("valid timestamp"),
+      last_modified: DateTime::from_timestamp(1_768_000_000, 0).expect("valid timestamp"),
+    }
+  }
+
+  #[test]
+  fn stamped_events_render_identically_every_time() {
+    let item = plan_item();
+    let revisions = FeedRevisions {
+      stamps: HashMap::from([(class_uid(TEST_CALENDAR_ID, &item), stamp(2))]),
+      cancelled: Vec::new(),
+    };
+    let render = || {
+      render_calendar(
+        TEST_CALENDAR_ID,
+        std::slice::from_ref(&item),
+        &[],
+        &revisions,
+        &options(CalendarLanguage::PL),
+      )
+      .expect("render succeeds")
+    };
+
+    let ics = render();
+    assert!(ics.contains("SEQUENCE:2"));
+    assert!(ics.contains("CREATED:20260101T000000Z"));
+    assert!(ics.contains("LAST-MODIFIED:20260109T230640Z"));
+    // Alarms included, nothing falls back to the render time or a random UID
+    assert_eq!(
+      count(&ics, "DTSTAMP:20260109T230640Z"),
+      count(&ics, "DTSTAMP:")
+    );
+    assert!(ics.contains("-555@wpsapi.ahe.lodz.pl-alarm-15"));
+    assert_eq!(render(), ics);
+  }
+
+  /// Everything a subscriber sees of one occurrence
+  type Occurrence = (NaiveDateTime, NaiveDateTime, String, String, String);
+
+  /// Expands a rendered feed the way a client does: `RRULE` occurrences
+  /// minus `EXDATE`s, replaced by the `RECURRENCE-ID` overrides of their UID
+  fn expand_feed(ics: &str) -> Vec<Occurrence> {
+    let calendar: Calendar = ics.parse().expect("rendered feed parses");
+    let events: Vec<&Event> = calendar
+      .components
+      .iter()
+      .filter_map(|component| component.as_event())
+      .collect();
+    let value =
+      |event: &Event, key: &str| event.property_value(key).unwrap_or_default().to_string();
+    let time = |event: &Event, key: &str| {
+      NaiveDateTime::parse_from_str(&value(event, key), "%Y%m%dT%H%M%S").expect("floating time")
+    };
+    let occurrence = |event: &Event, starts: NaiveDateTime| {
+      (
+        starts,
+        starts + (time(event, "DTEND") - time(event, "DTSTART")),
+        value(event, "SUMMARY"),
+        value(event, "LOCATION"),
+        value(event, "STATUS"),
+      )
+    };
+
+    let mut occurrences = Vec::new();
+    for event in events
+      .iter()
+      .filter(|event| event.property_value("RECURRENCE-ID").is_none())
+    {
+      let starts = time(event, "DTSTART");
+      let Some(rule) = event.property_value("RRULE") else {
+        occurrences.push(occurrence(event, starts));
+        continue;
+      };
+      let part = |name: &str| -> i64 {
+        rule
+          .split(';')
+          .find_map(|part| part.strip_prefix(name))
+          .and_then(|value| value.parse().ok())
+          .expect("rule part")
+      };
+      let exdates: Vec<&str> = event
+        .multi_properties()
+        .get("EXDATE")
+        .into_iter()
+        .flatten()
+        .map(Property::value)
+        .collect();
+      for index in 0..part("COUNT=") {
+        let at = starts + Duration::weeks(index * part("INTERVAL="));
+        if exdates.contains(&floating(at).as_str()) {
+          continue;
+        }
+        let moved = events.iter().find(|other| {
+          other.get_uid() == event.get_uid()
+            && other.property_value("RECURRENCE-ID") == Some(floating(at).as_str())
+        });
+        occurrences.push(match moved {
+          Some(moved) => occurrence(moved, time(moved, "DTSTART")),
+          None => occurrence(event, at),
+        });
+      }
+    }
+    occurrences.sort();
+    occurrences
+  }
+
+  #[test]
+  fn compressed_series_expand_to_the_same_occurrences() {
+    let weekly = weekly(8);
+    let mut items = weekly.clone();
+    // A gap, a class moved to another day and one in another room
+    items.remove(2);
+    items[3].starts_at += Duration::days(2);
+    items[3].ends_at += Duration::days(2);
+    items[5].room_number = Some("B7".to_string());
+    let mut other = plan_item();
+    other.subject_name = "Fizyka".to_string();
+    items.push(other);
+    items.retain(|item| item.schedule_item_id != weekly[1].schedule_item_id);
+    // Only a compressed feed served the vanished class in a series
+    let revisions = |series| FeedRevisions {
+      stamps: HashMap::new(),
+      cancelled: vec![CancelledItem {
+        item: TrackedItem::Class(weekly[1].clone()),
+        stamp: stamp(1),
+        series,
+      }],
+    };
+    let slot = SeriesSlot {
+      uid: only_series_uid(&items),
+      recurrence_id: weekly[1].starts_at,
+    };
+
+    let render = |compress_series: bool| {
+      render_calendar(
+        TEST_CALENDAR_ID,
+        &items,
+        &[],
+        &revisions(compress_series.then(|| slot.clone())),
+        &RenderOptions {
+          compress_series,
+          ..options(CalendarLanguage::PL)
+        },
+      )
+      .expect("render succeeds")
+    };
+    let plain = render(false);
+    let compressed = render(true);
+
+    assert_eq!(
+      count(&compressed, "RRULE:FREQ=WEEKLY;INTERVAL=1;COUNT=8"),
+      1
+    );
+    assert!(compressed.contains("EXDATE:"));
+    assert_eq!(count(&compressed, "RECURRENCE-ID:"), 3);
+    assert!(count(&compressed, "BEGIN:VEVENT") < count(&plain, "BEGIN:VEVENT"));
+    assert_eq!(expand_feed(&compressed), expand_feed(&plain));
+    assert_eq!(expand_feed(&plain).len(), items.len() + 1);
+  }
+
+  fn weekly(weeks: i64) -> Vec<PlanItem> {
+    (0..weeks)
+      .map(|week| {
+        let mut item = plan_item();
+        item.schedule_item_id = 1000 + week;
+        item.starts_at += Duration::weeks(week);
+        item.ends_at += Duration::weeks(week);
+        item
+      })
+      .collect()
+  }
+
+  fn only_series_uid(items: &[PlanItem]) -> String {
+    recurrence::group(items)
+      .iter()
+      .find_map(|entry| match entry {
+        PlanEntry::Series(series) => Some(series_uid(TEST_CALENDAR_ID, series)),
+        PlanEntry::Single(_) => None,
+      })
+      .expect("a series")
+  }
+
+  #[test]
+  fn vanished_occurrences_stay_in_the_series_they_were_served_in() {
+    let served = weekly(5);
+    let uid = only_series_uid(&served);
+    // The last occurrence vanishes, so the recomputed rule no longer covers it
+    let revisions = FeedRevisions {
+      stamps: HashMap::new(),
+      cancelled: vec![CancelledItem {
+        item: TrackedItem::Class(served[4].clone()),
+        stamp: stamp(1),
+        series: Some(SeriesSlot {
+          uid: uid.clone(),
+          recurrence_id: served[4].starts_at,
+        }),
+      }],
+    };
+    let render = |items: &[PlanItem]| {
+      render_calendar(
+        TEST_CALENDAR_ID,
+        items,
+        &[],
+        &revisions,
+        &RenderOptions {
+          compress_series: true,
+          ..options(CalendarLanguage::PL)
+        },
+      )
+      .expect("render succeeds")
+    };
+    let class_uid = format!("UID:{}", class_uid(TEST_CALENDAR_ID, &served[4]));
+    let recurrence_id = format!("RECURRENCE-ID:{}", floating(served[4].starts_at));
+
+    let shorter = render(&served[..4]);
+    assert!(shorter.contains("COUNT=4"));
+    assert_eq!(count(&shorter, &format!("UID:{uid}\r\n")), 2);
+    assert!(shorter.contains(&recurrence_id));
+    assert!(!shorter.contains(&class_uid));
+
+    // Even with the series gone, the override cancels what subscribers hold
+    let dissolved = render(&served[..1]);
+    assert!(!dissolved.contains("RRULE"));
+    assert_eq!(count(&dissolved, &format!("UID:{uid}\r\n")), 1);
+    assert!(dissolved.contains(&recurrence_id));
+    assert_eq!(count(&dissolved, "STATUS:CANCELLED"), 1);
+  }
+
+  #[test]
+  fn cancelled_events_keep_their_uid_and_drop_their_alarms() {
+    let revisions = FeedRevisions {
+      stamps: HashMap::new(),
+      cancelled: vec![
+        CancelledItem {
+          item: TrackedItem::Class(plan_item()),
+          stamp: stamp(1),
+          series: None,
+        },
+        CancelledItem {
+          item: TrackedItem::Exam(exam_event()),
+          stamp: stamp(3),
+          series: None,
+        },
+      ],
+    };
+    let ics = render_calendar(
+      TEST_CALENDAR_ID,
+      &[],
+      &[],
+      &revisions,
+      &options(CalendarLanguage::PL),
+    )
+    .expect("render succeeds");
+
+    assert_eq!(count(&ics, "BEGIN:VEVENT"), 2);
+    assert_eq!(count(&ics, "STATUS:CANCELLED"), 2);
+    assert_eq!(count(&ics, "BEGIN:VALARM"), 0);
+    assert!(ics.contains(&format!(
+      "UID:ahe-{TEST_CALENDAR_ID}-555@wpsapi.ahe.lodz.pl"
+    )));
+    assert!(ics.contains("SUMMARY:ODWOLANE: Algebra [Wyklad W]"));
+    assert!(ics.contains("SUMMARY:ODWOLANE: Egzamin: Algebra"));
+    assert!(ics.contains("SEQUENCE:1"));
+    assert!(ics.contains("SEQUENCE:3"));
+    assert!(ics.contains("TRANSP:TRANSPARENT"));
+  }
+
   #[test]
   fn class_summary_combines_type_and_short_code() {
     let item = plan_item();
-    assert_eq!(build_summary(&item), "Algebra [Wyklad W]");
+    assert_eq!(
+      class_summary(&item, &wording(CalendarLanguage::PL)),
+      "Algebra [Wyklad W]"
+    );
   }
 
   #[test]
@@ -356,7 +1354,10 @@ mod tests {
     let mut item = plan_item();
     item.class_type_short = "   ".to_string();
 
-    assert_eq!(build_summary(&item), "Algebra [Wyklad]");
+    assert_eq!(
+      class_summary(&item, &wording(CalendarLanguage::PL)),
+      "Algebra [Wyklad]"
+    );
   }
 
   #[test]
@@ -364,13 +1365,13 @@ mod tests {
     let mut item = plan_item();
     item.webinar = true;
 
-    assert_eq!(build_location(&item, &PL), "Webinar");
-    assert_eq!(build_location(&item, &EN), "Webinar");
+    assert_eq!(build_location(&item, &PL.ics), "Webinar");
+    assert_eq!(build_location(&item, &EN.ics), "Webinar");
   }
 
   #[test]
   fn location_joins_room_number_and_address() {
-    assert_eq!(build_location(&plan_item(), &PL), "A12 — Sterlinga 26");
+    assert_eq!(build_location(&plan_item(), &PL.ics), "A12 — Sterlinga 26");
   }
 
   #[test]
@@ -378,11 +1379,11 @@ mod tests {
     let mut item = plan_item();
     item.room_number = Some("  ".to_string());
 
-    assert_eq!(build_location(&item, &PL), "Sterlinga 26");
+    assert_eq!(build_location(&item, &PL.ics), "Sterlinga 26");
 
     item.room_address = None;
-    assert_eq!(build_location(&item, &PL), "Sala");
-    assert_eq!(build_location(&item, &EN), "Room");
+    assert_eq!(build_location(&item, &PL.ics), "Sala");
+    assert_eq!(build_location(&item, &EN.ics), "Room");
   }
 
   #[test]
@@ -392,31 +1393,148 @@ mod tests {
       full_name: "Anna Nowak".to_string(),
     });
 
-    let description = build_description(&item, &PL);
+    let description = build_description(&item, &wording(CalendarLanguage::PL));
     assert!(description.contains("Prowadzacy: Jan Kowalski, Anna Nowak"));
     assert!(description.contains("Typ: Wyklad"));
   }
 
+  #[test]
+  fn merged_blocks_share_one_event_and_list_their_times() {
+    let mut second = plan_item();
+    second.schedule_item_id = 556;
+    second.starts_at = datetime(11, 45);
+    second.ends_at = datetime(13, 15);
+    let merged = crate::merge::merge_adjacent(vec![plan_item(), second], Duration::minutes(15));
+
+    let ics = render(&merged, &[]);
+    assert!(ics.contains(&format!(
+      "UID:ahe-{TEST_CALENDAR_ID}-merged-555-556@wpsapi.ahe.lodz.pl"
+    )));
+    assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+    assert!(
+      build_description(&merged[0], &wording(CalendarLanguage::EN))
+        .contains("Blocks: 10:00-11:30, 11:45-13:15")
+    );
+  }
+
+  #[test]
+  fn templates_and_aliases_replace_the_built_in_summary() {
+    let templates = Templates::parse(
+      std::path::Path::new("templates.toml"),
+      "[aliases]\nAlgebra = \"ALG\"\n[templates]\nsummary = \"{subject} {type_short} {{{room_number}}}\"\n[languages.en]\nsummary = \"{subject_full} ({starts})\"\n",
+      &BUILTIN,
+    )
+    .expect("valid templates");
+    let item = plan_item();
+
+    assert_eq!(
+      class_summary(
+        &item,
+        &Wording::new(
+          CalendarLanguage::PL,
+          OutputZone::Floating,
+          &BUILTIN,
+          &templates
+        )
+      ),
+      format!(
+        "ALG W {{{}}}",
+        item.room_number.as_deref().unwrap_or_default()
+      )
+    );
+    assert_eq!(
+      class_summary(
+        &item,
+        &Wording::new(
+          CalendarLanguage::EN,
+          OutputZone::Floating,
+          &BUILTIN,
+          &templates
+        )
+      ),
+      format!("Algebra ({})", item.starts_at.format("%H:%M"))
+    );
+    assert_eq!(
+      class_description(
+        &item,
+        &Wording::new(
+          CalendarLanguage::PL,
+          OutputZone::Floating,
+          &BUILTIN,
+          &templates
+        )
+      ),
+      build_description(&item, &wording(CalendarLanguage::PL))
+    );
+  }
+
+  #[test]
+  fn exam_templates_keep_the_provisional_time_note() {
+    let templates = Templates::parse(
+      std::path::Path::new("templates.toml"),
+      "[templates]\nexam_description = \"{kind} {is_retake}/{time_confirmed}/{end_estimated}\"\ndescription = \"{webinar} {place} {map_url}\"\n",
+      &BUILTIN,
+    )
+    .expect("valid templates");
+    let wording = Wording::new(
+      CalendarLanguage::EN,
+      OutputZone::Floating,
+      &BUILTIN,
+      &templates,
+    );
+    let mut exam = exam_event();
+    assert_eq!(exam_description(&exam, &wording), "Exam false/true/false");
+
+    exam.time_confirmed = false;
+    assert_eq!(
+      exam_description(&exam, &wording),
+      format!("Exam false/false/false\n{}", EN.ics.note_time_unconfirmed)
+    );
+
+    let mut item = plan_item();
+    item.place = Some(Place {
+      name: "Budynek A".to_string(),
+      address: "Sterlinga 26".to_string(),
+      latitude: 51.776,
+      longitude: 19.463,
+    });
+    assert_eq!(
+      class_description(&item, &wording),
+      "false Budynek A, Sterlinga 26 https://www.google.com/maps/search/?api=1&query=51.776,19.463"
+    );
+  }
+
   #[test]
   fn description_falls_back_when_instructors_are_missing() {
     let mut item = plan_item();
     item.instructors.clear();
 
-    assert!(build_description(&item, &PL).contains("Prowadzacy: (brak danych)"));
-    assert!(build_description(&item, &EN).contains("Instructors: (no data)"));
+    assert!(
+      build_description(&item, &wording(CalendarLanguage::PL))
+        .contains("Prowadzacy: (brak danych)")
+    );
+    assert!(
+      build_description(&item, &wording(CalendarLanguage::EN)).contains("Instructors: (no data)")
+    );
   }
 
   #[test]
   fn exam_summary_marks_retakes() {
     let mut exam = exam_event();
-    assert_eq!(build_exam_summary(&exam, &PL), "Egzamin: Algebra");
+    assert_eq!(
+      exam_summary(&exam, &wording(CalendarLanguage::PL)),
+      "Egzamin: Algebra"
+    );
 
     exam.is_retake = true;
     assert_eq!(
-      build_exam_summary(&exam, &PL),
+      exam_summary(&exam, &wording(CalendarLanguage::PL)),
       "Egzamin poprawkowy: Algebra"
     );
-    assert_eq!(build_exam_summary(&exam, &EN), "Resit exam: Algebra");
+    assert_eq!(
+      exam_summary(&exam, &wording(CalendarLanguage::EN)),
+      "Resit exam: Algebra"
+    );
   }
 
   #[test]
@@ -424,29 +1542,90 @@ mod tests {
     let mut exam = exam_event();
     exam.subject = "   ".to_string();
 
-    assert_eq!(build_exam_summary(&exam, &PL), "Egzamin: (brak danych)");
+    assert_eq!(
+      exam_summary(&exam, &wording(CalendarLanguage::PL)),
+      "Egzamin: (brak danych)"
+    );
   }
 
   #[test]
   fn exam_description_fills_gaps_with_placeholder() {
     let exam = exam_event();
-    let description = build_exam_description(&exam, &PL);
+    let description = build_exam_description(&exam, &PL.ics);
 
     assert!(description.contains("Rodzaj: (brak danych)"));
     assert!(description.contains("Prowadzacy: Jan Kowalski"));
     assert!(description.contains("Szczegoly: (brak danych)"));
+    assert!(!description.contains("Kierunek"));
+  }
+
+  #[test]
+  fn guessed_exam_times_are_tentative_and_explained() {
+    let mut exam = exam_event();
+    exam.time_confirmed = false;
+    exam.end_estimated = true;
+
+    let ics = render(&[], &[exam]);
+
+    assert!(ics.contains("STATUS:TENTATIVE"));
+    assert!(ics.contains("DTSTART:20260115T090000"));
+    assert!(ics.contains("Godzina nie zostala jeszcze opublikowana"));
+  }
+
+  #[test]
+  fn guessed_exam_times_can_become_all_day_events() {
+    let mut exam = exam_event();
+    exam.time_confirmed = false;
+    let options = RenderOptions {
+      unconfirmed_exams: UnconfirmedExamTime::AllDay,
+      ..options(CalendarLanguage::EN)
+    };
+
+    let ics = render_calendar(
+      TEST_CALENDAR_ID,
+      &[],
+      &[exam],
+      &FeedRevisions::default(),
+      &options,
+    )
+    .expect("render succeeds");
+
+    assert!(ics.contains("DTSTART;VALUE=DATE:20260115"));
+    assert!(ics.contains("DTEND;VALUE=DATE:20260116"));
+    assert!(!ics.contains("STATUS:TENTATIVE"));
+    assert!(ics.contains("Time not yet published"));
+  }
+
+  #[test]
+  fn estimated_exam_end_is_noted() {
+    let mut exam = exam_event();
+    exam.end_estimated = true;
+
+    let description = build_exam_description(&exam, &EN.ics);
+
+    assert!(description.ends_with("\nEnd time estimated"));
+  }
+
+  #[test]
+  fn exam_description_names_the_programmes() {
+    let mut exam = exam_event();
+    exam.programmes = vec!["Informatyka".to_string(), "Zarządzanie".to_string()];
+
+    let description = build_exam_description(&exam, &EN.ics);
+
+    assert!(description.ends_with("\nProgramme: Informatyka, Zarządzanie"));
   }
 
   #[test]
   fn exam_location_falls_back_to_default_room() {
     let mut exam = exam_event();
-    assert_eq!(build_exam_location(&exam, &PL), "A12");
+    assert_eq!(build_exam_location(&exam, &PL.ics), "A12");
 
     exam.location = Some("   ".to_string());
-    assert_eq!(build_exam_location(&exam, &PL), "Sala");
+    assert_eq!(build_exam_location(&exam, &PL.ics), "Sala");
 
     exam.location = None;
-    assert_eq!(build_exam_location(&exam, &EN), "Room");
+    assert_eq!(build_exam_location(&exam, &EN.ics), "Room");
   }
 
   #[test]
@@ -459,6 +1638,53 @@ mod tests {
     assert!(!ics.contains(EXAM_COLOR));
   }
 
+  #[test]
+  fn meeting_links_get_a_join_button() {
+    let mut item = plan_item();
+    item.webinar = true;
+    item.meeting_url = Some("https://teams.microsoft.com/l/meetup-join/1".to_string());
+    let mut exam = exam_event();
+    exam.meeting_url = Some("https://zoom.us/j/42".to_string());
+
+    // Long content lines are folded, so undo that before matching
+    let ics = render(&[item.clone()], &[exam]).replace("\r\n ", "");
+    assert!(ics.contains(
+      "CONFERENCE;FEATURE=AUDIO,VIDEO;LABEL=Spotkanie online;VALUE=URI:https://teams.microsoft.com/l/meetup-join/1"
+    ));
+    assert!(ics.contains("X-MICROSOFT-SKYPETEAMSMEETINGURL:https://zoom.us/j/42"));
+    assert!(ics.contains("URL:https://zoom.us/j/42"));
+    assert!(!ics.contains(WPS_PLAN_URL));
+    assert!(
+      build_description(&item, &wording(CalendarLanguage::EN))
+        .contains("Online meeting: https://teams.microsoft.com")
+    );
+  }
+
+  #[test]
+  fn known_buildings_get_coordinates() {
+    let mut item = plan_item();
+    item.place = Some(Place {
+      name: "AHE".to_string(),
+      address: "ul. Sterlinga 26, 90-212 Łódź".to_string(),
+      latitude: 51.7776,
+      longitude: 19.4622,
+    });
+
+    let ics = render(std::slice::from_ref(&item), &[]).replace("\r\n ", "");
+    assert!(ics.contains("GEO:51.7776;19.4622"));
+    assert!(ics.contains(
+      "X-APPLE-STRUCTURED-LOCATION;VALUE=URI;X-ADDRESS=\"ul. Sterlinga 26, 90-212 Łódź\";X-APPLE-RADIUS=70;X-TITLE=\"A12 — AHE, ul. Sterlinga 26, 90-212 Łódź\":geo:51.7776,19.4622"
+    ));
+    assert_eq!(
+      build_location(&item, &PL.ics),
+      "A12 — AHE, ul. Sterlinga 26, 90-212 Łódź"
+    );
+    assert!(
+      build_description(&item, &wording(CalendarLanguage::EN))
+        .contains("Map: https://www.google.com/maps/search/?api=1&query=51.7776,19.4622")
+    );
+  }
+
   #[test]
   fn class_colour_comes_from_the_feed() {
     let ics = render(&[plan_item()], &[]);
@@ -479,15 +1705,100 @@ mod tests {
 
   #[test]
   fn calendar_name_follows_the_configured_language() {
-    let polish =
-      render_calendar(TEST_CALENDAR_ID, &[], &[], CalendarLanguage::Pl).expect("render succeeds");
-    let english =
-      render_calendar(TEST_CALENDAR_ID, &[], &[], CalendarLanguage::En).expect("render succeeds");
+    let polish = render_calendar(
+      TEST_CALENDAR_ID,
+      &[],
+      &[],
+      &FeedRevisions::default(),
+      &options(CalendarLanguage::PL),
+    )
+    .expect("render succeeds");
+    let english = render_calendar(
+      TEST_CALENDAR_ID,
+      &[],
+      &[],
+      &FeedRevisions::default(),
+      &options(CalendarLanguage::EN),
+    )
+    .expect("render succeeds");
 
     assert!(polish.contains("Plan AHE"));
     assert!(english.contains("AHE Schedule"));
   }
 
+  fn render_in(zone: OutputZone, items: &[PlanItem], compress_series: bool) -> String {
+    render_calendar(
+      TEST_CALENDAR_ID,
+      items,
+      &[exam_event()],
+      &FeedRevisions::default(),
+      &RenderOptions {
+        compress_series,
+        zone,
+        ..options(CalendarLanguage::PL)
+      },
+    )
+    .expect("render succeeds")
+  }
+
+  #[test]
+  fn utc_output_converts_event_times_and_texts() {
+    let ics = render_in(OutputZone::Utc, &[plan_item()], false);
+
+    assert!(ics.contains("X-WR-TIMEZONE:UTC"));
+    assert!(ics.contains("DTSTART:20260115T090000Z"));
+    assert!(ics.contains("DTEND:20260115T103000Z"));
+    assert!(ics.contains("DTSTART:20260115T080000Z"));
+    assert!(!ics.contains("BEGIN:VTIMEZONE"));
+    let wording = Wording::new(
+      CalendarLanguage::PL,
+      OutputZone::Utc,
+      &BUILTIN,
+      &NO_TEMPLATES,
+    );
+    assert_eq!(class_field(&plan_item(), "starts", &wording), "09:00");
+  }
+
+  #[test]
+  fn zoned_output_carries_its_timezone_definition() {
+    let zone = OutputZone::Zone(chrono_tz::America::New_York);
+    let ics = render_in(zone, &[plan_item()], false);
+
+    assert!(ics.contains("X-WR-TIMEZONE:America/New_York"));
+    assert!(ics.contains("DTSTART;TZID=America/New_York:20260115T040000"));
+    assert_eq!(count(&ics, "BEGIN:VTIMEZONE"), 1);
+    assert!(ics.find("BEGIN:VTIMEZONE") < ics.find("BEGIN:VEVENT"));
+    assert!(ics.contains("TZID:America/New_York\r\nBEGIN:STANDARD"));
+    let calendar: Calendar = ics.parse().expect("rendered feed parses");
+    assert_eq!(calendar.components.len(), 3);
+  }
+
+  #[test]
+  fn series_stay_in_warsaw_time_across_daylight_saving() {
+    // Weekly from before the March change to after it
+    let weekly: Vec<PlanItem> = (0..4)
+      .map(|week| {
+        let mut item = plan_item();
+        item.schedule_item_id = 2000 + week;
+        item.starts_at = NaiveDate::from_ymd_opt(2026, 3, 19)
+          .and_then(|day| day.and_hms_opt(10, 0, 0))
+          .expect("valid time")
+          + Duration::weeks(week);
+        item.ends_at = item.starts_at + Duration::minutes(90);
+        item
+      })
+      .collect();
+    let ics = render_in(OutputZone::Utc, &weekly, true);
+
+    assert!(ics.contains("DTSTART;TZID=Europe/Warsaw:20260319T100000"));
+    assert!(ics.contains("RRULE:FREQ=WEEKLY;INTERVAL=1;COUNT=4"));
+    assert!(ics.contains("TZID:Europe/Warsaw\r\n"));
+    assert!(ics.contains("TZOFFSETTO:+0200"));
+    // The exam outside the series is still written in UTC
+    assert!(ics.contains("DTSTART:20260115T080000Z"));
+    assert_eq!(count(&ics, "BEGIN:VTIMEZONE"), 1);
+  }
+
   #[test]
   fn calendar_id_is_stable_for_the_same_inputs() {
     assert_eq!(calendar_id(b"digest", 4242), calendar_id(b"digest", 4242));
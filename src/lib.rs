@@ -1,8 +1,16 @@
 pub mod api;
 pub mod app;
 pub mod cache;
+pub mod compression;
 pub mod config;
+pub mod formats;
 pub mod i18n;
 pub mod ics;
+pub mod locations;
+pub mod merge;
 pub mod models;
+pub mod recurrence;
+pub mod server;
+pub mod templates;
+pub mod timezone;
 pub mod web;
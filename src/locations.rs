@@ -0,0 +1,265 @@
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::config::parse_structured;
+use crate::models::{Place, PlanItem, normalize_text};
+
+/// One building of the address table and the rooms that are in it
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Entry {
+  name: String,
+  address: String,
+  latitude: f64,
+  longitude: f64,
+  /// Text found in the WPS `SalaAdres` of rooms in this building
+  #[serde(default)]
+  room_addresses: Vec<String>,
+  /// Starts of the WPS `SalaNumer` of rooms in this building
+  #[serde(default)]
+  room_prefixes: Vec<String>,
+}
+
+impl Entry {
+  fn place(&self) -> Place {
+    Place {
+      name: self.name.clone(),
+      address: self.address.clone(),
+      latitude: self.latitude,
+      longitude: self.longitude,
+    }
+  }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct LocationFile {
+  #[serde(default)]
+  place: Vec<Entry>,
+  /// Whether the built-in AHE Łódź buildings follow the file's own
+  #[serde(default)]
+  builtin: bool,
+}
+
+/// Maps WPS room details to campus buildings.
+///
+/// A room is looked up by its address first, as that is what WPS fills in
+/// most reliably, and by the start of its number second. Entries are tried
+/// in order, so the first match wins. The default table is empty, so no class
+/// gets a building unless the operator configures one.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct LocationTable {
+  entries: Vec<Entry>,
+}
+
+/// The AHE Łódź buildings, opted into with `builtin = true`.
+///
+/// Addresses are the ones WPS gives in `SalaAdres`. Coordinates are approximate
+/// positions of those street addresses, not surveyed entrances, so a site that
+/// needs precise pins should list its own.
+fn builtin_entries() -> Vec<Entry> {
+  let building = |name: &str, address: &str, latitude, longitude, room_address: &str| Entry {
+    name: name.to_string(),
+    address: address.to_string(),
+    latitude,
+    longitude,
+    room_addresses: vec![room_address.to_string()],
+    room_prefixes: Vec::new(),
+  };
+  vec![
+    building(
+      "AHE",
+      "ul. Sterlinga 26, 90-212 Łódź",
+      51.777_6,
+      19.462_2,
+      "Sterlinga 26",
+    ),
+    building(
+      "AHE",
+      "ul. Rewolucji 1905 r. 64, 90-222 Łódź",
+      51.776_4,
+      19.470_6,
+      "Rewolucji 1905 r. 64",
+    ),
+  ]
+}
+
+impl LocationTable {
+  /// Reads a TOML or YAML file with `place` entries; the built-in buildings
+  /// follow them when the file sets `builtin = true`
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the file cannot be read or parsed, or an entry has
+  /// no way to match a room or impossible coordinates.
+  pub fn load(path: &Path) -> Result<Self> {
+    let contents = std::fs::read_to_string(path)
+      .with_context(|| format!("failed to read locations file {}", path.display()))?;
+    Self::parse(path, &contents)
+  }
+
+  fn parse(path: &Path, contents: &str) -> Result<Self> {
+    let file: LocationFile = parse_structured(path, contents, "locations file")?;
+    for entry in &file.place {
+      if entry.room_addresses.is_empty() && entry.room_prefixes.is_empty() {
+        bail!(
+          "place `{}` in {} needs room_addresses or room_prefixes",
+          entry.name,
+          path.display()
+        );
+      }
+      if !(-90.0..=90.0).contains(&entry.latitude) || !(-180.0..=180.0).contains(&entry.longitude) {
+        bail!(
+          "place `{}` in {} has coordinates out of range",
+          entry.name,
+          path.display()
+        );
+      }
+    }
+
+    let mut entries = file.place;
+    if file.builtin {
+      entries.extend(builtin_entries());
+    }
+    Ok(Self { entries })
+  }
+
+  /// Building of a class held on campus; webinars have none
+  #[must_use]
+  pub fn resolve(&self, item: &PlanItem) -> Option<Place> {
+    if item.webinar {
+      return None;
+    }
+
+    let by_address = item
+      .room_address
+      .as_deref()
+      .map(normalize_text)
+      .and_then(|address| {
+        self.entries.iter().find(|entry| {
+          entry
+            .room_addresses
+            .iter()
+            .any(|known| address.contains(&normalize_text(known)))
+        })
+      });
+    let by_number = || {
+      let number = normalize_text(item.room_number.as_deref()?);
+      self.entries.iter().find(|entry| {
+        entry
+          .room_prefixes
+          .iter()
+          .any(|prefix| number.starts_with(&normalize_text(prefix)))
+      })
+    };
+
+    by_address.or_else(by_number).map(Entry::place)
+  }
+
+  /// Fills in `place` on every item the table knows the building of
+  pub fn annotate(&self, items: &mut [PlanItem]) {
+    for item in items {
+      item.place = self.resolve(item);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::NaiveDate;
+
+  use super::*;
+
+  fn room(number: Option<&str>, address: Option<&str>) -> PlanItem {
+    let starts_at = NaiveDate::from_ymd_opt(2026, 3, 2)
+      .expect("valid date")
+      .and_hms_opt(10, 0, 0)
+      .expect("valid time");
+    PlanItem {
+      starts_at,
+      ends_at: starts_at,
+      subject_name: "Algebra".to_string(),
+      class_type: "Wyklad".to_string(),
+      class_type_short: "W".to_string(),
+      room_number: number.map(str::to_string),
+      room_address: address.map(str::to_string),
+      webinar: false,
+      instructors: Vec::new(),
+      schedule_item_id: 1,
+      form_color: None,
+      meeting_url: None,
+      place: None,
+      merged_from: Vec::new(),
+    }
+  }
+
+  const FILE: &str = r#"
+[[place]]
+name = "Budynek B"
+address = "ul. Rewolucji 1905 r. 52, 90-213 Łódź"
+latitude = 51.7771
+longitude = 19.4688
+room_prefixes = ["B"]
+"#;
+
+  #[test]
+  fn builtin_table_knows_the_campus_addresses() {
+    assert_eq!(
+      LocationTable::default().resolve(&room(Some("A12"), Some("Sterlinga 26"))),
+      None
+    );
+    let table =
+      LocationTable::parse(Path::new("locations.toml"), "builtin = true").expect("valid file");
+
+    let place = table
+      .resolve(&room(Some("A12"), Some("ul.  STERLINGA 26, Łódź")))
+      .expect("known address");
+    assert_eq!(place.address, "ul. Sterlinga 26, 90-212 Łódź");
+    assert_eq!(
+      table.resolve(&room(Some("A12"), Some("Piotrkowska 1"))),
+      None
+    );
+    assert!(
+      table
+        .resolve(&room(None, Some("ul. Rewolucji 1905 r.  64")))
+        .is_some()
+    );
+    // Another building on the same street is not building 64
+    assert_eq!(
+      table.resolve(&room(None, Some("Rewolucji 1905 r. 52"))),
+      None
+    );
+
+    let mut webinar = room(None, Some("Sterlinga 26"));
+    webinar.webinar = true;
+    assert_eq!(table.resolve(&webinar), None);
+  }
+
+  #[test]
+  fn file_entries_match_room_prefixes_before_the_builtin_ones() {
+    let table = LocationTable::parse(Path::new("locations.toml"), FILE).expect("valid file");
+
+    let place = table
+      .resolve(&room(Some("b 104"), None))
+      .expect("known prefix");
+    assert_eq!(place.name, "Budynek B");
+    assert_eq!(table.resolve(&room(None, Some("Sterlinga 26"))), None);
+
+    let with_builtin = format!("builtin = true\n{FILE}");
+    let table =
+      LocationTable::parse(Path::new("locations.toml"), &with_builtin).expect("valid file");
+    assert!(table.resolve(&room(None, Some("Sterlinga 26"))).is_some());
+  }
+
+  #[test]
+  fn unusable_entries_are_rejected() {
+    let unmatched = "place:\n  - name: X\n    address: Y\n    latitude: 1\n    longitude: 2\n";
+    assert!(LocationTable::parse(Path::new("locations.yml"), unmatched).is_err());
+
+    let off_map = FILE.replace("51.7771", "151.7771");
+    assert!(LocationTable::parse(Path::new("locations.toml"), &off_map).is_err());
+    assert!(LocationTable::parse(Path::new("locations.json"), "{}").is_err());
+  }
+}
@@ -0,0 +1,156 @@
+use chrono::Duration;
+
+use crate::models::{PlanBlock, PlanItem};
+
+/// Joins back-to-back blocks of the same class into one item.
+///
+/// WPS splits long classes into consecutive items, e.g. two 90-minute blocks
+/// with a short break. A block is appended to an earlier item of the same
+/// subject, type, room and instructors when it starts on the same day at most
+/// `max_gap` after that item ends. The merged item remembers the blocks it
+/// covers in `merged_from`.
+#[must_use]
+pub fn merge_adjacent(mut items: Vec<PlanItem>, max_gap: Duration) -> Vec<PlanItem> {
+  items.sort_by_key(|item| (item.starts_at, item.schedule_item_id));
+
+  let mut merged: Vec<PlanItem> = Vec::with_capacity(items.len());
+  for item in items {
+    match merged
+      .iter_mut()
+      .rev()
+      .find(|previous| continues(previous, &item, max_gap))
+    {
+      Some(previous) => extend(previous, &item),
+      None => merged.push(item),
+    }
+  }
+  merged
+}
+
+fn continues(previous: &PlanItem, item: &PlanItem, max_gap: Duration) -> bool {
+  let gap = item.starts_at - previous.ends_at;
+  gap >= Duration::zero()
+    && gap <= max_gap
+    && item.starts_at.date() == previous.ends_at.date()
+    && same_class(previous, item)
+}
+
+fn same_class(left: &PlanItem, right: &PlanItem) -> bool {
+  left.subject_name == right.subject_name
+    && left.class_type == right.class_type
+    && left.class_type_short == right.class_type_short
+    && left.room_number == right.room_number
+    && left.room_address == right.room_address
+    && left.webinar == right.webinar
+    && left.meeting_url == right.meeting_url
+    && left
+      .instructors
+      .iter()
+      .map(|instructor| &instructor.full_name)
+      .eq(
+        right
+          .instructors
+          .iter()
+          .map(|instructor| &instructor.full_name),
+      )
+}
+
+fn extend(previous: &mut PlanItem, item: &PlanItem) {
+  if previous.merged_from.is_empty() {
+    previous.merged_from.push(block(previous));
+  }
+  previous.merged_from.push(block(item));
+  previous.ends_at = item.ends_at;
+}
+
+fn block(item: &PlanItem) -> PlanBlock {
+  PlanBlock {
+    schedule_item_id: item.schedule_item_id,
+    starts_at: item.starts_at,
+    ends_at: item.ends_at,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::{NaiveDate, NaiveDateTime};
+
+  use super::*;
+  use crate::models::Instructor;
+
+  fn at(hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, 3, 2)
+      .expect("valid date")
+      .and_hms_opt(hour, minute, 0)
+      .expect("valid time")
+  }
+
+  fn exercise(id: i64, starts_at: NaiveDateTime) -> PlanItem {
+    PlanItem {
+      starts_at,
+      ends_at: starts_at + Duration::minutes(90),
+      subject_name: "Algebra".to_string(),
+      class_type: "Cwiczenia".to_string(),
+      class_type_short: "C".to_string(),
+      room_number: Some("A12".to_string()),
+      room_address: Some("Sterlinga 26".to_string()),
+      webinar: false,
+      instructors: vec![Instructor {
+        full_name: "Jan Kowalski".to_string(),
+      }],
+      schedule_item_id: id,
+      form_color: None,
+      meeting_url: None,
+      place: None,
+      merged_from: Vec::new(),
+    }
+  }
+
+  #[test]
+  fn back_to_back_blocks_become_one_item() {
+    let items = vec![
+      exercise(2, at(11, 45)),
+      exercise(1, at(10, 0)),
+      exercise(3, at(13, 30)),
+    ];
+
+    let merged = merge_adjacent(items, Duration::minutes(15));
+
+    assert_eq!(merged.len(), 1);
+    assert_eq!(merged[0].starts_at, at(10, 0));
+    assert_eq!(merged[0].ends_at, at(15, 0));
+    let ids: Vec<_> = merged[0]
+      .merged_from
+      .iter()
+      .map(|block| block.schedule_item_id)
+      .collect();
+    assert_eq!(ids, [1, 2, 3]);
+    assert_eq!(merged[0].merged_from[1].starts_at, at(11, 45));
+  }
+
+  #[test]
+  fn long_breaks_and_other_classes_stay_apart() {
+    let mut other_room = exercise(3, at(11, 30));
+    other_room.room_number = Some("B4".to_string());
+    let items = vec![exercise(1, at(8, 0)), exercise(2, at(9, 50)), other_room];
+
+    let merged = merge_adjacent(items, Duration::minutes(15));
+
+    assert_eq!(merged.len(), 3);
+    assert!(merged.iter().all(|item| item.merged_from.is_empty()));
+  }
+
+  #[test]
+  fn interleaved_classes_do_not_break_a_run() {
+    let mut lecture = exercise(9, at(11, 30));
+    lecture.subject_name = "Fizyka".to_string();
+    let items = vec![exercise(1, at(10, 0)), lecture, exercise(2, at(11, 40))];
+
+    let merged = merge_adjacent(items, Duration::minutes(10));
+
+    assert_eq!(merged.len(), 2);
+    assert_eq!(merged[0].merged_from.len(), 2);
+    assert_eq!(merged[0].ends_at, at(13, 10));
+    assert_eq!(merged[1].schedule_item_id, 9);
+  }
+}
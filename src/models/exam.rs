@@ -1,5 +1,5 @@
 use chrono::NaiveDateTime;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct CurrentAcademicYearResponse {
@@ -7,7 +7,7 @@ pub struct CurrentAcademicYearResponse {
   pub academic_year: i32,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 pub struct TermQuery {
   pub academic_year: i32,
   pub semester_id: i32,
@@ -61,7 +61,16 @@ pub struct ExamRecipient {
   pub section: Option<String>,
 }
 
-#[derive(Debug, Clone)]
+/// One indeks exams are looked up for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExamIndex {
+  pub index_id: i64,
+  pub section_name: Option<String>,
+  /// Shown on the events when a student has several indexes
+  pub programme: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ExamEvent {
   pub published_data_id: i64,
   pub subject: String,
@@ -72,4 +81,12 @@ pub struct ExamEvent {
   pub starts: NaiveDateTime,
   pub ends: NaiveDateTime,
   pub is_retake: bool,
+  /// False when WPS gave no usable start time and `starts` is a guess
+  pub time_confirmed: bool,
+  /// True when `ends` was derived from the default exam length
+  pub end_estimated: bool,
+  /// Programmes the exam was found for; empty for single-index students
+  pub programmes: Vec<String>,
+  /// Join link of an online exam, found in its room, notes or details
+  pub meeting_url: Option<String>,
 }
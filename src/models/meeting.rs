@@ -0,0 +1,154 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Deserializer};
+use serde_json::Value;
+
+/// Services online classes and exams are held on; a link to one of them is a
+/// meeting link wherever it appears
+const MEETING_HOSTS: &[&str] = &[
+  "teams.microsoft.com",
+  "teams.live.com",
+  "zoom.us",
+  "meet.google.com",
+  "webex.com",
+  "clickmeeting.com",
+];
+
+/// Plan fields that hold the join link itself, compared case-insensitively.
+/// Other link fields point at syllabi or materials, so they only count when
+/// the link goes to a known meeting service.
+const MEETING_FIELDS: &[&str] = &[
+  "linkspotkania",
+  "linkdospotkania",
+  "linkwebinar",
+  "linkdowebinaru",
+  "adresspotkania",
+  "urlspotkania",
+];
+
+/// Picks a meeting link out of the plan fields the model does not name.
+///
+/// WPS has no fixed field for it, so a link in one of the known meeting fields
+/// counts whatever it points at, as does any value pointing at a known meeting
+/// service. Fields are visited in name order, so the pick is stable.
+pub(super) fn plan_meeting_url<'de, D>(deserializer: D) -> Result<Option<String>, D::Error>
+where
+  D: Deserializer<'de>,
+{
+  let fields = BTreeMap::<String, Value>::deserialize(deserializer)?;
+  let named = fields.iter().find_map(|(key, value)| {
+    let key = key.to_lowercase();
+    MEETING_FIELDS
+      .contains(&key.as_str())
+      .then(|| first_url(value.as_str()?))
+      .flatten()
+  });
+  Ok(named.or_else(|| {
+    fields
+      .values()
+      .find_map(|value| meeting_url(value.as_str()?))
+  }))
+}
+
+/// First link to a known meeting service in free text
+#[must_use]
+pub fn meeting_url(text: &str) -> Option<String> {
+  urls(text)
+    .find(|url| is_meeting_host(url))
+    .map(str::to_string)
+}
+
+/// First web link in free text, whatever it points at
+#[must_use]
+pub fn first_url(text: &str) -> Option<String> {
+  urls(text).next().map(str::to_string)
+}
+
+fn urls(text: &str) -> impl Iterator<Item = &str> {
+  text
+    .split(|ch: char| ch.is_whitespace() || matches!(ch, '<' | '>' | '"' | '\'' | '(' | ')'))
+    .map(|token| token.trim_end_matches(['.', ',', ';', ':', '!', '?']))
+    .filter(|token| {
+      let lower = token.to_ascii_lowercase();
+      (lower.starts_with("https://") || lower.starts_with("http://")) && host(token).is_some()
+    })
+}
+
+fn host(url: &str) -> Option<&str> {
+  let rest = url.split_once("://")?.1;
+  let authority = rest.split(['/', '?', '#']).next()?;
+  let host = authority.rsplit('@').next()?.split(':').next()?;
+  (!host.is_empty()).then_some(host)
+}
+
+fn is_meeting_host(url: &str) -> bool {
+  host(url).is_some_and(|host| {
+    let host = host.to_ascii_lowercase();
+    MEETING_HOSTS
+      .iter()
+      .any(|known| host == *known || host.ends_with(&format!(".{known}")))
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn finds_meeting_links_in_free_text() {
+    let text = "Egzamin online (https://teams.microsoft.com/l/meetup-join/19%3a, haslo 123).";
+    assert_eq!(
+      meeting_url(text).as_deref(),
+      Some("https://teams.microsoft.com/l/meetup-join/19%3a")
+    );
+    assert_eq!(
+      meeting_url("Link: https://us02web.zoom.us/j/123?pwd=abc.").as_deref(),
+      Some("https://us02web.zoom.us/j/123?pwd=abc")
+    );
+  }
+
+  #[test]
+  fn plan_items_pick_up_links_from_unnamed_fields() {
+    let item = |extra: Value| {
+      let mut raw = serde_json::json!({
+        "DataOD": "2026-03-02T10:00:00",
+        "DataDO": "2026-03-02T11:30:00",
+        "PNazwa": "Algebra",
+        "TypZajec": "Wyklad",
+        "TypZajecSkrot": "W",
+        "Webinar": true,
+        "IDPlanZajecPoz": 1,
+      });
+      raw
+        .as_object_mut()
+        .expect("object")
+        .extend(extra.as_object().expect("object").clone());
+      serde_json::from_value::<crate::models::PlanItem>(raw).expect("valid plan item")
+    };
+
+    let named = item(serde_json::json!({ "LinkSpotkania": "https://ahe.lodz.pl/webinar/1" }));
+    assert_eq!(
+      named.meeting_url.as_deref(),
+      Some("https://ahe.lodz.pl/webinar/1")
+    );
+    let hosted = item(serde_json::json!({ "Uwagi": "Zoom: https://zoom.us/j/42" }));
+    assert_eq!(hosted.meeting_url.as_deref(), Some("https://zoom.us/j/42"));
+    assert_eq!(item(serde_json::json!({})).meeting_url, None);
+    let syllabus = item(serde_json::json!({ "LinkDoSylabusa": "https://ahe.lodz.pl/sylabus/7" }));
+    assert_eq!(syllabus.meeting_url, None);
+  }
+
+  #[test]
+  fn other_links_are_not_meetings() {
+    assert_eq!(meeting_url("Regulamin: https://ahe.lodz.pl/egzaminy"), None);
+    assert_eq!(
+      meeting_url("https://teams.microsoft.com.evil.example/x"),
+      None
+    );
+    assert_eq!(meeting_url("sala A12"), None);
+    assert_eq!(
+      first_url("Sala: https://ahe.lodz.pl/spotkanie").as_deref(),
+      Some("https://ahe.lodz.pl/spotkanie")
+    );
+  }
+}
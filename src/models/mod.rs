@@ -1,12 +1,16 @@
 mod exam;
+mod meeting;
 mod plan;
 mod student;
+mod text;
 mod token;
 
 pub use exam::{
-  CurrentAcademicYearResponse, ExamEvent, ExamProtocolIntermediateItem, ExamProtocolItem,
-  ExamRecipient, ExamScheduleItem, TermQuery,
+  CurrentAcademicYearResponse, ExamEvent, ExamIndex, ExamProtocolIntermediateItem,
+  ExamProtocolItem, ExamRecipient, ExamScheduleItem, TermQuery,
 };
-pub use plan::{Instructor, PlanItem};
+pub use meeting::{first_url, meeting_url};
+pub use plan::{Instructor, Place, PlanBlock, PlanItem};
 pub use student::{StudentData, StudentIndex};
+pub use text::normalize_text;
 pub use token::TokenResponse;
@@ -1,7 +1,7 @@
 use chrono::NaiveDateTime;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Instructor {
   #[serde(rename = "ImieNazwisko")]
   pub full_name: String,
@@ -31,4 +31,32 @@ pub struct PlanItem {
   pub schedule_item_id: i64,
   #[serde(rename = "FormaKolor", default)]
   pub form_color: Option<String>,
+  /// Join link of an online class, when WPS gives one
+  #[serde(flatten, deserialize_with = "super::meeting::plan_meeting_url")]
+  pub meeting_url: Option<String>,
+  /// Building the room is in, when the address table knows it
+  #[serde(skip)]
+  pub place: Option<Place>,
+  /// WPS blocks this item was joined from; empty unless back-to-back blocks
+  /// of the same class were merged into it
+  #[serde(skip)]
+  pub merged_from: Vec<PlanBlock>,
+}
+
+/// One of the consecutive WPS items a merged class covers
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Deserialize, Serialize)]
+pub struct PlanBlock {
+  pub schedule_item_id: i64,
+  pub starts_at: NaiveDateTime,
+  pub ends_at: NaiveDateTime,
+}
+
+/// Campus building with the coordinates calendar apps need for maps and travel time
+#[derive(Debug, Clone, PartialEq, Deserialize, Serialize)]
+pub struct Place {
+  pub name: String,
+  /// Postal address, e.g. `ul. Sterlinga 26, 90-212 Łódź`
+  pub address: String,
+  pub latitude: f64,
+  pub longitude: f64,
 }
@@ -31,4 +31,6 @@ pub struct StudentIndex {
   pub semester: Option<i32>,
   #[serde(rename = "SekcjaNazwa", default)]
   pub section_name: Option<String>,
+  #[serde(rename = "KierunekNazwa", default)]
+  pub programme_name: Option<String>,
 }
@@ -0,0 +1,10 @@
+/// Collapses runs of whitespace and lowercases, as WPS is not consistent about
+/// case and spacing in names and addresses
+#[must_use]
+pub fn normalize_text(value: &str) -> String {
+  value
+    .split_whitespace()
+    .collect::<Vec<_>>()
+    .join(" ")
+    .to_lowercase()
+}
@@ -0,0 +1,528 @@
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use chrono::{Datelike, Duration, NaiveDateTime, NaiveTime};
+use sha2::{Digest, Sha256};
+
+use crate::models::PlanItem;
+
+/// Fewer regular occurrences than this are not worth a series
+const MIN_SERIES_OCCURRENCES: usize = 3;
+/// Weekly and bi-weekly series only; sparser patterns stay single events
+const MAX_INTERVAL_WEEKS: i64 = 2;
+
+/// A class as rendered: on its own or folded into a weekly series
+#[derive(Debug)]
+pub enum PlanEntry<'a> {
+  Single(&'a PlanItem),
+  Series(Series<'a>),
+}
+
+impl PlanEntry<'_> {
+  fn starts(&self) -> NaiveDateTime {
+    match self {
+      Self::Single(item) => item.starts_at,
+      Self::Series(series) => series.master.starts_at,
+    }
+  }
+}
+
+/// Classes repeating on the same weekday, time and room, emitted as one
+/// master event with an `RRULE`
+#[derive(Debug)]
+pub struct Series<'a> {
+  /// Derived from what makes the classes one series, so it survives the
+  /// calendar window sliding past the first occurrence
+  pub key: String,
+  /// First regular occurrence; the master event is rendered from it
+  pub master: &'a PlanItem,
+  pub interval_weeks: i64,
+  /// Occurrences the rule generates, excluded ones included
+  pub count: i64,
+  /// Generated starts without a class, emitted as `EXDATE`
+  pub exdates: Vec<NaiveDateTime>,
+  /// Classes moved away from a generated start, emitted with `RECURRENCE-ID`
+  pub overrides: Vec<Override<'a>>,
+  /// Every class the series stands for, regular or moved
+  pub members: Vec<&'a PlanItem>,
+}
+
+/// One occurrence of a series that differs from the rule
+#[derive(Debug)]
+pub struct Override<'a> {
+  /// Start the rule generates for the replaced occurrence
+  pub recurrence_id: NaiveDateTime,
+  pub item: &'a PlanItem,
+}
+
+impl Series<'_> {
+  /// `RRULE` value; `COUNT` rather than `UNTIL` keeps it free of timezones
+  #[must_use]
+  pub fn rrule(&self) -> String {
+    format!(
+      "FREQ=WEEKLY;INTERVAL={};COUNT={}",
+      self.interval_weeks, self.count
+    )
+  }
+
+  /// Drops the `EXDATE` at `recurrence_id`, which a vanished occurrence left
+  /// behind, so the caller can emit it as a cancelled occurrence instead
+  pub fn take_exdate(&mut self, recurrence_id: NaiveDateTime) -> bool {
+    let Some(position) = self
+      .exdates
+      .iter()
+      .position(|exdate| *exdate == recurrence_id)
+    else {
+      return false;
+    };
+
+    self.exdates.remove(position);
+    true
+  }
+
+  /// Every member with the start the rule generates for it
+  pub fn occurrences(&self) -> impl Iterator<Item = (&PlanItem, NaiveDateTime)> {
+    self.members.iter().map(|item| {
+      let recurrence_id = self
+        .overrides
+        .iter()
+        .find(|moved| std::ptr::eq(moved.item, *item))
+        .map_or(item.starts_at, |moved| moved.recurrence_id);
+      (*item, recurrence_id)
+    })
+  }
+
+  /// Start of the last occurrence the rule generates
+  #[must_use]
+  pub fn last_start(&self) -> NaiveDateTime {
+    self.master.starts_at + Duration::weeks((self.count - 1) * self.interval_weeks)
+  }
+}
+
+/// What makes two classes the same course, wherever and whenever they happen
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Identity<'a> {
+  subject: &'a str,
+  class_type: &'a str,
+  class_type_short: &'a str,
+  instructors: Vec<&'a str>,
+}
+
+impl<'a> Identity<'a> {
+  fn of(item: &'a PlanItem) -> Self {
+    Self {
+      subject: &item.subject_name,
+      class_type: &item.class_type,
+      class_type_short: &item.class_type_short,
+      instructors: item
+        .instructors
+        .iter()
+        .map(|instructor| instructor.full_name.as_str())
+        .collect(),
+    }
+  }
+}
+
+/// Weekly time and place of a class
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+struct Slot<'a> {
+  weekday: u32,
+  starts: NaiveTime,
+  length: Duration,
+  room_number: Option<&'a str>,
+  room_address: Option<&'a str>,
+  webinar: bool,
+  meeting_url: Option<&'a str>,
+}
+
+impl<'a> Slot<'a> {
+  fn of(item: &'a PlanItem) -> Self {
+    Self {
+      weekday: item.starts_at.weekday().num_days_from_monday(),
+      starts: item.starts_at.time(),
+      length: item.ends_at - item.starts_at,
+      room_number: item.room_number.as_deref(),
+      room_address: item.room_address.as_deref(),
+      webinar: item.webinar,
+      meeting_url: item.meeting_url.as_deref(),
+    }
+  }
+}
+
+/// Folds classes that repeat weekly or bi-weekly into series; everything
+/// else stays a single event. Entries come out ordered by start.
+#[must_use]
+pub fn group(items: &[PlanItem]) -> Vec<PlanEntry<'_>> {
+  let mut courses: BTreeMap<Identity, Vec<&PlanItem>> = BTreeMap::new();
+  for item in items {
+    courses.entry(Identity::of(item)).or_default().push(item);
+  }
+
+  let mut entries = Vec::new();
+  for (identity, classes) in courses {
+    let mut slots: BTreeMap<Slot, Vec<&PlanItem>> = BTreeMap::new();
+    for item in classes {
+      slots.entry(Slot::of(item)).or_default().push(item);
+    }
+
+    let mut series = Vec::new();
+    let mut loose = Vec::new();
+    for (slot, mut members) in slots {
+      members.sort_by_key(|item| item.starts_at);
+      match build_series(&identity, &slot, &members) {
+        Some(built) => series.push(built),
+        None => loose.extend(members),
+      }
+    }
+
+    loose.sort_by_key(|item| item.starts_at);
+    for item in loose {
+      if !attach_override(&mut series, item) {
+        entries.push(PlanEntry::Single(item));
+      }
+    }
+    entries.extend(series.into_iter().map(PlanEntry::Series));
+  }
+
+  entries.sort_by_key(PlanEntry::starts);
+  entries
+}
+
+/// `members` must be sorted by start
+fn build_series<'a>(
+  identity: &Identity,
+  slot: &Slot,
+  members: &[&'a PlanItem],
+) -> Option<Series<'a>> {
+  let (&master, _) = members.split_first()?;
+  if members.len() < MIN_SERIES_OCCURRENCES {
+    return None;
+  }
+
+  // Same weekday and time, so every start is a whole number of weeks after the first
+  let weeks: Vec<i64> = members
+    .iter()
+    .map(|item| (item.starts_at - master.starts_at).num_days() / 7)
+    .collect();
+  let interval_weeks = weeks
+    .windows(2)
+    .try_fold(0, |interval, pair| match pair[1] - pair[0] {
+      // Two classes at the same start are not a series
+      0 => None,
+      step => Some(gcd(interval, step)),
+    })?;
+  if interval_weeks > MAX_INTERVAL_WEEKS {
+    return None;
+  }
+
+  let count = weeks.last().copied().unwrap_or_default() / interval_weeks + 1;
+  let exdates: Vec<NaiveDateTime> = (0..count)
+    .map(|occurrence| master.starts_at + Duration::weeks(occurrence * interval_weeks))
+    .filter(|start| !members.iter().any(|item| item.starts_at == *start))
+    .collect();
+  // Mostly holes means the classes only happen to share a slot
+  if exdates.len() > members.len() {
+    return None;
+  }
+
+  Some(Series {
+    key: series_key(identity, slot),
+    master,
+    interval_weeks,
+    count,
+    exdates,
+    overrides: Vec::new(),
+    members: members.to_vec(),
+  })
+}
+
+/// A class moved within the week of a missing occurrence replaces it
+fn attach_override<'a>(series: &mut [Series<'a>], item: &'a PlanItem) -> bool {
+  let week = item.starts_at.iso_week();
+  for candidate in series {
+    let Some(position) = candidate
+      .exdates
+      .iter()
+      .position(|exdate| exdate.iso_week() == week)
+    else {
+      continue;
+    };
+
+    let recurrence_id = candidate.exdates.remove(position);
+    candidate.overrides.push(Override {
+      recurrence_id,
+      item,
+    });
+    candidate.members.push(item);
+    return true;
+  }
+  false
+}
+
+fn series_key(identity: &Identity, slot: &Slot) -> String {
+  let mut hasher = Sha256::new();
+  for part in [
+    identity.subject,
+    identity.class_type,
+    identity.class_type_short,
+    &identity.instructors.join("\n"),
+    &slot.weekday.to_string(),
+    &slot.starts.to_string(),
+    &slot.length.num_minutes().to_string(),
+    slot.room_number.unwrap_or_default(),
+    slot.room_address.unwrap_or_default(),
+    if slot.webinar { "webinar" } else { "" },
+  ] {
+    // The separator keeps adjacent fields from running into each other
+    hasher.update(part.as_bytes());
+    hasher.update([0u8]);
+  }
+  // Only hashed when present, so keys of classes without a link stay put
+  if let Some(url) = slot.meeting_url {
+    hasher.update(url.as_bytes());
+    hasher.update([0u8]);
+  }
+
+  hasher.finalize()[..8]
+    .iter()
+    .fold(String::new(), |mut acc, byte| {
+      let _ = write!(acc, "{byte:02x}");
+      acc
+    })
+}
+
+fn gcd(left: i64, right: i64) -> i64 {
+  if right == 0 {
+    left.abs()
+  } else {
+    gcd(right, left % right)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::NaiveDate;
+
+  use super::*;
+  use crate::models::Instructor;
+
+  /// Everything a subscriber sees of one occurrence
+  type Occurrence = (
+    NaiveDateTime,
+    NaiveDateTime,
+    String,
+    Option<String>,
+    Vec<String>,
+  );
+
+  fn lecture(id: i64, month: u32, day: u32, hour: u32) -> PlanItem {
+    let starts_at = NaiveDate::from_ymd_opt(2026, month, day)
+      .expect("valid date")
+      .and_hms_opt(hour, 0, 0)
+      .expect("valid time");
+    PlanItem {
+      starts_at,
+      ends_at: starts_at + Duration::minutes(90),
+      subject_name: "Algebra".to_string(),
+      class_type: "Wyklad".to_string(),
+      class_type_short: "W".to_string(),
+      room_number: Some("A12".to_string()),
+      room_address: Some("Sterlinga 26".to_string()),
+      webinar: false,
+      instructors: vec![Instructor {
+        full_name: "Jan Kowalski".to_string(),
+      }],
+      schedule_item_id: id,
+      form_color: None,
+      meeting_url: None,
+      place: None,
+      merged_from: Vec::new(),
+    }
+  }
+
+  /// Monday lectures from 2 March 2026 on
+  fn weekly(weeks: i64) -> Vec<PlanItem> {
+    let first = lecture(0, 3, 2, 10);
+    (0..weeks)
+      .map(|week| {
+        let mut item = first.clone();
+        item.schedule_item_id = week;
+        item.starts_at += Duration::weeks(week);
+        item.ends_at += Duration::weeks(week);
+        item
+      })
+      .collect()
+  }
+
+  fn occurrence(item: &PlanItem, starts_at: NaiveDateTime) -> Occurrence {
+    (
+      starts_at,
+      starts_at + (item.ends_at - item.starts_at),
+      item.subject_name.clone(),
+      item.room_number.clone(),
+      item
+        .instructors
+        .iter()
+        .map(|instructor| instructor.full_name.clone())
+        .collect(),
+    )
+  }
+
+  /// What a client computes from the rendered entries, per RFC 5545
+  fn expand(entries: &[PlanEntry]) -> Vec<Occurrence> {
+    let mut occurrences = Vec::new();
+    for entry in entries {
+      match entry {
+        PlanEntry::Single(item) => occurrences.push(occurrence(item, item.starts_at)),
+        PlanEntry::Series(series) => {
+          for index in 0..series.count {
+            let start = series.master.starts_at + Duration::weeks(index * series.interval_weeks);
+            if series.exdates.contains(&start) {
+              continue;
+            }
+            match series
+              .overrides
+              .iter()
+              .find(|moved| moved.recurrence_id == start)
+            {
+              Some(moved) => occurrences.push(occurrence(moved.item, moved.item.starts_at)),
+              None => occurrences.push(occurrence(series.master, start)),
+            }
+          }
+        }
+      }
+    }
+    occurrences.sort();
+    occurrences
+  }
+
+  fn assert_round_trip(items: &[PlanItem]) -> Vec<PlanEntry<'_>> {
+    let entries = group(items);
+    let mut expected: Vec<Occurrence> = items
+      .iter()
+      .map(|item| occurrence(item, item.starts_at))
+      .collect();
+    expected.sort();
+
+    assert_eq!(expand(&entries), expected);
+    entries
+  }
+
+  fn series<'a, 'b>(entries: &'b [PlanEntry<'a>]) -> Vec<&'b Series<'a>> {
+    entries
+      .iter()
+      .filter_map(|entry| match entry {
+        PlanEntry::Series(series) => Some(series),
+        PlanEntry::Single(_) => None,
+      })
+      .collect()
+  }
+
+  #[test]
+  fn weekly_classes_become_one_series() {
+    let items = weekly(6);
+
+    let entries = assert_round_trip(&items);
+    assert_eq!(entries.len(), 1);
+    let found = series(&entries);
+    assert_eq!(found[0].rrule(), "FREQ=WEEKLY;INTERVAL=1;COUNT=6");
+    assert!(found[0].exdates.is_empty());
+    assert_eq!(found[0].members.len(), 6);
+  }
+
+  #[test]
+  fn gaps_become_exdates_and_moved_classes_overrides() {
+    let mut items = vec![
+      lecture(1, 3, 2, 10),
+      lecture(2, 3, 9, 10),
+      // 16 March is skipped entirely
+      lecture(4, 3, 23, 10),
+      // 30 March moves to Thursday afternoon
+      lecture(5, 4, 2, 14),
+      lecture(6, 4, 6, 10),
+      lecture(7, 4, 13, 10),
+    ];
+    // 13 April gets a different room, same time
+    items[5].room_number = Some("B7".to_string());
+    items.push(lecture(8, 4, 20, 10));
+
+    let entries = assert_round_trip(&items);
+    let found = series(&entries);
+    assert_eq!(found.len(), 1);
+    let series = found[0];
+    assert_eq!(series.rrule(), "FREQ=WEEKLY;INTERVAL=1;COUNT=8");
+    assert_eq!(series.exdates, [items[2].starts_at - Duration::weeks(1)]);
+    assert_eq!(series.overrides.len(), 2);
+    assert_eq!(series.members.len(), items.len());
+    let (_, moved_from) = series
+      .occurrences()
+      .find(|(item, _)| item.schedule_item_id == 5)
+      .expect("moved class");
+    assert_eq!(moved_from, lecture(5, 3, 30, 10).starts_at);
+  }
+
+  #[test]
+  fn bi_weekly_classes_keep_their_interval() {
+    let items: Vec<PlanItem> = [2, 16, 30]
+      .into_iter()
+      .enumerate()
+      .map(|(id, day)| lecture(i64::try_from(id).expect("small"), 3, day, 8))
+      .chain([lecture(9, 4, 27, 8)])
+      .collect();
+
+    let entries = assert_round_trip(&items);
+    let found = series(&entries);
+    assert_eq!(found[0].rrule(), "FREQ=WEEKLY;INTERVAL=2;COUNT=5");
+    assert_eq!(found[0].exdates.len(), 1);
+  }
+
+  #[test]
+  fn sparse_or_short_runs_stay_single_events() {
+    let pair = [lecture(1, 3, 2, 10), lecture(2, 3, 9, 10)];
+    assert!(series(&assert_round_trip(&pair)).is_empty());
+
+    let monthly = [
+      lecture(1, 3, 2, 10),
+      lecture(2, 3, 30, 10),
+      lecture(3, 4, 27, 10),
+    ];
+    assert!(series(&assert_round_trip(&monthly)).is_empty());
+
+    let mut others = vec![
+      lecture(1, 3, 2, 10),
+      lecture(2, 3, 9, 10),
+      lecture(3, 3, 16, 10),
+    ];
+    others[1].subject_name = "Analiza".to_string();
+    assert!(series(&assert_round_trip(&others)).is_empty());
+  }
+
+  #[test]
+  fn series_keys_ignore_the_window() {
+    let items = weekly(5);
+
+    let full = group(&items);
+    let later = group(&items[1..]);
+    assert_eq!(series(&full)[0].key, series(&later)[0].key);
+  }
+
+  #[test]
+  fn vanished_occurrences_can_be_reclaimed_from_exdates() {
+    let mut items = vec![
+      lecture(1, 3, 2, 10),
+      lecture(2, 3, 9, 10),
+      lecture(3, 3, 16, 10),
+      lecture(4, 3, 23, 10),
+    ];
+    let vanished = items.remove(1);
+
+    let mut entries = group(&items);
+    let PlanEntry::Series(series) = &mut entries[0] else {
+      panic!("expected a series");
+    };
+    assert!(series.take_exdate(vanished.starts_at));
+    assert!(series.exdates.is_empty());
+    assert!(!series.take_exdate(vanished.starts_at));
+    assert_eq!(series.last_start(), items[2].starts_at);
+  }
+}
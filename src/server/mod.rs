@@ -0,0 +1,107 @@
+#[cfg(unix)]
+mod reload;
+mod shutdown;
+mod tls;
+
+use std::net::SocketAddr;
+
+use anyhow::{Context, Result};
+use axum::Router;
+use axum_server::Handle;
+use axum_server::tls_rustls::RustlsConfig;
+use listenfd::ListenFd;
+use tracing::{error, info};
+
+use crate::config::ServerSettings;
+
+/// Binds the configured listener and serves the router, over HTTPS when a
+/// certificate is configured and plain HTTP otherwise.
+///
+/// Listeners passed in via systemd socket activation (`LISTEN_FDS`) take
+/// precedence over `BIND_ADDR` / `HTTP_REDIRECT_ADDR`. On SIGTERM or SIGINT
+/// the server stops accepting connections and drains in-flight requests for
+/// up to the configured shutdown timeout; SIGHUP re-reads the secrets.
+///
+/// # Errors
+///
+/// Returns an error if a listener cannot be bound, the certificate cannot be
+/// loaded, or the server stops with an I/O error.
+pub async fn serve<C: ServerSettings>(app: Router, config: &C) -> Result<()> {
+  let mut inherited = ListenFd::from_env();
+  let bind_addr = config.bind_addr();
+  let listener = take_or_bind(&mut inherited, 0, bind_addr).await?;
+  let make_service = app.into_make_service_with_connect_info::<SocketAddr>();
+
+  let handle = Handle::new();
+  tokio::spawn(shutdown::drain_on_signal(
+    handle.clone(),
+    config.shutdown_timeout(),
+  ));
+  #[cfg(unix)]
+  tokio::spawn(reload::reload_on_sighup(config.clone()));
+
+  let Some(settings) = config.tls() else {
+    println!("listening on http://{}", listener.local_addr()?);
+    axum_server::from_tcp(listener)?
+      .handle(handle)
+      .serve(make_service)
+      .await?;
+    info!("server stopped");
+    return Ok(());
+  };
+
+  let https_port = match config.https_public_port() {
+    Some(port) => port,
+    None => listener.local_addr()?.port(),
+  };
+  let rustls = RustlsConfig::from_pem_file(&settings.cert_path, &settings.key_path)
+    .await
+    .context("failed to load TLS_CERT_PATH / TLS_KEY_PATH")?;
+  tokio::spawn(tls::watch_certificate(rustls.clone(), settings.clone()));
+
+  if let Some(redirect_addr) = config.http_redirect_addr() {
+    let redirect_listener = take_or_bind(&mut inherited, 1, redirect_addr).await?;
+    println!(
+      "redirecting http://{} to https",
+      redirect_listener.local_addr()?
+    );
+    let redirect_server = axum_server::from_tcp(redirect_listener)?.handle(handle.clone());
+    tokio::spawn(async move {
+      let redirect = tls::redirect_router(https_port).into_make_service();
+      if let Err(error) = redirect_server.serve(redirect).await {
+        error!(error = %error, "http redirect listener stopped");
+      }
+    });
+  }
+
+  println!("listening on https://{}", listener.local_addr()?);
+  axum_server::from_tcp_rustls(listener, rustls)?
+    .handle(handle)
+    .serve(make_service)
+    .await?;
+  info!("server stopped");
+
+  Ok(())
+}
+
+/// Uses the socket-activated listener at `index` when present, otherwise binds `addr`.
+async fn take_or_bind(
+  inherited: &mut ListenFd,
+  index: usize,
+  addr: &str,
+) -> Result<std::net::TcpListener> {
+  if let Some(listener) = inherited
+    .take_tcp_listener(index)
+    .context("LISTEN_FDS socket is not a TCP listener")?
+  {
+    info!(index, "using socket-activated listener");
+    listener.set_nonblocking(true)?;
+    return Ok(listener);
+  }
+
+  let listener = tokio::net::TcpListener::bind(addr)
+    .await
+    .with_context(|| format!("failed to bind {addr}"))?;
+
+  Ok(listener.into_std()?)
+}
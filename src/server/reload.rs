@@ -0,0 +1,25 @@
+use tokio::signal::unix::{SignalKind, signal};
+use tracing::{info, warn};
+
+use crate::config::ServerSettings;
+
+/// Re-reads secrets on every SIGHUP, so rotating a `_FILE` (e.g. the WPS
+/// password) does not need a restart. A failed reload keeps the old values.
+pub(super) async fn reload_on_sighup<C: ServerSettings>(config: C) {
+  let mut hangups = match signal(SignalKind::hangup()) {
+    Ok(stream) => stream,
+    Err(error) => {
+      warn!(error = %error, "failed to listen for SIGHUP, secrets will not be reloaded");
+      return;
+    }
+  };
+
+  while hangups.recv().await.is_some() {
+    match config.reload_secrets() {
+      Ok(()) => info!("secrets reloaded"),
+      Err(error) => {
+        warn!(error = %format!("{error:#}"), "secret reload failed, keeping previous values");
+      }
+    }
+  }
+}
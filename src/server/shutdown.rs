@@ -0,0 +1,51 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use axum_server::Handle;
+use tracing::{info, warn};
+
+/// Waits for SIGTERM or SIGINT, then stops accepting connections and gives
+/// in-flight requests up to `timeout` to finish.
+pub(super) async fn drain_on_signal(handle: Handle<SocketAddr>, timeout: Duration) {
+  shutdown_signal().await;
+
+  info!(
+    connections = handle.connection_count(),
+    timeout_seconds = timeout.as_secs(),
+    "shutdown requested, draining in-flight requests"
+  );
+  handle.graceful_shutdown(Some(timeout));
+}
+
+async fn shutdown_signal() {
+  let interrupt = async {
+    if let Err(error) = tokio::signal::ctrl_c().await {
+      warn!(error = %error, "failed to listen for SIGINT");
+      std::future::pending::<()>().await;
+    }
+  };
+
+  #[cfg(unix)]
+  let terminate = async {
+    use tokio::signal::unix::{SignalKind, signal};
+
+    match signal(SignalKind::terminate()) {
+      Ok(mut stream) => {
+        stream.recv().await;
+      }
+      Err(error) => {
+        warn!(error = %error, "failed to listen for SIGTERM");
+        std::future::pending::<()>().await;
+      }
+    }
+  };
+
+  // Windows has no SIGTERM; Ctrl+C covers interactive stops there
+  #[cfg(not(unix))]
+  let terminate = std::future::pending::<()>();
+
+  tokio::select! {
+    () = interrupt => {},
+    () = terminate => {},
+  }
+}
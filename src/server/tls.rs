@@ -0,0 +1,142 @@
+use std::time::{Duration, SystemTime};
+
+use axum::Router;
+use axum::extract::State;
+use axum::http::header::HOST;
+use axum::http::uri::{Authority, PathAndQuery};
+use axum::http::{HeaderMap, StatusCode, Uri};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::{info, warn};
+
+use crate::config::TlsSettings;
+
+/// How often the certificate files are checked for changes (e.g. a certbot renewal).
+const CERT_RELOAD_CHECK_SECONDS: u64 = 60;
+
+/// Reloads the served certificate whenever either PEM file changes on disk.
+pub(super) async fn watch_certificate(rustls: RustlsConfig, settings: TlsSettings) {
+  let mut last_seen = modified_times(&settings).await;
+  let mut ticker = tokio::time::interval(Duration::from_secs(CERT_RELOAD_CHECK_SECONDS));
+  // The first tick completes immediately
+  ticker.tick().await;
+
+  loop {
+    ticker.tick().await;
+
+    let current = modified_times(&settings).await;
+    if current == last_seen {
+      continue;
+    }
+
+    // A renewal may replace the certificate before the key; on failure the
+    // previous pair keeps serving and the next tick retries.
+    match rustls
+      .reload_from_pem_file(&settings.cert_path, &settings.key_path)
+      .await
+    {
+      Ok(()) => {
+        info!("TLS certificate reloaded");
+        last_seen = current;
+      }
+      Err(error) => {
+        warn!(error = %error, "TLS certificate reload failed, keeping the previous one");
+      }
+    }
+  }
+}
+
+async fn modified_times(settings: &TlsSettings) -> (Option<SystemTime>, Option<SystemTime>) {
+  (
+    modified_time(&settings.cert_path).await,
+    modified_time(&settings.key_path).await,
+  )
+}
+
+async fn modified_time(path: &std::path::Path) -> Option<SystemTime> {
+  // Follows symlinks, so certbot's live/ -> archive/ swaps are noticed
+  tokio::fs::metadata(path).await.ok()?.modified().ok()
+}
+
+/// Plain HTTP router that sends every request to the HTTPS listener.
+pub(super) fn redirect_router(https_port: u16) -> Router {
+  Router::new()
+    .fallback(redirect_to_https)
+    .with_state(https_port)
+}
+
+async fn redirect_to_https(
+  State(https_port): State<u16>,
+  headers: HeaderMap,
+  uri: Uri,
+) -> Response {
+  let host = headers.get(HOST).and_then(|value| value.to_str().ok());
+
+  match https_redirect_target(host, &uri, https_port) {
+    Some(target) => Redirect::permanent(&target).into_response(),
+    None => (StatusCode::BAD_REQUEST, "missing or invalid Host header").into_response(),
+  }
+}
+
+fn https_redirect_target(host: Option<&str>, uri: &Uri, https_port: u16) -> Option<String> {
+  let authority: Authority = host?.trim().parse().ok()?;
+  let host = authority.host();
+  let path = uri.path_and_query().map_or("/", PathAndQuery::as_str);
+
+  if https_port == 443 {
+    Some(format!("https://{host}{path}"))
+  } else {
+    Some(format!("https://{host}:{https_port}{path}"))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn uri(value: &str) -> Uri {
+    value.parse().expect("valid uri")
+  }
+
+  #[test]
+  fn redirect_keeps_path_and_query() {
+    assert_eq!(
+      https_redirect_target(
+        Some("cal.example"),
+        &uri("/calendar.ics?from=2026-01-01"),
+        443
+      ),
+      Some("https://cal.example/calendar.ics?from=2026-01-01".to_string())
+    );
+  }
+
+  #[test]
+  fn redirect_replaces_the_plain_http_port() {
+    assert_eq!(
+      https_redirect_target(Some("cal.example:8080"), &uri("/healthz"), 8443),
+      Some("https://cal.example:8443/healthz".to_string())
+    );
+    assert_eq!(
+      https_redirect_target(Some("cal.example:80"), &uri("/"), 443),
+      Some("https://cal.example/".to_string())
+    );
+  }
+
+  #[test]
+  fn redirect_keeps_ipv6_brackets() {
+    assert_eq!(
+      https_redirect_target(Some("[::1]:8080"), &uri("/"), 8443),
+      Some("https://[::1]:8443/".to_string())
+    );
+  }
+
+  #[test]
+  fn redirect_needs_a_usable_host() {
+    assert_eq!(https_redirect_target(None, &uri("/"), 443), None);
+    assert_eq!(https_redirect_target(Some(""), &uri("/"), 443), None);
+    assert_eq!(
+      https_redirect_target(Some("evil.example/path"), &uri("/"), 443),
+      None
+    );
+  }
+}
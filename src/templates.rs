@@ -0,0 +1,346 @@
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use anyhow::{Context, Result, bail};
+use serde::Deserialize;
+
+use crate::config::{CalendarLanguage, parse_structured};
+use crate::i18n::Catalogs;
+use crate::models::normalize_text;
+
+/// Placeholders a class template may use
+pub const CLASS_FIELDS: &[&str] = &[
+  "subject",
+  "subject_full",
+  "type",
+  "type_short",
+  "room_number",
+  "room_address",
+  "location",
+  "instructors",
+  "date",
+  "starts",
+  "ends",
+  "blocks",
+  "meeting_url",
+  "schedule_item_id",
+  "webinar",
+  "form_color",
+  "place",
+  "map_url",
+];
+
+/// Placeholders an exam template may use
+pub const EXAM_FIELDS: &[&str] = &[
+  "subject",
+  "subject_full",
+  "kind",
+  "notes",
+  "location",
+  "lecturer",
+  "details",
+  "date",
+  "starts",
+  "ends",
+  "programmes",
+  "meeting_url",
+  "published_data_id",
+  "is_retake",
+  "time_confirmed",
+  "end_estimated",
+];
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Segment {
+  Text(String),
+  Field(String),
+}
+
+/// Text with `{field}` placeholders; `{{` and `}}` stand for literal braces
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Template {
+  segments: Vec<Segment>,
+}
+
+impl Template {
+  /// # Errors
+  ///
+  /// Returns an error for unbalanced braces or a placeholder not in `fields`.
+  pub fn parse(source: &str, fields: &[&str]) -> Result<Self> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = source.chars();
+    while let Some(ch) = chars.next() {
+      match ch {
+        '{' if chars.as_str().starts_with('{') => {
+          chars.next();
+          text.push('{');
+        }
+        '}' if chars.as_str().starts_with('}') => {
+          chars.next();
+          text.push('}');
+        }
+        '{' => {
+          let Some((name, rest)) = chars.as_str().split_once('}') else {
+            bail!("unclosed `{{` in template `{source}`");
+          };
+          let name = name.trim();
+          if !fields.contains(&name) {
+            bail!(
+              "unknown placeholder `{{{name}}}` in template `{source}`; expected one of: {}",
+              fields.join(", ")
+            );
+          }
+          if !text.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut text)));
+          }
+          segments.push(Segment::Field(name.to_string()));
+          chars = rest.chars();
+        }
+        '}' => bail!("unmatched `}}` in template `{source}`"),
+        _ => text.push(ch),
+      }
+    }
+    if !text.is_empty() {
+      segments.push(Segment::Text(text));
+    }
+    Ok(Self { segments })
+  }
+
+  /// Fills in every placeholder with what `value` returns for its name
+  #[must_use]
+  pub fn render(&self, value: impl Fn(&str) -> String) -> String {
+    self
+      .segments
+      .iter()
+      .map(|segment| match segment {
+        Segment::Text(text) => text.clone(),
+        Segment::Field(name) => value(name),
+      })
+      .collect()
+  }
+}
+
+/// Event texts a user may take over; unset ones keep the built-in layout
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TemplateSet {
+  pub summary: Option<Template>,
+  pub description: Option<Template>,
+  pub exam_summary: Option<Template>,
+  pub exam_description: Option<Template>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct RawTemplateSet {
+  summary: Option<String>,
+  description: Option<String>,
+  exam_summary: Option<String>,
+  exam_description: Option<String>,
+}
+
+impl RawTemplateSet {
+  fn parse(self) -> Result<TemplateSet> {
+    let parse =
+      |raw: Option<String>, fields| raw.map(|raw| Template::parse(&raw, fields)).transpose();
+    Ok(TemplateSet {
+      summary: parse(self.summary, CLASS_FIELDS).context("invalid summary")?,
+      description: parse(self.description, CLASS_FIELDS).context("invalid description")?,
+      exam_summary: parse(self.exam_summary, EXAM_FIELDS).context("invalid exam_summary")?,
+      exam_description: parse(self.exam_description, EXAM_FIELDS)
+        .context("invalid exam_description")?,
+    })
+  }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TemplateFile {
+  #[serde(default)]
+  aliases: BTreeMap<String, String>,
+  #[serde(default)]
+  templates: RawTemplateSet,
+  #[serde(default)]
+  languages: BTreeMap<String, RawTemplateSet>,
+}
+
+/// User wording for events: short subject names and templates, for every
+/// language or for one of them
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Templates {
+  /// Keyed by the normalized full subject name
+  aliases: BTreeMap<String, String>,
+  all: TemplateSet,
+  languages: Vec<(CalendarLanguage, TemplateSet)>,
+}
+
+impl Templates {
+  /// Reads a TOML or YAML file with `aliases`, `templates` and per-language
+  /// `languages.<code>` tables
+  ///
+  /// # Errors
+  ///
+  /// Returns an error if the file cannot be read or parsed, names a language
+  /// without a catalog, or has a template with an unknown placeholder.
+  pub fn load(path: &Path, catalogs: &Catalogs) -> Result<Self> {
+    let contents = std::fs::read_to_string(path)
+      .with_context(|| format!("failed to read templates file {}", path.display()))?;
+    Self::parse(path, &contents, catalogs)
+  }
+
+  pub(crate) fn parse(path: &Path, contents: &str, catalogs: &Catalogs) -> Result<Self> {
+    let file: TemplateFile = parse_structured(path, contents, "templates file")?;
+    let in_file = || format!("in templates file {}", path.display());
+
+    let all = file.templates.parse().with_context(in_file)?;
+    let mut languages = Vec::with_capacity(file.languages.len());
+    for (code, raw) in file.languages {
+      let Some(lang) = CalendarLanguage::from_code(&code).filter(|lang| catalogs.contains(*lang))
+      else {
+        bail!("unknown language `{code}` {}", in_file());
+      };
+      let set = raw
+        .parse()
+        .with_context(|| format!("for language `{code}` {}", in_file()))?;
+      languages.push((lang, set));
+    }
+
+    let aliases = file
+      .aliases
+      .into_iter()
+      .map(|(subject, alias)| (normalize_text(&subject), alias))
+      .collect();
+    Ok(Self {
+      aliases,
+      all,
+      languages,
+    })
+  }
+
+  /// Short name configured for a subject, or the subject itself
+  #[must_use]
+  pub fn alias<'a>(&'a self, subject: &'a str) -> &'a str {
+    self
+      .aliases
+      .get(&normalize_text(subject))
+      .map_or(subject, String::as_str)
+  }
+
+  #[must_use]
+  pub fn summary(&self, lang: CalendarLanguage) -> Option<&Template> {
+    self.pick(lang, |set| set.summary.as_ref())
+  }
+
+  #[must_use]
+  pub fn description(&self, lang: CalendarLanguage) -> Option<&Template> {
+    self.pick(lang, |set| set.description.as_ref())
+  }
+
+  #[must_use]
+  pub fn exam_summary(&self, lang: CalendarLanguage) -> Option<&Template> {
+    self.pick(lang, |set| set.exam_summary.as_ref())
+  }
+
+  #[must_use]
+  pub fn exam_description(&self, lang: CalendarLanguage) -> Option<&Template> {
+    self.pick(lang, |set| set.exam_description.as_ref())
+  }
+
+  /// A language's own template wins over the one for every language
+  fn pick<'a>(
+    &'a self,
+    lang: CalendarLanguage,
+    field: impl Fn(&'a TemplateSet) -> Option<&'a Template>,
+  ) -> Option<&'a Template> {
+    self
+      .languages
+      .iter()
+      .find(|(code, _)| *code == lang)
+      .and_then(|(_, set)| field(set))
+      .or_else(|| field(&self.all))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const FILE: &str = r#"
+[aliases]
+"Podstawy programowania obiektowego" = "PPO"
+
+[templates]
+summary = "{subject} ({type_short})"
+
+[languages.en]
+summary = "{subject} - {type}"
+exam_summary = "Exam {{{subject}}}"
+"#;
+
+  #[test]
+  fn placeholders_are_filled_in_and_braces_escaped() {
+    let template = Template::parse("{{{subject}}} {starts}-{ends}", CLASS_FIELDS).expect("valid");
+
+    let text = template.render(|name| match name {
+      "subject" => "PPO".to_string(),
+      "starts" => "10:00".to_string(),
+      _ => "11:30".to_string(),
+    });
+    assert_eq!(text, "{PPO} 10:00-11:30");
+  }
+
+  #[test]
+  fn unknown_placeholders_and_stray_braces_are_rejected() {
+    let error = Template::parse("{lecturer}", CLASS_FIELDS).expect_err("exam-only field");
+    assert!(error.to_string().contains("lecturer"), "{error}");
+    assert!(Template::parse("{subject", CLASS_FIELDS).is_err());
+    assert!(Template::parse("subject}", CLASS_FIELDS).is_err());
+  }
+
+  #[test]
+  fn language_templates_win_over_the_shared_ones() {
+    let templates = Templates::parse(Path::new("templates.toml"), FILE, &Catalogs::default())
+      .expect("valid file");
+    let subject =
+      |template: Option<&Template>| template.expect("template set").render(str::to_string);
+
+    assert_eq!(
+      subject(templates.summary(CalendarLanguage::PL)),
+      "subject (type_short)"
+    );
+    assert_eq!(
+      subject(templates.summary(CalendarLanguage::EN)),
+      "subject - type"
+    );
+    assert_eq!(templates.exam_summary(CalendarLanguage::PL), None);
+    assert_eq!(templates.description(CalendarLanguage::EN), None);
+  }
+
+  #[test]
+  fn aliases_ignore_case_and_spacing() {
+    let templates = Templates::parse(Path::new("templates.toml"), FILE, &Catalogs::default())
+      .expect("valid file");
+
+    assert_eq!(
+      templates.alias("Podstawy  programowania Obiektowego"),
+      "PPO"
+    );
+    assert_eq!(templates.alias("Algebra"), "Algebra");
+  }
+
+  #[test]
+  fn unknown_languages_and_keys_are_rejected() {
+    let unknown_lang = "[languages.xx]\nsummary = \"{subject}\"\n";
+    assert!(
+      Templates::parse(
+        Path::new("templates.toml"),
+        unknown_lang,
+        &Catalogs::default()
+      )
+      .is_err()
+    );
+
+    let typo = "templates:\n  sumary: \"{subject}\"\n";
+    assert!(Templates::parse(Path::new("templates.yaml"), typo, &Catalogs::default()).is_err());
+  }
+}
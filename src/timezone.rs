@@ -0,0 +1,255 @@
+use std::fmt::{self, Write};
+
+use chrono::{DateTime, Duration, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::{OffsetComponents, OffsetName, TZ_VARIANTS, Tz, TzOffset};
+
+/// Zone of every time WPS publishes; they carry no offset of their own.
+pub const SOURCE_ZONE: Tz = chrono_tz::Europe::Warsaw;
+
+/// Zone a calendar is written in
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum OutputZone {
+  /// Warsaw wall-clock times without an offset, exactly as WPS publishes them
+  #[default]
+  Floating,
+  /// UTC times with a `Z` suffix
+  Utc,
+  /// Wall-clock times of an IANA zone, described by a `VTIMEZONE`
+  Zone(Tz),
+}
+
+impl OutputZone {
+  /// Parses `floating`, `utc` or an IANA zone name such as `America/New_York`,
+  /// ignoring case
+  #[must_use]
+  pub fn from_name(value: &str) -> Option<Self> {
+    let value = value.trim();
+    if value.eq_ignore_ascii_case("floating") {
+      return Some(Self::Floating);
+    }
+    if value.eq_ignore_ascii_case("utc") || value.eq_ignore_ascii_case("z") {
+      return Some(Self::Utc);
+    }
+    TZ_VARIANTS
+      .iter()
+      .find(|tz| tz.name().eq_ignore_ascii_case(value))
+      .map(|tz| Self::Zone(*tz))
+  }
+
+  /// IANA name of the zone times read in; floating times are Warsaw ones
+  #[must_use]
+  pub fn tz_name(self) -> &'static str {
+    match self {
+      Self::Floating => SOURCE_ZONE.name(),
+      Self::Utc => "UTC",
+      Self::Zone(tz) => tz.name(),
+    }
+  }
+
+  /// What a Warsaw time reads as on a clock in this zone
+  #[must_use]
+  pub fn wall_clock(self, local: NaiveDateTime) -> NaiveDateTime {
+    match self {
+      Self::Floating => local,
+      Self::Utc => instant(local).naive_utc(),
+      Self::Zone(tz) => instant(local).with_timezone(&tz).naive_local(),
+    }
+  }
+}
+
+impl fmt::Display for OutputZone {
+  fn fmt(&self, formatter: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Self::Floating => formatter.write_str("floating"),
+      zone => formatter.write_str(zone.tz_name()),
+    }
+  }
+}
+
+/// The instant a Warsaw wall-clock time refers to.
+///
+/// A time that occurs twice when clocks go back is taken the first time
+/// round. One skipped when they go forward is read with the offset in force
+/// before the change, so it lands an hour later, as a wall clock would show.
+#[must_use]
+pub fn instant(local: NaiveDateTime) -> DateTime<Utc> {
+  SOURCE_ZONE
+    .from_local_datetime(&local)
+    .earliest()
+    .map_or_else(
+      || {
+        let before = SOURCE_ZONE
+          .offset_from_utc_datetime(&(local - Duration::days(1)))
+          .fix();
+        Utc.from_utc_datetime(&(local - Duration::seconds(before.local_minus_utc().into())))
+      },
+      |time| time.with_timezone(&Utc),
+    )
+}
+
+/// `VTIMEZONE` of `tz` with the offset in force at `from` and every change
+/// up to `to`, so clients need no zone database of their own
+#[must_use]
+pub fn vtimezone(tz: Tz, from: DateTime<Utc>, to: DateTime<Utc>) -> String {
+  let mut out = format!("BEGIN:VTIMEZONE\r\nTZID:{}\r\n", tz.name());
+  let mut day = from - Duration::days(1);
+  let mut offset = tz.offset_from_utc_datetime(&day.naive_utc());
+  observance(&mut out, day, &offset, &offset);
+
+  // Zones change offset at most a few times a year, so a daily scan finds
+  // every change and a search within the day pins it to the second
+  while day <= to {
+    let next = day + Duration::days(1);
+    let next_offset = tz.offset_from_utc_datetime(&next.naive_utc());
+    if same_rules(&offset, &next_offset) {
+      day = next;
+      continue;
+    }
+
+    let (mut before, mut after) = (day, next);
+    while after - before > Duration::seconds(1) {
+      let middle = before + (after - before) / 2;
+      if same_rules(&offset, &tz.offset_from_utc_datetime(&middle.naive_utc())) {
+        before = middle;
+      } else {
+        after = middle;
+      }
+    }
+    let changed = tz.offset_from_utc_datetime(&after.naive_utc());
+    observance(&mut out, after, &offset, &changed);
+    offset = changed;
+    day = after;
+  }
+
+  out.push_str("END:VTIMEZONE\r\n");
+  out
+}
+
+fn same_rules(left: &TzOffset, right: &TzOffset) -> bool {
+  left.fix() == right.fix() && left.dst_offset() == right.dst_offset()
+}
+
+/// One `STANDARD` or `DAYLIGHT` block; its `DTSTART` is the wall-clock time
+/// the change happens at, read with the offset before it
+fn observance(out: &mut String, at: DateTime<Utc>, before: &TzOffset, after: &TzOffset) {
+  let kind = if after.dst_offset().is_zero() {
+    "STANDARD"
+  } else {
+    "DAYLIGHT"
+  };
+  let starts = at.with_timezone(&before.fix()).naive_local();
+  let _ = write!(
+    out,
+    "BEGIN:{kind}\r\nDTSTART:{}\r\nTZOFFSETFROM:{}\r\nTZOFFSETTO:{}\r\n",
+    starts.format("%Y%m%dT%H%M%S"),
+    utc_offset(before),
+    utc_offset(after)
+  );
+  if let Some(name) = after.abbreviation() {
+    let _ = write!(out, "TZNAME:{name}\r\n");
+  }
+  let _ = write!(out, "END:{kind}\r\n");
+}
+
+/// `+0100` style offset, with seconds only when there are some
+fn utc_offset(offset: &TzOffset) -> String {
+  let seconds = offset.fix().local_minus_utc();
+  let sign = if seconds < 0 { '-' } else { '+' };
+  let seconds = seconds.unsigned_abs();
+  let mut text = format!("{sign}{:02}{:02}", seconds / 3600, seconds / 60 % 60);
+  if !seconds.is_multiple_of(60) {
+    let _ = write!(text, "{:02}", seconds % 60);
+  }
+  text
+}
+
+#[cfg(test)]
+mod tests {
+  use chrono::NaiveDate;
+
+  use super::*;
+
+  fn warsaw(month: u32, day: u32, hour: u32, minute: u32) -> NaiveDateTime {
+    NaiveDate::from_ymd_opt(2026, month, day)
+      .expect("valid date")
+      .and_hms_opt(hour, minute, 0)
+      .expect("valid time")
+  }
+
+  #[test]
+  fn zone_names_are_parsed_without_regard_to_case() {
+    assert_eq!(OutputZone::from_name("UTC"), Some(OutputZone::Utc));
+    assert_eq!(
+      OutputZone::from_name(" floating "),
+      Some(OutputZone::Floating)
+    );
+    assert_eq!(
+      OutputZone::from_name("america/new_york"),
+      Some(OutputZone::Zone(chrono_tz::America::New_York))
+    );
+    assert_eq!(OutputZone::from_name("Mars/Olympus_Mons"), None);
+    assert_eq!(OutputZone::from_name(""), None);
+  }
+
+  #[test]
+  fn warsaw_times_follow_daylight_saving() {
+    assert_eq!(
+      OutputZone::Utc.wall_clock(warsaw(1, 15, 10, 0)),
+      warsaw(1, 15, 9, 0)
+    );
+    assert_eq!(
+      OutputZone::Utc.wall_clock(warsaw(7, 15, 10, 0)),
+      warsaw(7, 15, 8, 0)
+    );
+    // New York switches three weeks before Warsaw, so the gap is 5 hours
+    // there only in between
+    let new_york = OutputZone::Zone(chrono_tz::America::New_York);
+    assert_eq!(new_york.wall_clock(warsaw(3, 2, 15, 0)), warsaw(3, 2, 9, 0));
+    assert_eq!(
+      new_york.wall_clock(warsaw(3, 16, 15, 0)),
+      warsaw(3, 16, 10, 0)
+    );
+    assert_eq!(new_york.wall_clock(warsaw(4, 6, 15, 0)), warsaw(4, 6, 9, 0));
+    assert_eq!(
+      OutputZone::Floating.wall_clock(warsaw(3, 29, 2, 30)),
+      warsaw(3, 29, 2, 30)
+    );
+  }
+
+  #[test]
+  fn skipped_and_repeated_times_resolve_like_a_wall_clock() {
+    // 02:30 does not exist on 29 March; the clock reads 03:30 CEST instead
+    assert_eq!(instant(warsaw(3, 29, 2, 30)), instant(warsaw(3, 29, 3, 30)));
+    assert_eq!(
+      OutputZone::Utc.wall_clock(warsaw(3, 29, 2, 30)),
+      warsaw(3, 29, 1, 30)
+    );
+    // 02:30 happens twice on 25 October; the first one is still CEST
+    assert_eq!(
+      OutputZone::Utc.wall_clock(warsaw(10, 25, 2, 30)),
+      warsaw(10, 25, 0, 30)
+    );
+  }
+
+  #[test]
+  fn vtimezone_lists_each_offset_change_in_the_window() {
+    let from = instant(warsaw(1, 10, 0, 0));
+    let to = instant(warsaw(12, 10, 0, 0));
+    let text = vtimezone(SOURCE_ZONE, from, to);
+
+    assert!(text.starts_with("BEGIN:VTIMEZONE\r\nTZID:Europe/Warsaw\r\n"));
+    assert!(text.ends_with("END:VTIMEZONE\r\n"));
+    assert_eq!(text.matches("BEGIN:STANDARD").count(), 2);
+    assert_eq!(text.matches("BEGIN:DAYLIGHT").count(), 1);
+    assert!(text.contains(
+      "BEGIN:DAYLIGHT\r\nDTSTART:20260329T020000\r\nTZOFFSETFROM:+0100\r\nTZOFFSETTO:+0200\r\nTZNAME:CEST\r\n"
+    ));
+    assert!(text.contains(
+      "BEGIN:STANDARD\r\nDTSTART:20261025T030000\r\nTZOFFSETFROM:+0200\r\nTZOFFSETTO:+0100\r\nTZNAME:CET\r\n"
+    ));
+
+    let kolkata = vtimezone(chrono_tz::Asia::Kolkata, from, to);
+    assert!(kolkata.contains("TZOFFSETTO:+0530"));
+    assert_eq!(kolkata.matches("BEGIN:STANDARD").count(), 1);
+  }
+}
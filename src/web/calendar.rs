@@ -1,23 +1,54 @@
 use std::fmt;
+use std::io;
 use std::net::SocketAddr;
+use std::sync::Arc;
 
+use axum::body::Bytes;
 use axum::http::HeaderMap;
-use chrono::{Duration, NaiveDate};
+use chrono::{Duration, NaiveDate, Utc};
 use tracing::{debug, info, warn};
 
+use crate::api::ExamFetch;
 use crate::app::AppState;
-use crate::cache::{IcsCacheKey, credential_key};
-use crate::config::ServerSettings;
-use crate::ics::{calendar_id, render_calendar};
-use crate::models::{ExamEvent, PlanItem};
-use crate::web::AppError;
+use crate::cache::{CachedCalendar, FeedFormat, FeedVersion, IcsCacheKey, credential_key};
+use crate::compression::ContentEncoding;
+use crate::config::{CalendarLanguage, CalendarToken, ServerSettings};
+use crate::formats::Component;
+use crate::formats::jcal::to_jcal;
+use crate::formats::xcal::to_xcal;
+use crate::i18n::ErrorTexts;
+use crate::ics::{RenderOptions, Wording, calendar_id, render_calendar};
+use crate::merge::merge_adjacent;
+use crate::models::{ExamEvent, ExamIndex, PlanItem};
+use crate::timezone::{OutputZone, SOURCE_ZONE};
+use crate::web::dto::CalendarJsonResponse;
+use crate::web::language::negotiate_language;
 use crate::web::real_ip::resolve_client_ip;
+use crate::web::{AppError, ErrorMessage, JCAL_CONTENT_TYPE, XCAL_CONTENT_TYPE};
 
 #[derive(Debug)]
 pub(crate) struct CalendarQueryParams {
   pub(crate) from: Option<NaiveDate>,
   pub(crate) to: Option<NaiveDate>,
   pub(crate) token: Option<String>,
+  /// Negotiated with [`request_language`]
+  pub(crate) lang: CalendarLanguage,
+  /// Output zone name; the configured one when absent
+  pub(crate) tz: Option<String>,
+}
+
+/// Feed serialized per request, plus the validators for conditional requests
+#[derive(Debug)]
+pub(crate) struct RenderedFeed {
+  pub(crate) body: Bytes,
+  pub(crate) version: FeedVersion,
+}
+
+impl RenderedFeed {
+  /// Only ICS bodies are cached, so these are compressed per response.
+  pub(crate) fn compressed(&self, encoding: ContentEncoding) -> io::Result<Bytes> {
+    encoding.compress(&self.body).map(Bytes::from)
+  }
 }
 
 #[derive(Debug)]
@@ -28,6 +59,9 @@ pub(crate) struct CalendarRenderData {
   pub(crate) to: NaiveDate,
   pub(crate) plan: Vec<PlanItem>,
   pub(crate) exams: Vec<ExamEvent>,
+  /// False when exams were skipped after a failed fetch, so their absence
+  /// says nothing about cancellations
+  pub(crate) exams_complete: bool,
 }
 
 struct CalendarRequestContext {
@@ -35,10 +69,23 @@ struct CalendarRequestContext {
   student_id: i64,
   /// Opaque per-calendar id used in event UIDs, never the raw student id.
   calendar_id: String,
-  index_id: Option<i64>,
-  section_name: Option<String>,
+  exam_indexes: Vec<ExamIndex>,
   from: NaiveDate,
   to: NaiveDate,
+  lang: CalendarLanguage,
+  zone: OutputZone,
+}
+
+impl CalendarRequestContext {
+  fn cache_key(&self) -> IcsCacheKey {
+    IcsCacheKey {
+      student_id: self.student_id,
+      from: self.from,
+      to: self.to,
+      lang: self.lang,
+      zone: self.zone,
+    }
+  }
 }
 
 impl fmt::Debug for CalendarRequestContext {
@@ -48,10 +95,11 @@ impl fmt::Debug for CalendarRequestContext {
       .field("token", &"<redacted>")
       .field("student_id", &self.student_id)
       .field("calendar_id", &self.calendar_id)
-      .field("index_id", &self.index_id)
-      .field("section_name", &self.section_name)
+      .field("exam_indexes", &self.exam_indexes)
       .field("from", &self.from)
       .field("to", &self.to)
+      .field("lang", &self.lang)
+      .field("zone", &self.zone)
       .finish()
   }
 }
@@ -61,48 +109,154 @@ pub(crate) async fn render_calendar_ics<C: ServerSettings>(
   username: &str,
   password: &str,
   query: CalendarQueryParams,
-  headers: HeaderMap,
+  headers: &HeaderMap,
   addr: SocketAddr,
-) -> Result<String, AppError> {
+) -> Result<CachedCalendar, AppError> {
   let context =
     prepare_calendar_request_context(&state, username, password, query, headers, addr).await?;
+  cached_calendar(&state, &context).await
+}
 
-  let key = IcsCacheKey {
-    student_id: context.student_id,
-    from: context.from,
-    to: context.to,
-  };
+/// The same calendar as the ICS feed in RFC 7265 jCal or RFC 6321 xCal.
+///
+/// Converted from the cached ICS body rather than rendered again, so both
+/// carry exactly what a subscriber to the `.ics` feed sees.
+pub(crate) async fn render_calendar_converted<C: ServerSettings>(
+  state: AppState<C>,
+  username: &str,
+  password: &str,
+  query: CalendarQueryParams,
+  headers: &HeaderMap,
+  addr: SocketAddr,
+  format: ConvertedFormat,
+) -> Result<RenderedFeed, AppError> {
+  let context =
+    prepare_calendar_request_context(&state, username, password, query, headers, addr).await?;
+  let calendar = cached_calendar(&state, &context).await?;
+  let body = format.convert(&calendar.body)?;
+  let version = state
+    .ics_cache
+    .track_version(context.cache_key(), format.feed_format(), &body)
+    .await;
+
+  Ok(RenderedFeed {
+    body: Bytes::from(body),
+    version,
+  })
+}
 
-  if let Some(cached) = state.ics_cache.get(&key).await {
-    debug!("ics cache hit");
-    return Ok(cached);
+/// Alternative representations of the ICS feed
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ConvertedFormat {
+  Jcal,
+  Xcal,
+}
+
+impl ConvertedFormat {
+  pub(crate) fn content_type(self) -> &'static str {
+    match self {
+      Self::Jcal => JCAL_CONTENT_TYPE,
+      Self::Xcal => XCAL_CONTENT_TYPE,
+    }
   }
 
-  debug!("ics cache miss");
-  let data = fetch_calendar_render_data(&state, &context).await?;
-  let ics = render_calendar(
-    &data.calendar_id,
-    &data.plan,
-    &data.exams,
-    state.config.calendar_lang(),
-  )?;
+  fn feed_format(self) -> FeedFormat {
+    match self {
+      Self::Jcal => FeedFormat::Jcal,
+      Self::Xcal => FeedFormat::Xcal,
+    }
+  }
 
-  state.ics_cache.insert(key, ics.clone()).await;
+  fn convert(self, ics: &[u8]) -> anyhow::Result<Vec<u8>> {
+    let calendar = Component::parse(std::str::from_utf8(ics)?)?;
+    Ok(match self {
+      Self::Jcal => serde_json::to_vec(&to_jcal(&calendar))?,
+      Self::Xcal => to_xcal(&calendar).into_bytes(),
+    })
+  }
+}
 
-  Ok(ics)
+async fn cached_calendar<C: ServerSettings>(
+  state: &AppState<C>,
+  context: &CalendarRequestContext,
+) -> Result<CachedCalendar, AppError> {
+  // Concurrent misses for the same key share one upstream fetch
+  state
+    .ics_cache
+    .get_or_render(context.cache_key(), async {
+      debug!("ics cache miss");
+      let data = fetch_calendar_render_data(state, context).await?;
+      // Merged before history and rendering, so a merged class is tracked
+      // under the same UID it is served with
+      let plan = match state.config.merge_gap_minutes() {
+        Some(minutes) => merge_adjacent(data.plan, Duration::minutes(minutes)),
+        None => data.plan,
+      };
+      let revisions = state
+        .event_history
+        .observe(
+          &data.calendar_id,
+          &plan,
+          &data.exams,
+          data.exams_complete,
+          (data.from, data.to),
+          state.config.compress_series(),
+        )
+        .await;
+      let ics = render_calendar(
+        &data.calendar_id,
+        &plan,
+        &data.exams,
+        &revisions,
+        &render_options(&state.config, context.lang, context.zone),
+      )?;
+      Ok(ics)
+    })
+    .await
+    .map_err(Arc::unwrap_or_clone)
 }
 
-pub(crate) async fn fetch_calendar_data<C: ServerSettings>(
+pub(crate) async fn render_calendar_json<C: ServerSettings>(
   state: AppState<C>,
   username: &str,
   password: &str,
   query: CalendarQueryParams,
-  headers: HeaderMap,
+  headers: &HeaderMap,
   addr: SocketAddr,
-) -> Result<CalendarRenderData, AppError> {
+) -> Result<RenderedFeed, AppError> {
   let context =
     prepare_calendar_request_context(&state, username, password, query, headers, addr).await?;
-  fetch_calendar_render_data(&state, &context).await
+  let data = fetch_calendar_render_data(&state, &context).await?;
+  let key = IcsCacheKey {
+    student_id: data.student_id,
+    from: data.from,
+    to: data.to,
+    lang: context.lang,
+    zone: context.zone,
+  };
+  let body = serde_json::to_vec(&CalendarJsonResponse::from_parts(
+    data.student_id,
+    data.from,
+    data.to,
+    data.plan,
+    data.exams,
+    &Wording::new(
+      context.lang,
+      context.zone,
+      state.config.catalogs(),
+      state.config.templates(),
+    ),
+  ))
+  .map_err(anyhow::Error::from)?;
+  let version = state
+    .ics_cache
+    .track_version(key, FeedFormat::Json, &body)
+    .await;
+
+  Ok(RenderedFeed {
+    body: Bytes::from(body),
+    version,
+  })
 }
 
 async fn prepare_calendar_request_context<C: ServerSettings>(
@@ -110,11 +264,11 @@ async fn prepare_calendar_request_context<C: ServerSettings>(
   username: &str,
   password: &str,
   query: CalendarQueryParams,
-  headers: HeaderMap,
+  headers: &HeaderMap,
   addr: SocketAddr,
 ) -> Result<CalendarRequestContext, AppError> {
   let peer_ip = addr.ip();
-  let resolved_ip = resolve_client_ip(peer_ip, &headers, state.config.real_ip_header());
+  let resolved_ip = resolve_client_ip(peer_ip, headers, state.config.real_ip_header());
   if matches!(
     resolved_ip.source,
     crate::web::real_ip::ClientIpSource::HeaderInvalid
@@ -123,19 +277,15 @@ async fn prepare_calendar_request_context<C: ServerSettings>(
   }
   info!(ip = %resolved_ip.ip, "calendar request");
 
-  if let Some(expected) = state.config.calendar_token() {
-    let provided = extract_token(&query, &headers);
-    let is_valid = provided
-      .as_deref()
-      .is_some_and(|value| expected.verify(value));
-
-    if !is_valid {
-      warn!(ip = %resolved_ip.ip, "unauthorized: invalid calendar token");
-      return Err(AppError::unauthorized("invalid calendar token"));
-    }
+  if let Some(expected) = state.config.calendar_token()
+    && !token_matches(&expected, query.token.clone(), headers)
+  {
+    warn!(ip = %resolved_ip.ip, "unauthorized: invalid calendar token");
+    return Err(AppError::unauthorized(ErrorMessage::InvalidToken));
   }
 
-  let today = chrono::Local::now().date_naive();
+  // Warsaw's date, as the exam terms use, whatever zone the server runs in
+  let today = Utc::now().with_timezone(&SOURCE_ZONE).date_naive();
   let from = query
     .from
     .unwrap_or_else(|| today - Duration::days(state.config.calendar_past_days()));
@@ -144,7 +294,20 @@ async fn prepare_calendar_request_context<C: ServerSettings>(
     .unwrap_or_else(|| today + Duration::days(state.config.calendar_future_days()));
 
   if to < from {
-    return Err(AppError::bad_request("to must be >= from"));
+    return Err(AppError::bad_request(ErrorMessage::InvalidRange));
+  }
+  let zone = match query.tz.as_deref() {
+    Some(name) => OutputZone::from_name(name)
+      .ok_or_else(|| AppError::bad_request(ErrorMessage::InvalidTimezone))?,
+    None => state.config.calendar_timezone(),
+  };
+  // Every distinct window is its own cache entry, so unbounded spans would let
+  // one caller pin arbitrarily large calendars in memory
+  let max_days = state.config.calendar_max_range_days();
+  if exceeds_max_range(from, to, max_days) {
+    return Err(AppError::bad_request(ErrorMessage::RangeTooLong {
+      max_days,
+    }));
   }
 
   let token = match state
@@ -164,6 +327,7 @@ async fn prepare_calendar_request_context<C: ServerSettings>(
       username,
       password,
       state.config.exams_enabled(),
+      state.config.exam_indexes(),
       &state.api,
       &token,
     )
@@ -176,10 +340,11 @@ async fn prepare_calendar_request_context<C: ServerSettings>(
       student_context.student_id,
     ),
     student_id: student_context.student_id,
-    index_id: student_context.index_id,
-    section_name: student_context.section_name,
+    exam_indexes: student_context.exam_indexes,
     from,
     to,
+    lang: query.lang,
+    zone,
   })
 }
 
@@ -187,46 +352,62 @@ async fn fetch_calendar_render_data<C: ServerSettings>(
   state: &AppState<C>,
   context: &CalendarRequestContext,
 ) -> Result<CalendarRenderData, AppError> {
-  let date_from = context.from.format("%Y-%m-%d").to_string();
-  let date_to = context.to.format("%Y-%m-%d").to_string();
   let plan = state
     .api
-    .get_plan(&context.token, context.student_id, &date_from, &date_to)
-    .await?;
-  let exams = if state.config.exams_enabled() {
-    if let Some(index_id) = context.index_id {
-      match state
-        .api
-        .get_exams(
-          &context.token,
-          index_id,
-          context.section_name.as_deref(),
-          context.from,
-          context.to,
-        )
-        .await
-      {
-        Ok(items) => items,
-        Err(error) => {
+    .get_plan(&context.token, context.student_id, context.from, context.to);
+  let exams = async {
+    if !state.config.exams_enabled() {
+      info!("exam fetching disabled by AHE_CAL_EXAMS_ENABLED");
+      return ExamFetch {
+        events: Vec::new(),
+        complete: true,
+      };
+    }
+    if context.exam_indexes.is_empty() {
+      warn!(
+        context.student_id,
+        "IndeksID not found in student data, skipping exams"
+      );
+      return ExamFetch {
+        events: Vec::new(),
+        complete: true,
+      };
+    }
+
+    match state
+      .api
+      .get_exams(
+        &context.token,
+        &context.exam_indexes,
+        context.from,
+        context.to,
+        state.config.exam_time_defaults(),
+      )
+      .await
+    {
+      Ok(fetch) => {
+        if !fetch.complete {
           warn!(
             context.student_id,
-            error = %error,
-            "failed to fetch exams, continuing with schedule only"
+            "some exam lookups failed, keeping missing exams as they were"
           );
-          Vec::new()
         }
+        fetch
+      }
+      Err(error) => {
+        warn!(
+          context.student_id,
+          error = %error,
+          "failed to fetch exams, continuing with schedule only"
+        );
+        ExamFetch::default()
       }
-    } else {
-      warn!(
-        context.student_id,
-        "IndeksID not found in student data, skipping exams"
-      );
-      Vec::new()
     }
-  } else {
-    info!("exam fetching disabled by AHE_CAL_EXAMS_ENABLED");
-    Vec::new()
   };
+  // The plan and the exam pipeline hit unrelated endpoints, so run them side by side
+  let (plan, exams) = tokio::join!(plan, exams);
+  let mut plan = plan?;
+  state.config.locations().annotate(&mut plan);
 
   Ok(CalendarRenderData {
     student_id: context.student_id,
@@ -234,11 +415,63 @@ async fn fetch_calendar_render_data<C: ServerSettings>(
     from: context.from,
     to: context.to,
     plan,
-    exams,
+    exams: exams.events,
+    exams_complete: exams.complete,
   })
 }
 
-fn extract_token(query: &CalendarQueryParams, headers: &HeaderMap) -> Option<String> {
+fn render_options<C: ServerSettings>(
+  config: &C,
+  lang: CalendarLanguage,
+  zone: OutputZone,
+) -> RenderOptions<'_> {
+  RenderOptions {
+    lang,
+    unconfirmed_exams: config.exam_unconfirmed(),
+    compress_series: config.compress_series(),
+    zone,
+    catalogs: config.catalogs(),
+    templates: config.templates(),
+  }
+}
+
+/// Language of a calendar request; see [`negotiate_language`]
+pub(crate) fn request_language<C: ServerSettings>(
+  config: &C,
+  requested: Option<&str>,
+  headers: &HeaderMap,
+) -> CalendarLanguage {
+  negotiate_language(
+    requested,
+    headers,
+    config.catalogs(),
+    config.calendar_lang(),
+  )
+}
+
+/// Error wording in the language of the request
+pub(crate) fn error_texts<C: ServerSettings>(config: &C, lang: CalendarLanguage) -> ErrorTexts {
+  config.catalogs().get(lang).errors.clone()
+}
+
+/// Whether `from`..=`to` covers more than `max_days` days, counting both ends;
+/// zero disables the limit
+fn exceeds_max_range(from: NaiveDate, to: NaiveDate, max_days: i64) -> bool {
+  max_days > 0 && (to - from).num_days() + 1 > max_days
+}
+
+/// Whether the request carries the expected token in the query or a header
+pub(crate) fn token_matches(
+  expected: &CalendarToken,
+  query_token: Option<String>,
+  headers: &HeaderMap,
+) -> bool {
+  extract_token(query_token, headers)
+    .as_deref()
+    .is_some_and(|value| expected.verify(value))
+}
+
+fn extract_token(query_token: Option<String>, headers: &HeaderMap) -> Option<String> {
   let header_token = headers
     .get("x-calendar-token")
     .and_then(|value| value.to_str().ok())
@@ -250,5 +483,22 @@ fn extract_token(query: &CalendarQueryParams, headers: &HeaderMap) -> Option<Str
     .and_then(|value| value.strip_prefix("Bearer "))
     .map(str::to_string);
 
-  query.token.clone().or(header_token).or(bearer_token)
+  query_token.or(header_token).or(bearer_token)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn day(value: &str) -> NaiveDate {
+    value.parse().unwrap()
+  }
+
+  #[test]
+  fn max_range_counts_both_ends() {
+    assert!(!exceeds_max_range(day("2026-01-01"), day("2026-01-01"), 1));
+    assert!(!exceeds_max_range(day("2026-01-01"), day("2026-01-31"), 31));
+    assert!(exceeds_max_range(day("2026-01-01"), day("2026-02-01"), 31));
+    assert!(!exceeds_max_range(day("2026-01-01"), day("2027-01-01"), 0));
+  }
 }
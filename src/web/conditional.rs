@@ -0,0 +1,276 @@
+use std::io;
+
+use axum::body::Bytes;
+use axum::http::header::{
+  CACHE_CONTROL, CONTENT_ENCODING, CONTENT_TYPE, ETAG, IF_MODIFIED_SINCE, IF_NONE_MATCH,
+  LAST_MODIFIED, VARY,
+};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use tokio::task::spawn_blocking;
+use tracing::warn;
+
+use crate::cache::FeedVersion;
+use crate::compression::ContentEncoding;
+use crate::web::encoding::negotiate_encoding;
+
+/// Feeds are per-user, so shared caches must not store them
+const FEED_CACHE_CONTROL: &str = "private, max-age=600";
+
+/// Answers with `304 Not Modified` when the client already holds `version`,
+/// otherwise with the body in the best coding the client accepts; both carry
+/// the feed validators.
+///
+/// `compress` is called with the negotiated coding only when a compressed body
+/// is actually sent. It runs on the blocking pool, as a large feed takes long
+/// enough to stall other requests on the executor.
+pub(crate) async fn feed_response(
+  request_headers: &HeaderMap,
+  content_type: &'static str,
+  version: &FeedVersion,
+  body: Bytes,
+  compress: impl FnOnce(ContentEncoding) -> io::Result<Bytes> + Send + 'static,
+) -> Response {
+  let mut encoding = negotiate_encoding(request_headers);
+  let negotiated_etag = representation_etag(&version.etag, encoding);
+
+  let mut response = if is_not_modified(request_headers, &negotiated_etag, version) {
+    StatusCode::NOT_MODIFIED.into_response()
+  } else {
+    let bytes = match encoding {
+      Some(coding) => spawn_blocking(move || compress(coding))
+        .await
+        .unwrap_or_else(|error| Err(io::Error::other(error)))
+        .unwrap_or_else(|error| {
+          warn!(error = %error, "response compression failed, sending identity");
+          encoding = None;
+          body
+        }),
+      None => body,
+    };
+    let mut response = ([(CONTENT_TYPE, content_type)], bytes).into_response();
+    if let Some(encoding) = encoding {
+      response.headers_mut().insert(
+        CONTENT_ENCODING,
+        HeaderValue::from_static(encoding.as_str()),
+      );
+    }
+    response
+  };
+
+  // Recomputed in case compression fell back to identity
+  let etag = representation_etag(&version.etag, encoding);
+  let headers = response.headers_mut();
+  if let Ok(etag) = HeaderValue::from_str(&etag) {
+    headers.insert(ETAG, etag);
+  }
+  if let Ok(last_modified) = HeaderValue::from_str(&http_date(version.last_modified)) {
+    headers.insert(LAST_MODIFIED, last_modified);
+  }
+  headers.insert(CACHE_CONTROL, HeaderValue::from_static(FEED_CACHE_CONTROL));
+  headers.insert(
+    VARY,
+    HeaderValue::from_static("accept-encoding, accept-language"),
+  );
+
+  response
+}
+
+/// Each content coding is its own representation, so it needs its own strong tag
+fn representation_etag(etag: &str, encoding: Option<ContentEncoding>) -> String {
+  match (encoding, etag.strip_suffix('"')) {
+    (Some(encoding), Some(opaque)) => format!("{opaque}-{}\"", encoding.as_str()),
+    _ => etag.to_string(),
+  }
+}
+
+/// RFC 9110 precedence: `If-None-Match` wins, `If-Modified-Since` is only
+/// consulted when it is absent.
+fn is_not_modified(headers: &HeaderMap, etag: &str, version: &FeedVersion) -> bool {
+  if let Some(value) = headers.get(IF_NONE_MATCH) {
+    return value
+      .to_str()
+      .is_ok_and(|value| etag_list_matches(value, etag));
+  }
+
+  headers
+    .get(IF_MODIFIED_SINCE)
+    .and_then(|value| value.to_str().ok())
+    .and_then(|value| DateTime::parse_from_rfc2822(value.trim()).ok())
+    .is_some_and(|since| version.last_modified <= since)
+}
+
+/// Weak comparison, as required for `If-None-Match`
+fn etag_list_matches(list: &str, etag: &str) -> bool {
+  let etag = etag.trim_start_matches("W/");
+  list
+    .split(',')
+    .map(str::trim)
+    .any(|candidate| candidate == "*" || candidate.trim_start_matches("W/") == etag)
+}
+
+fn http_date(value: DateTime<Utc>) -> String {
+  value.format("%a, %d %b %Y %H:%M:%S GMT").to_string()
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderName;
+  use axum::http::header::ACCEPT_ENCODING;
+  use chrono::TimeZone;
+
+  use super::*;
+
+  const ETAG_VALUE: &str = "\"0123456789abcdef0123456789abcdef\"";
+
+  fn version() -> FeedVersion {
+    FeedVersion {
+      etag: ETAG_VALUE.to_string(),
+      last_modified: Utc
+        .with_ymd_and_hms(2026, 1, 15, 8, 30, 0)
+        .single()
+        .expect("valid timestamp"),
+    }
+  }
+
+  fn headers(pairs: &[(HeaderName, &str)]) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    for (name, value) in pairs {
+      headers.insert(name, HeaderValue::from_str(value).expect("valid value"));
+    }
+    headers
+  }
+
+  #[test]
+  fn http_date_uses_imf_fixdate() {
+    assert_eq!(
+      http_date(version().last_modified),
+      "Thu, 15 Jan 2026 08:30:00 GMT"
+    );
+  }
+
+  fn identity_body() -> Bytes {
+    Bytes::from_static(b"BEGIN:VCALENDAR")
+  }
+
+  fn not_modified(request: &HeaderMap) -> bool {
+    is_not_modified(request, ETAG_VALUE, &version())
+  }
+
+  #[test]
+  fn matching_etag_is_not_modified() {
+    assert!(not_modified(&headers(&[(IF_NONE_MATCH, ETAG_VALUE)])));
+    assert!(not_modified(&headers(&[(
+      IF_NONE_MATCH,
+      &format!("\"other\", W/{ETAG_VALUE}")
+    )])));
+    assert!(not_modified(&headers(&[(IF_NONE_MATCH, "*")])));
+  }
+
+  #[test]
+  fn encoded_representations_get_distinct_etags() {
+    assert_eq!(representation_etag(ETAG_VALUE, None), ETAG_VALUE);
+    assert_eq!(
+      representation_etag(ETAG_VALUE, Some(ContentEncoding::Brotli)),
+      "\"0123456789abcdef0123456789abcdef-br\""
+    );
+  }
+
+  #[test]
+  fn foreign_etag_is_modified_even_with_a_recent_date() {
+    let request = headers(&[
+      (IF_NONE_MATCH, "\"stale\""),
+      (IF_MODIFIED_SINCE, "Fri, 16 Jan 2026 00:00:00 GMT"),
+    ]);
+
+    assert!(!not_modified(&request));
+  }
+
+  #[test]
+  fn modified_since_compares_against_last_change() {
+    let same = headers(&[(IF_MODIFIED_SINCE, "Thu, 15 Jan 2026 08:30:00 GMT")]);
+    let earlier = headers(&[(IF_MODIFIED_SINCE, "Thu, 15 Jan 2026 08:29:59 GMT")]);
+    let garbage = headers(&[(IF_MODIFIED_SINCE, "yesterday")]);
+
+    assert!(not_modified(&same));
+    assert!(!not_modified(&earlier));
+    assert!(!not_modified(&garbage));
+    assert!(!not_modified(&HeaderMap::new()));
+  }
+
+  #[tokio::test]
+  async fn not_modified_response_has_no_body_but_keeps_validators() {
+    let response = feed_response(
+      &headers(&[(IF_NONE_MATCH, ETAG_VALUE)]),
+      "text/calendar; charset=utf-8",
+      &version(),
+      identity_body(),
+      |_| panic!("a 304 must not compress the body"),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(response.headers()[ETAG], ETAG_VALUE);
+    assert_eq!(
+      response.headers()[LAST_MODIFIED],
+      "Thu, 15 Jan 2026 08:30:00 GMT"
+    );
+    assert!(response.headers().get(CONTENT_TYPE).is_none());
+  }
+
+  #[tokio::test]
+  async fn fresh_response_carries_cache_headers() {
+    let response = feed_response(
+      &HeaderMap::new(),
+      "text/calendar; charset=utf-8",
+      &version(),
+      identity_body(),
+      |_| panic!("identity must not be compressed"),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[CACHE_CONTROL], FEED_CACHE_CONTROL);
+    assert_eq!(response.headers()[ETAG], ETAG_VALUE);
+    assert_eq!(response.headers()[VARY], "accept-encoding, accept-language");
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+  }
+
+  #[tokio::test]
+  async fn negotiated_coding_is_announced() {
+    let response = feed_response(
+      &headers(&[(ACCEPT_ENCODING, "gzip")]),
+      "text/calendar; charset=utf-8",
+      &version(),
+      identity_body(),
+      |encoding| {
+        assert_eq!(encoding, ContentEncoding::Gzip);
+        Ok(Bytes::from_static(b"compressed"))
+      },
+    )
+    .await;
+
+    assert_eq!(response.headers()[CONTENT_ENCODING], "gzip");
+    assert_eq!(
+      response.headers()[ETAG],
+      "\"0123456789abcdef0123456789abcdef-gzip\""
+    );
+  }
+
+  #[tokio::test]
+  async fn failed_compression_falls_back_to_identity() {
+    let response = feed_response(
+      &headers(&[(ACCEPT_ENCODING, "br")]),
+      "text/calendar; charset=utf-8",
+      &version(),
+      identity_body(),
+      |_| Err(io::Error::other("encoder broke")),
+    )
+    .await;
+
+    assert_eq!(response.status(), StatusCode::OK);
+    assert!(response.headers().get(CONTENT_ENCODING).is_none());
+    assert_eq!(response.headers()[ETAG], ETAG_VALUE);
+  }
+}
@@ -1,13 +1,17 @@
-use chrono::{NaiveDate, NaiveDateTime};
+use chrono::{DateTime, FixedOffset, NaiveDate, NaiveDateTime, Utc};
 use serde::Serialize;
 
-use crate::models::{ExamEvent, PlanItem};
+use crate::ics::{Wording, class_description, class_summary, exam_description, exam_summary};
+use crate::models::{ExamEvent, Place, PlanItem};
+use crate::timezone::{self, OutputZone};
 
 #[derive(Debug, Serialize)]
 pub(crate) struct CalendarJsonResponse {
   student_id: i64,
   from: NaiveDate,
   to: NaiveDate,
+  /// IANA zone of the event times; floating times are Warsaw ones
+  timezone: &'static str,
   plan: Vec<CalendarPlanJsonItem>,
   exams: Vec<CalendarExamJsonItem>,
 }
@@ -15,8 +19,11 @@ pub(crate) struct CalendarJsonResponse {
 #[derive(Debug, Serialize)]
 struct CalendarPlanJsonItem {
   schedule_item_id: i64,
-  starts_at: NaiveDateTime,
-  ends_at: NaiveDateTime,
+  /// Event title and notes as the ICS feed shows them
+  summary: String,
+  description: String,
+  starts_at: JsonTime,
+  ends_at: JsonTime,
   subject_name: String,
   class_type: String,
   class_type_short: String,
@@ -25,19 +32,62 @@ struct CalendarPlanJsonItem {
   webinar: bool,
   instructors: Vec<String>,
   form_color: Option<String>,
+  meeting_url: Option<String>,
+  /// Building from the address table, with its coordinates
+  place: Option<CalendarPlaceJson>,
+}
+
+#[derive(Debug, Serialize)]
+struct CalendarPlaceJson {
+  name: String,
+  address: String,
+  latitude: f64,
+  longitude: f64,
 }
 
 #[derive(Debug, Serialize)]
 struct CalendarExamJsonItem {
   published_data_id: i64,
+  /// Event title and notes as the ICS feed shows them
+  summary: String,
+  description: String,
   subject: String,
   notes: Option<String>,
   location: Option<String>,
   lecturer: Option<String>,
   details: Option<String>,
-  starts: NaiveDateTime,
-  ends: NaiveDateTime,
+  starts: JsonTime,
+  ends: JsonTime,
   is_retake: bool,
+  /// False when `starts` is a guess because WPS has not published the time
+  time_confirmed: bool,
+  end_estimated: bool,
+  /// Only filled in for students with several indexes
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  programmes: Vec<String>,
+  meeting_url: Option<String>,
+}
+
+/// A WPS time in the requested zone: without an offset when floating, with
+/// one (or `Z`) otherwise
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+enum JsonTime {
+  Floating(NaiveDateTime),
+  Utc(DateTime<Utc>),
+  Zoned(DateTime<FixedOffset>),
+}
+
+impl JsonTime {
+  fn new(value: NaiveDateTime, zone: OutputZone) -> Self {
+    match zone {
+      OutputZone::Floating => Self::Floating(value),
+      OutputZone::Utc => Self::Utc(timezone::instant(value)),
+      OutputZone::Zone(tz) => {
+        Self::Zoned(timezone::instant(value).with_timezone(&tz).fixed_offset())
+      }
+    }
+  }
 }
 
 impl CalendarJsonResponse {
@@ -47,23 +97,33 @@ impl CalendarJsonResponse {
     to: NaiveDate,
     plan: Vec<PlanItem>,
     exams: Vec<ExamEvent>,
+    wording: &Wording,
   ) -> Self {
     Self {
       student_id,
       from,
       to,
-      plan: plan.into_iter().map(Into::into).collect(),
-      exams: exams.into_iter().map(Into::into).collect(),
+      timezone: wording.zone().tz_name(),
+      plan: plan
+        .into_iter()
+        .map(|item| CalendarPlanJsonItem::new(item, wording))
+        .collect(),
+      exams: exams
+        .into_iter()
+        .map(|exam| CalendarExamJsonItem::new(exam, wording))
+        .collect(),
     }
   }
 }
 
-impl From<PlanItem> for CalendarPlanJsonItem {
-  fn from(value: PlanItem) -> Self {
+impl CalendarPlanJsonItem {
+  fn new(value: PlanItem, wording: &Wording) -> Self {
     Self {
+      summary: class_summary(&value, wording),
+      description: class_description(&value, wording),
       schedule_item_id: value.schedule_item_id,
-      starts_at: value.starts_at,
-      ends_at: value.ends_at,
+      starts_at: JsonTime::new(value.starts_at, wording.zone()),
+      ends_at: JsonTime::new(value.ends_at, wording.zone()),
       subject_name: value.subject_name,
       class_type: value.class_type,
       class_type_short: value.class_type_short,
@@ -72,22 +132,41 @@ impl From<PlanItem> for CalendarPlanJsonItem {
       webinar: value.webinar,
       instructors: value.instructors.into_iter().map(|i| i.full_name).collect(),
       form_color: value.form_color,
+      meeting_url: value.meeting_url,
+      place: value.place.map(Into::into),
+    }
+  }
+}
+
+impl From<Place> for CalendarPlaceJson {
+  fn from(value: Place) -> Self {
+    Self {
+      name: value.name,
+      address: value.address,
+      latitude: value.latitude,
+      longitude: value.longitude,
     }
   }
 }
 
-impl From<ExamEvent> for CalendarExamJsonItem {
-  fn from(value: ExamEvent) -> Self {
+impl CalendarExamJsonItem {
+  fn new(value: ExamEvent, wording: &Wording) -> Self {
     Self {
+      summary: exam_summary(&value, wording),
+      description: exam_description(&value, wording),
       published_data_id: value.published_data_id,
       subject: value.subject,
       notes: value.notes,
       location: value.location,
       lecturer: value.lecturer,
       details: value.details,
-      starts: value.starts,
-      ends: value.ends,
+      starts: JsonTime::new(value.starts, wording.zone()),
+      ends: JsonTime::new(value.ends, wording.zone()),
       is_retake: value.is_retake,
+      time_confirmed: value.time_confirmed,
+      end_estimated: value.end_estimated,
+      programmes: value.programmes,
+      meeting_url: value.meeting_url,
     }
   }
 }
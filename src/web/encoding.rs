@@ -0,0 +1,159 @@
+use axum::http::HeaderMap;
+use axum::http::header::ACCEPT_ENCODING;
+
+use crate::compression::ContentEncoding;
+
+/// Tie-break order when the client weighs several codings equally
+const PREFERENCE: [ContentEncoding; 3] = [
+  ContentEncoding::Brotli,
+  ContentEncoding::Zstd,
+  ContentEncoding::Gzip,
+];
+
+/// Picks the best content coding the client accepts, or `None` for identity.
+pub(crate) fn negotiate_encoding(headers: &HeaderMap) -> Option<ContentEncoding> {
+  let accept = headers
+    .get_all(ACCEPT_ENCODING)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .collect::<Vec<_>>()
+    .join(",");
+
+  let mut best: Option<(ContentEncoding, u16)> = None;
+  for encoding in PREFERENCE {
+    let weight = coding_weight(&accept, encoding);
+    if weight > 0 && best.is_none_or(|(_, current)| weight > current) {
+      best = Some((encoding, weight));
+    }
+  }
+
+  best.map(|(encoding, _)| encoding)
+}
+
+/// Weight in thousandths; an explicit entry wins over `*`, absent means 0.
+fn coding_weight(accept: &str, encoding: ContentEncoding) -> u16 {
+  let mut wildcard = None;
+
+  for entry in accept.split(',') {
+    let mut parts = entry.split(';');
+    let coding = parts.next().unwrap_or_default().trim().to_ascii_lowercase();
+    let weight = parts
+      .find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name
+          .trim()
+          .eq_ignore_ascii_case("q")
+          .then(|| parse_qvalue(value))
+      })
+      .unwrap_or(Some(1000));
+    let Some(weight) = weight else {
+      continue;
+    };
+
+    let matches =
+      coding == encoding.as_str() || (encoding == ContentEncoding::Gzip && coding == "x-gzip");
+    if matches {
+      return weight;
+    }
+    if coding == "*" {
+      wildcard = Some(weight);
+    }
+  }
+
+  wildcard.unwrap_or(0)
+}
+
+/// Parses an RFC 9110 qvalue (`0`..`1` with up to three decimals).
+pub(super) fn parse_qvalue(value: &str) -> Option<u16> {
+  let value = value.trim();
+  let (whole, fraction) = value.split_once('.').unwrap_or((value, ""));
+  if fraction.len() > 3 || !fraction.bytes().all(|byte| byte.is_ascii_digit()) {
+    return None;
+  }
+
+  let thousandths = format!("{fraction:0<3}").parse::<u16>().ok()?;
+  match whole {
+    "0" => Some(thousandths),
+    "1" if thousandths == 0 => Some(1000),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderValue;
+
+  use super::*;
+
+  fn negotiate(accept: &str) -> Option<ContentEncoding> {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      ACCEPT_ENCODING,
+      HeaderValue::from_str(accept).expect("valid value"),
+    );
+    negotiate_encoding(&headers)
+  }
+
+  #[test]
+  fn no_header_means_identity() {
+    assert_eq!(negotiate_encoding(&HeaderMap::new()), None);
+    assert_eq!(negotiate("identity"), None);
+    assert_eq!(negotiate(""), None);
+  }
+
+  #[test]
+  fn equal_weights_prefer_brotli_then_zstd() {
+    assert_eq!(
+      negotiate("gzip, deflate, br, zstd"),
+      Some(ContentEncoding::Brotli)
+    );
+    assert_eq!(negotiate("gzip, zstd"), Some(ContentEncoding::Zstd));
+    assert_eq!(negotiate("gzip"), Some(ContentEncoding::Gzip));
+    assert_eq!(negotiate("x-gzip"), Some(ContentEncoding::Gzip));
+  }
+
+  #[test]
+  fn explicit_weights_win() {
+    assert_eq!(
+      negotiate("br;q=0.5, gzip;q=0.9"),
+      Some(ContentEncoding::Gzip)
+    );
+    assert_eq!(negotiate("BR ; Q=1.0, gzip"), Some(ContentEncoding::Brotli));
+  }
+
+  #[test]
+  fn zero_weight_excludes_a_coding() {
+    assert_eq!(negotiate("br;q=0, gzip"), Some(ContentEncoding::Gzip));
+    assert_eq!(negotiate("*;q=0"), None);
+    assert_eq!(
+      negotiate("*, br;q=0, zstd;q=0"),
+      Some(ContentEncoding::Gzip)
+    );
+  }
+
+  #[test]
+  fn wildcard_covers_unlisted_codings() {
+    assert_eq!(negotiate("*"), Some(ContentEncoding::Brotli));
+    assert_eq!(
+      negotiate("gzip;q=0.2, *;q=0.1"),
+      Some(ContentEncoding::Gzip)
+    );
+  }
+
+  #[test]
+  fn malformed_weights_are_ignored() {
+    assert_eq!(negotiate("br;q=2, gzip"), Some(ContentEncoding::Gzip));
+    assert_eq!(negotiate("br;q=abc"), None);
+  }
+
+  #[test]
+  fn qvalues_follow_the_grammar() {
+    assert_eq!(parse_qvalue("1"), Some(1000));
+    assert_eq!(parse_qvalue("1.000"), Some(1000));
+    assert_eq!(parse_qvalue("0.5"), Some(500));
+    assert_eq!(parse_qvalue("0.125"), Some(125));
+    assert_eq!(parse_qvalue("0"), Some(0));
+    assert_eq!(parse_qvalue("1.5"), None);
+    assert_eq!(parse_qvalue("0.1234"), None);
+  }
+}
@@ -0,0 +1,111 @@
+use axum::http::HeaderMap;
+use axum::http::header::ACCEPT_LANGUAGE;
+
+use crate::config::CalendarLanguage;
+use crate::i18n::Catalogs;
+use crate::web::encoding::parse_qvalue;
+
+/// Picks the language of a response: an explicit `lang=` first, then the best
+/// `Accept-Language` entry with a catalog, then the configured default.
+///
+/// A requested language without a catalog is ignored rather than rejected, so
+/// a subscription URL keeps working if a catalog is later removed.
+pub(crate) fn negotiate_language(
+  requested: Option<&str>,
+  headers: &HeaderMap,
+  catalogs: &Catalogs,
+  default: CalendarLanguage,
+) -> CalendarLanguage {
+  if let Some(lang) = requested.and_then(|tag| supported(tag, catalogs)) {
+    return lang;
+  }
+
+  let accept = headers
+    .get_all(ACCEPT_LANGUAGE)
+    .iter()
+    .filter_map(|value| value.to_str().ok())
+    .collect::<Vec<_>>()
+    .join(",");
+
+  // Equal weights keep the client's order
+  let mut best: Option<(CalendarLanguage, u16)> = None;
+  for entry in accept.split(',') {
+    let mut parts = entry.split(';');
+    let tag = parts.next().unwrap_or_default();
+    let weight = parts
+      .find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name
+          .trim()
+          .eq_ignore_ascii_case("q")
+          .then(|| parse_qvalue(value))
+      })
+      .unwrap_or(Some(1000));
+    let Some(weight) = weight.filter(|weight| *weight > 0) else {
+      continue;
+    };
+
+    if let Some(lang) = supported(tag, catalogs)
+      && best.is_none_or(|(_, current)| weight > current)
+    {
+      best = Some((lang, weight));
+    }
+  }
+
+  best.map_or(default, |(lang, _)| lang)
+}
+
+/// A language tag with a catalog, trying `pt-BR` before `pt`
+fn supported(tag: &str, catalogs: &Catalogs) -> Option<CalendarLanguage> {
+  let tag = tag.trim();
+  let primary = tag.split(['-', '_']).next().unwrap_or_default();
+  [tag, primary]
+    .into_iter()
+    .filter_map(CalendarLanguage::from_code)
+    .find(|lang| catalogs.contains(*lang))
+}
+
+#[cfg(test)]
+mod tests {
+  use axum::http::HeaderValue;
+
+  use super::*;
+
+  fn negotiate(requested: Option<&str>, accept: &str) -> CalendarLanguage {
+    let mut headers = HeaderMap::new();
+    headers.insert(
+      ACCEPT_LANGUAGE,
+      HeaderValue::from_str(accept).expect("valid value"),
+    );
+    negotiate_language(
+      requested,
+      &headers,
+      &Catalogs::default(),
+      CalendarLanguage::PL,
+    )
+  }
+
+  #[test]
+  fn query_parameter_wins_over_the_header() {
+    assert_eq!(negotiate(Some("uk"), "en"), CalendarLanguage::UK);
+    assert_eq!(negotiate(Some("EN-us"), "uk"), CalendarLanguage::EN);
+  }
+
+  #[test]
+  fn unknown_languages_fall_through() {
+    assert_eq!(negotiate(Some("de"), "en"), CalendarLanguage::EN);
+    assert_eq!(negotiate(Some("x y"), ""), CalendarLanguage::PL);
+    assert_eq!(negotiate(None, "de-DE, fr;q=0.8"), CalendarLanguage::PL);
+  }
+
+  #[test]
+  fn header_weights_decide_between_supported_languages() {
+    assert_eq!(
+      negotiate(None, "de-DE, uk;q=0.7, en-GB;q=0.9"),
+      CalendarLanguage::EN
+    );
+    assert_eq!(negotiate(None, "uk-UA, en"), CalendarLanguage::UK);
+    assert_eq!(negotiate(None, "en;q=0, uk;q=0.1"), CalendarLanguage::UK);
+    assert_eq!(negotiate(None, "*"), CalendarLanguage::PL);
+  }
+}
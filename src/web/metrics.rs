@@ -0,0 +1,124 @@
+use std::fmt::Write;
+
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::http::header::CONTENT_TYPE;
+use axum::response::IntoResponse;
+use serde::Deserialize;
+
+use crate::app::AppState;
+use crate::cache::CacheSnapshot;
+use crate::config::ServerSettings;
+use crate::web::calendar::token_matches;
+use crate::web::{AppError, ErrorMessage};
+
+const METRICS_CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
+#[derive(Debug, Deserialize)]
+pub(super) struct MetricsQuery {
+  token: Option<String>,
+}
+
+/// Prometheus text exposition of the cache sizes and eviction counters,
+/// behind the calendar token when one is configured
+pub(super) async fn metrics<C: ServerSettings>(
+  State(state): State<AppState<C>>,
+  Query(query): Query<MetricsQuery>,
+  headers: HeaderMap,
+) -> Result<impl IntoResponse, AppError> {
+  if let Some(expected) = state.config.calendar_token()
+    && !token_matches(&expected, query.token, &headers)
+  {
+    return Err(AppError::unauthorized(ErrorMessage::InvalidToken));
+  }
+
+  Ok((
+    [(CONTENT_TYPE, METRICS_CONTENT_TYPE)],
+    render(&state.cache_snapshots()),
+  ))
+}
+
+fn render(snapshots: &[CacheSnapshot]) -> String {
+  let mut body = String::new();
+
+  gauge(
+    &mut body,
+    "ahe_cache_entries",
+    "Entries currently held",
+    snapshots,
+    |snapshot| snapshot.entries,
+  );
+  gauge(
+    &mut body,
+    "ahe_cache_weighted_size",
+    "Current size in the unit of the capacity (bytes for ics, entries otherwise)",
+    snapshots,
+    |snapshot| snapshot.weighted_size,
+  );
+  gauge(
+    &mut body,
+    "ahe_cache_capacity",
+    "Configured upper bound",
+    snapshots,
+    |snapshot| snapshot.capacity,
+  );
+
+  let name = "ahe_cache_evictions_total";
+  let _ = writeln!(
+    body,
+    "# HELP {name} Entries removed to stay within capacity or after expiring\n# TYPE {name} counter"
+  );
+  for snapshot in snapshots {
+    for (cause, count) in [
+      ("size", snapshot.size_evictions),
+      ("expired", snapshot.expirations),
+    ] {
+      let _ = writeln!(
+        body,
+        "{name}{{cache=\"{}\",cause=\"{cause}\"}} {count}",
+        snapshot.name
+      );
+    }
+  }
+
+  body
+}
+
+fn gauge(
+  body: &mut String,
+  name: &str,
+  help: &str,
+  snapshots: &[CacheSnapshot],
+  value: impl Fn(&CacheSnapshot) -> u64,
+) {
+  let _ = writeln!(body, "# HELP {name} {help}\n# TYPE {name} gauge");
+  for snapshot in snapshots {
+    let _ = writeln!(
+      body,
+      "{name}{{cache=\"{}\"}} {}",
+      snapshot.name,
+      value(snapshot)
+    );
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::cache::EvictionCounters;
+
+  #[test]
+  fn renders_one_series_per_cache_and_cause() {
+    let counters = EvictionCounters::default();
+    let snapshots = [CacheSnapshot::new("ics", 3, 2048, 4096, &counters)];
+
+    let body = render(&snapshots);
+
+    assert!(body.contains("# TYPE ahe_cache_entries gauge\n"));
+    assert!(body.contains("ahe_cache_entries{cache=\"ics\"} 3\n"));
+    assert!(body.contains("ahe_cache_weighted_size{cache=\"ics\"} 2048\n"));
+    assert!(body.contains("ahe_cache_capacity{cache=\"ics\"} 4096\n"));
+    assert!(body.contains("ahe_cache_evictions_total{cache=\"ics\",cause=\"size\"} 0\n"));
+    assert!(body.contains("ahe_cache_evictions_total{cache=\"ics\",cause=\"expired\"} 0\n"));
+  }
+}
@@ -1,52 +1,213 @@
 mod calendar;
+mod conditional;
 mod dto;
+mod encoding;
+mod language;
+mod metrics;
 mod real_ip;
 mod routes;
 mod shared_routes;
 
+use std::time::Duration;
+
 use axum::http::StatusCode;
+use axum::http::header::RETRY_AFTER;
 use axum::response::{IntoResponse, Response};
 use tracing::error;
 
+use crate::api::ApiError;
+use crate::i18n::{ErrorTexts, RANGE_FIELDS, en};
+use crate::templates::Template;
+
 pub use routes::router;
 pub use shared_routes::shared_router;
 
-/// Body returned for any failure that did not originate from a request the
-/// caller can fix
-const INTERNAL_ERROR_BODY: &str = "internal server error";
+const ICS_CONTENT_TYPE: &str = "text/calendar; charset=utf-8";
+const JSON_CONTENT_TYPE: &str = "application/json; charset=utf-8";
+const JCAL_CONTENT_TYPE: &str = "application/calendar+json; charset=utf-8";
+const XCAL_CONTENT_TYPE: &str = "application/calendar+xml; charset=utf-8";
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct AppError {
   status: StatusCode,
-  message: String,
+  message: ErrorMessage,
+  /// `message` in the caller's language; English when unset
+  body: Option<String>,
   detail: Option<String>,
+  retry_after: Option<Duration>,
+  login_rejection: Option<LoginRejection>,
+}
+
+/// What the caller is told about a failure, worded by the translation catalog
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorMessage {
+  InvalidToken,
+  InvalidRange,
+  InvalidTimezone,
+  RangeTooLong {
+    max_days: i64,
+  },
+  InvalidCredentials,
+  AccountLocked,
+  /// WPS refused the credentials from the configuration
+  UpstreamCredentials,
+  UpstreamUnavailable,
+  UpstreamTimeout,
+  UpstreamUnexpected,
+  /// Any failure that did not originate from a request the caller can fix
+  Internal,
+}
+
+impl ErrorMessage {
+  fn text(self, texts: &ErrorTexts) -> String {
+    let text = match self {
+      Self::InvalidToken => &texts.invalid_token,
+      Self::InvalidRange => &texts.invalid_range,
+      Self::InvalidTimezone => &texts.invalid_timezone,
+      Self::RangeTooLong { max_days } => {
+        // Catalogs are checked for this template when they are loaded
+        return Template::parse(&texts.range_too_long, RANGE_FIELDS).map_or_else(
+          |_| texts.range_too_long.to_string(),
+          |template| template.render(|_| max_days.to_string()),
+        );
+      }
+      Self::InvalidCredentials => &texts.invalid_credentials,
+      Self::AccountLocked => &texts.account_locked,
+      Self::UpstreamCredentials => &texts.upstream_credentials,
+      Self::UpstreamUnavailable => &texts.upstream_unavailable,
+      Self::UpstreamTimeout => &texts.upstream_timeout,
+      Self::UpstreamUnexpected => &texts.upstream_unexpected,
+      Self::Internal => &texts.internal_error,
+    };
+    text.to_string()
+  }
+}
+
+/// WPS refused the credentials used for the request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum LoginRejection {
+  Invalid,
+  Locked,
 }
 
 impl AppError {
-  pub fn bad_request(message: impl Into<String>) -> Self {
+  #[must_use]
+  pub fn bad_request(message: ErrorMessage) -> Self {
     Self {
       status: StatusCode::BAD_REQUEST,
-      message: message.into(),
+      message,
+      body: None,
       detail: None,
+      retry_after: None,
+      login_rejection: None,
     }
   }
 
-  pub fn unauthorized(message: impl Into<String>) -> Self {
+  #[must_use]
+  pub fn unauthorized(message: ErrorMessage) -> Self {
     Self {
       status: StatusCode::UNAUTHORIZED,
-      message: message.into(),
+      message,
+      body: None,
+      detail: None,
+      retry_after: None,
+      login_rejection: None,
+    }
+  }
+
+  /// For routes where the caller supplied the WPS credentials: a rejected
+  /// login is their problem (401/403), not an upstream failure.
+  #[must_use]
+  pub fn for_caller_credentials(self) -> Self {
+    let (status, message) = match self.login_rejection {
+      Some(LoginRejection::Invalid) => (StatusCode::UNAUTHORIZED, ErrorMessage::InvalidCredentials),
+      Some(LoginRejection::Locked) => (StatusCode::FORBIDDEN, ErrorMessage::AccountLocked),
+      None => return self,
+    };
+
+    Self {
+      status,
+      message,
+      body: None,
       detail: None,
+      ..self
+    }
+  }
+
+  /// Words the message in the caller's language
+  #[must_use]
+  pub fn localized(self, texts: &ErrorTexts) -> Self {
+    Self {
+      body: Some(self.message.text(texts)),
+      ..self
+    }
+  }
+
+  /// Maps a WPS failure onto our own wording; `detail` is only logged
+  fn upstream(error: &ApiError, detail: String) -> Self {
+    let (status, message, login_rejection) = match error {
+      // Configured credentials are the operator's problem, not the subscriber's
+      ApiError::InvalidCredentials => (
+        StatusCode::BAD_GATEWAY,
+        ErrorMessage::UpstreamCredentials,
+        Some(LoginRejection::Invalid),
+      ),
+      ApiError::AccountLocked => (
+        StatusCode::BAD_GATEWAY,
+        ErrorMessage::UpstreamCredentials,
+        Some(LoginRejection::Locked),
+      ),
+      ApiError::Unavailable { .. } => (
+        StatusCode::BAD_GATEWAY,
+        ErrorMessage::UpstreamUnavailable,
+        None,
+      ),
+      ApiError::Timeout { .. } => (
+        StatusCode::GATEWAY_TIMEOUT,
+        ErrorMessage::UpstreamTimeout,
+        None,
+      ),
+      ApiError::SchemaMismatch { .. } | ApiError::UnexpectedStatus { .. } => (
+        StatusCode::BAD_GATEWAY,
+        ErrorMessage::UpstreamUnexpected,
+        None,
+      ),
+    };
+
+    Self {
+      status,
+      message,
+      body: None,
+      detail: Some(detail),
+      retry_after: error.retry_after(),
+      login_rejection,
     }
   }
 }
 
+impl From<ApiError> for AppError {
+  fn from(err: ApiError) -> Self {
+    Self::upstream(&err, err.to_string())
+  }
+}
+
 impl From<anyhow::Error> for AppError {
   fn from(err: anyhow::Error) -> Self {
-    // Errors from the WPS layer
+    // Caches wrap WPS calls in anyhow, so look through the chain for the typed cause
+    if let Some(api_error) = err
+      .chain()
+      .find_map(|cause| cause.downcast_ref::<ApiError>())
+    {
+      return Self::upstream(api_error, format!("{err:#}"));
+    }
+
     Self {
       status: StatusCode::INTERNAL_SERVER_ERROR,
-      message: INTERNAL_ERROR_BODY.to_string(),
+      message: ErrorMessage::Internal,
+      body: None,
       detail: Some(format!("{err:#}")),
+      retry_after: None,
+      login_rejection: None,
     }
   }
 }
@@ -57,7 +218,16 @@ impl IntoResponse for AppError {
       error!(status = %self.status, detail, "request failed");
     }
 
-    (self.status, self.message).into_response()
+    let body = self
+      .body
+      .unwrap_or_else(|| self.message.text(&en::EN.errors));
+    let mut response = (self.status, body).into_response();
+    if let Some(retry_after) = self.retry_after {
+      response
+        .headers_mut()
+        .insert(RETRY_AFTER, retry_after.as_secs().into());
+    }
+    response
   }
 }
 
@@ -86,7 +256,7 @@ mod tests {
     let (status, body) = render(AppError::from(upstream)).await;
 
     assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
-    assert_eq!(body, INTERNAL_ERROR_BODY);
+    assert_eq!(body, "internal server error");
     assert!(!body.contains("alice"));
     assert!(!body.contains("403"));
     assert!(!body.contains("locked"));
@@ -98,19 +268,83 @@ mod tests {
       anyhow::anyhow!("body={{\"pesel\":\"x\"}}").context("student data request failed");
     let (_, body) = render(AppError::from(upstream)).await;
 
-    assert_eq!(body, INTERNAL_ERROR_BODY);
+    assert_eq!(body, "internal server error");
     assert!(!body.contains("pesel"));
   }
 
   #[tokio::test]
-  async fn client_facing_messages_are_preserved() {
+  async fn client_facing_messages_default_to_english() {
     // These are written by this service, so they are safe to return as-is
-    let (status, body) = render(AppError::bad_request("to must be >= from")).await;
+    let (status, body) = render(AppError::bad_request(ErrorMessage::InvalidRange)).await;
     assert_eq!(status, StatusCode::BAD_REQUEST);
     assert_eq!(body, "to must be >= from");
 
-    let (status, body) = render(AppError::unauthorized("invalid calendar token")).await;
+    let (status, body) = render(AppError::unauthorized(ErrorMessage::InvalidToken)).await;
     assert_eq!(status, StatusCode::UNAUTHORIZED);
     assert_eq!(body, "invalid calendar token");
   }
+
+  #[tokio::test]
+  async fn upstream_failures_map_to_gateway_statuses() {
+    let unavailable = ApiError::Unavailable {
+      operation: "plan",
+      reason: "503 Service Unavailable".to_string(),
+      retry_after: Some(Duration::from_secs(90)),
+    };
+    let response = AppError::from(unavailable).into_response();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(response.headers()[RETRY_AFTER], "90");
+
+    let timeout = AppError::from(ApiError::Timeout { operation: "plan" });
+    let (status, body) = render(timeout).await;
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+    assert_eq!(body, "upstream timed out");
+
+    let mismatch = AppError::from(ApiError::SchemaMismatch {
+      operation: "plan",
+      detail: "missing field `DataOd` near \"alice\"".to_string(),
+    });
+    let response = mismatch.into_response();
+    assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+    assert!(response.headers().get(RETRY_AFTER).is_none());
+  }
+
+  #[tokio::test]
+  async fn typed_cause_is_found_behind_anyhow_context() {
+    let wrapped = anyhow::Error::from(ApiError::Timeout { operation: "login" })
+      .context("student context lookup failed");
+    let (status, _) = render(AppError::from(wrapped)).await;
+
+    assert_eq!(status, StatusCode::GATEWAY_TIMEOUT);
+  }
+
+  #[tokio::test]
+  async fn rejected_login_depends_on_whose_credentials_they_are() {
+    let (status, _) = render(AppError::from(ApiError::InvalidCredentials)).await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+
+    let invalid = AppError::from(ApiError::InvalidCredentials).for_caller_credentials();
+    assert_eq!(render(invalid).await.0, StatusCode::UNAUTHORIZED);
+
+    let locked = AppError::from(ApiError::AccountLocked).for_caller_credentials();
+    assert_eq!(render(locked).await.0, StatusCode::FORBIDDEN);
+
+    // Everything else is untouched
+    let timeout = AppError::from(ApiError::Timeout { operation: "plan" }).for_caller_credentials();
+    assert_eq!(render(timeout).await.0, StatusCode::GATEWAY_TIMEOUT);
+  }
+
+  #[tokio::test]
+  async fn messages_follow_the_catalog() {
+    let range = AppError::bad_request(ErrorMessage::RangeTooLong { max_days: 30 });
+    let (status, body) = render(range.localized(&crate::i18n::pl::PL.errors)).await;
+    assert_eq!(status, StatusCode::BAD_REQUEST);
+    assert_eq!(body, "zakres dat nie moze przekraczac 30 dni");
+
+    // Remapping a rejected login needs the wording again
+    let locked = AppError::from(ApiError::AccountLocked)
+      .for_caller_credentials()
+      .localized(&crate::i18n::uk::UK.errors);
+    assert_eq!(render(locked).await.1, "обліковий запис WPS заблоковано");
+  }
 }
@@ -2,7 +2,6 @@ use std::net::SocketAddr;
 
 use axum::Router;
 use axum::extract::{ConnectInfo, Query, State};
-use axum::http::header::CONTENT_TYPE;
 use axum::http::{HeaderMap, StatusCode};
 use axum::response::IntoResponse;
 use axum::routing::get;
@@ -11,24 +10,32 @@ use serde::Deserialize;
 use tracing::warn;
 
 use crate::app::AppState;
-use crate::config::Config;
-use crate::web::AppError;
-use crate::web::calendar::{CalendarQueryParams, fetch_calendar_data, render_calendar_ics};
-use crate::web::dto::CalendarJsonResponse;
+use crate::config::{CalendarLanguage, Config};
+use crate::web::calendar::{
+  CalendarQueryParams, ConvertedFormat, error_texts, render_calendar_converted,
+  render_calendar_ics, render_calendar_json, request_language,
+};
+use crate::web::conditional::feed_response;
+use crate::web::metrics::metrics;
+use crate::web::{AppError, ICS_CONTENT_TYPE, JSON_CONTENT_TYPE};
 
 #[derive(Debug, Deserialize)]
 struct CalendarQuery {
   from: Option<NaiveDate>,
   to: Option<NaiveDate>,
   token: Option<String>,
+  lang: Option<String>,
+  tz: Option<String>,
 }
 
-impl From<CalendarQuery> for CalendarQueryParams {
-  fn from(value: CalendarQuery) -> Self {
-    Self {
-      from: value.from,
-      to: value.to,
-      token: value.token,
+impl CalendarQuery {
+  fn into_params(self, lang: CalendarLanguage) -> CalendarQueryParams {
+    CalendarQueryParams {
+      from: self.from,
+      to: self.to,
+      token: self.token,
+      lang,
+      tz: self.tz,
     }
   }
 }
@@ -38,7 +45,12 @@ pub fn router(state: AppState<Config>) -> Router {
   let mut router = Router::new()
     .route("/calendar.ics", get(calendar_ics))
     .route("/calendar/me.ics", get(calendar_ics))
+    .route("/calendar.jcal.json", get(calendar_jcal))
+    .route("/calendar/me.jcal.json", get(calendar_jcal))
+    .route("/calendar.xcs", get(calendar_xcal))
+    .route("/calendar/me.xcs", get(calendar_xcal))
     .route("/healthz", get(healthz))
+    .route("/metrics", get(metrics::<Config>))
     .route("/readyz", get(readyz))
     .fallback(not_found);
 
@@ -61,10 +73,24 @@ async fn calendar_ics(
   headers: HeaderMap,
   ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> Result<impl IntoResponse, AppError> {
-  let username = state.config.username.clone();
-  let password = state.config.password.clone();
-  let ics = render_calendar_ics(state, &username, &password, query.into(), headers, addr).await?;
-  Ok(([(CONTENT_TYPE, "text/calendar; charset=utf-8")], ics))
+  let (username, password) = state.config.credentials();
+  let lang = request_language(&state.config, query.lang.as_deref(), &headers);
+  let errors = error_texts(&state.config, lang);
+  let params = query.into_params(lang);
+  let calendar = render_calendar_ics(state, &username, &password, params, &headers, addr)
+    .await
+    .map_err(|error| error.localized(&errors))?;
+  let version = calendar.version.clone();
+  Ok(
+    feed_response(
+      &headers,
+      ICS_CONTENT_TYPE,
+      &version,
+      calendar.body.clone(),
+      move |encoding| calendar.encoded(encoding),
+    )
+    .await,
+  )
 }
 
 async fn calendar_json(
@@ -73,18 +99,83 @@ async fn calendar_json(
   headers: HeaderMap,
   ConnectInfo(addr): ConnectInfo<SocketAddr>,
 ) -> Result<impl IntoResponse, AppError> {
-  let username = state.config.username.clone();
-  let password = state.config.password.clone();
-  let data = fetch_calendar_data(state, &username, &password, query.into(), headers, addr).await?;
-  let body = serde_json::to_vec(&CalendarJsonResponse::from_parts(
-    data.student_id,
-    data.from,
-    data.to,
-    data.plan,
-    data.exams,
+  let (username, password) = state.config.credentials();
+  let lang = request_language(&state.config, query.lang.as_deref(), &headers);
+  let errors = error_texts(&state.config, lang);
+  let params = query.into_params(lang);
+  let json = render_calendar_json(state, &username, &password, params, &headers, addr)
+    .await
+    .map_err(|error| error.localized(&errors))?;
+  let version = json.version.clone();
+  Ok(
+    feed_response(
+      &headers,
+      JSON_CONTENT_TYPE,
+      &version,
+      json.body.clone(),
+      move |encoding| json.compressed(encoding),
+    )
+    .await,
+  )
+}
+
+async fn calendar_jcal(
+  state: State<AppState<Config>>,
+  query: Query<CalendarQuery>,
+  headers: HeaderMap,
+  addr: ConnectInfo<SocketAddr>,
+) -> Result<impl IntoResponse, AppError> {
+  Box::pin(calendar_converted(
+    state,
+    query,
+    headers,
+    addr,
+    ConvertedFormat::Jcal,
   ))
-  .map_err(anyhow::Error::from)?;
-  Ok(([(CONTENT_TYPE, "application/json; charset=utf-8")], body))
+  .await
+}
+
+async fn calendar_xcal(
+  state: State<AppState<Config>>,
+  query: Query<CalendarQuery>,
+  headers: HeaderMap,
+  addr: ConnectInfo<SocketAddr>,
+) -> Result<impl IntoResponse, AppError> {
+  Box::pin(calendar_converted(
+    state,
+    query,
+    headers,
+    addr,
+    ConvertedFormat::Xcal,
+  ))
+  .await
+}
+
+async fn calendar_converted(
+  State(state): State<AppState<Config>>,
+  Query(query): Query<CalendarQuery>,
+  headers: HeaderMap,
+  ConnectInfo(addr): ConnectInfo<SocketAddr>,
+  format: ConvertedFormat,
+) -> Result<impl IntoResponse, AppError> {
+  let (username, password) = state.config.credentials();
+  let lang = request_language(&state.config, query.lang.as_deref(), &headers);
+  let errors = error_texts(&state.config, lang);
+  let params = query.into_params(lang);
+  let feed = render_calendar_converted(state, &username, &password, params, &headers, addr, format)
+    .await
+    .map_err(|error| error.localized(&errors))?;
+  let version = feed.version.clone();
+  Ok(
+    feed_response(
+      &headers,
+      format.content_type(),
+      &version,
+      feed.body.clone(),
+      move |encoding| feed.compressed(encoding),
+    )
+    .await,
+  )
 }
 
 async fn healthz() -> impl IntoResponse {
@@ -92,9 +183,10 @@ async fn healthz() -> impl IntoResponse {
 }
 
 async fn readyz(State(state): State<AppState<Config>>) -> impl IntoResponse {
+  let (username, password) = state.config.credentials();
   let token = match state
     .token_cache
-    .get_or_login(&state.config.username, &state.config.password, &state.api)
+    .get_or_login(&username, &password, &state.api)
     .await
   {
     Ok(token) => token,
@@ -2,18 +2,21 @@ use std::net::SocketAddr;
 
 use axum::Router;
 use axum::extract::{ConnectInfo, Query, State};
-use axum::http::header::CONTENT_TYPE;
 use axum::http::{HeaderMap, StatusCode};
-use axum::response::IntoResponse;
+use axum::response::{IntoResponse, Response};
 use axum::routing::get;
 use chrono::NaiveDate;
 use serde::Deserialize;
 
 use crate::app::AppState;
-use crate::config::SharedConfig;
-use crate::web::AppError;
-use crate::web::calendar::{CalendarQueryParams, fetch_calendar_data, render_calendar_ics};
-use crate::web::dto::CalendarJsonResponse;
+use crate::config::{ServerSettings, SharedConfig};
+use crate::web::calendar::{
+  CalendarQueryParams, ConvertedFormat, error_texts, render_calendar_converted,
+  render_calendar_ics, render_calendar_json, request_language,
+};
+use crate::web::conditional::feed_response;
+use crate::web::metrics::{MetricsQuery, metrics};
+use crate::web::{AppError, ICS_CONTENT_TYPE, JSON_CONTENT_TYPE};
 
 /// Query params for the shared binary
 #[derive(Debug, Deserialize)]
@@ -23,6 +26,8 @@ struct CalendarQuery {
   from: Option<NaiveDate>,
   to: Option<NaiveDate>,
   token: Option<String>,
+  lang: Option<String>,
+  tz: Option<String>,
 }
 
 /// Builds the HTTP router for the shared binary
@@ -30,7 +35,12 @@ pub fn shared_router(state: AppState<SharedConfig>) -> Router {
   let mut router = Router::new()
     .route("/calendar.ics", get(calendar_ics))
     .route("/calendar/me.ics", get(calendar_ics))
+    .route("/calendar.jcal.json", get(calendar_jcal))
+    .route("/calendar/me.jcal.json", get(calendar_jcal))
+    .route("/calendar.xcs", get(calendar_xcal))
+    .route("/calendar/me.xcs", get(calendar_xcal))
     .route("/healthz", get(healthz))
+    .route("/metrics", get(shared_metrics))
     .fallback(not_found);
 
   if state.config.json_enabled {
@@ -54,13 +64,30 @@ async fn calendar_ics(
 ) -> Result<impl IntoResponse, AppError> {
   let username = query.username.clone();
   let password = query.password.clone();
+  let lang = request_language(&state.config, query.lang.as_deref(), &headers);
   let params = CalendarQueryParams {
     from: query.from,
     to: query.to,
     token: query.token,
+    lang,
+    tz: query.tz,
   };
-  let ics = render_calendar_ics(state, &username, &password, params, headers, addr).await?;
-  Ok(([(CONTENT_TYPE, "text/calendar; charset=utf-8")], ics))
+  let errors = error_texts(&state.config, lang);
+  // The subscriber sent these credentials, so a rejected login is on them
+  let calendar = render_calendar_ics(state, &username, &password, params, &headers, addr)
+    .await
+    .map_err(|error| error.for_caller_credentials().localized(&errors))?;
+  let version = calendar.version.clone();
+  Ok(
+    feed_response(
+      &headers,
+      ICS_CONTENT_TYPE,
+      &version,
+      calendar.body.clone(),
+      move |encoding| calendar.encoded(encoding),
+    )
+    .await,
+  )
 }
 
 async fn calendar_json(
@@ -71,21 +98,111 @@ async fn calendar_json(
 ) -> Result<impl IntoResponse, AppError> {
   let username = query.username.clone();
   let password = query.password.clone();
+  let lang = request_language(&state.config, query.lang.as_deref(), &headers);
   let params = CalendarQueryParams {
     from: query.from,
     to: query.to,
     token: query.token,
+    lang,
+    tz: query.tz,
   };
-  let data = fetch_calendar_data(state, &username, &password, params, headers, addr).await?;
-  let body = serde_json::to_vec(&CalendarJsonResponse::from_parts(
-    data.student_id,
-    data.from,
-    data.to,
-    data.plan,
-    data.exams,
+  let errors = error_texts(&state.config, lang);
+  // The subscriber sent these credentials, so a rejected login is on them
+  let json = render_calendar_json(state, &username, &password, params, &headers, addr)
+    .await
+    .map_err(|error| error.for_caller_credentials().localized(&errors))?;
+  let version = json.version.clone();
+  Ok(
+    feed_response(
+      &headers,
+      JSON_CONTENT_TYPE,
+      &version,
+      json.body.clone(),
+      move |encoding| json.compressed(encoding),
+    )
+    .await,
+  )
+}
+
+async fn calendar_jcal(
+  state: State<AppState<SharedConfig>>,
+  query: Query<CalendarQuery>,
+  headers: HeaderMap,
+  addr: ConnectInfo<SocketAddr>,
+) -> Result<impl IntoResponse, AppError> {
+  Box::pin(calendar_converted(
+    state,
+    query,
+    headers,
+    addr,
+    ConvertedFormat::Jcal,
   ))
-  .map_err(anyhow::Error::from)?;
-  Ok(([(CONTENT_TYPE, "application/json; charset=utf-8")], body))
+  .await
+}
+
+async fn calendar_xcal(
+  state: State<AppState<SharedConfig>>,
+  query: Query<CalendarQuery>,
+  headers: HeaderMap,
+  addr: ConnectInfo<SocketAddr>,
+) -> Result<impl IntoResponse, AppError> {
+  Box::pin(calendar_converted(
+    state,
+    query,
+    headers,
+    addr,
+    ConvertedFormat::Xcal,
+  ))
+  .await
+}
+
+async fn calendar_converted(
+  State(state): State<AppState<SharedConfig>>,
+  Query(query): Query<CalendarQuery>,
+  headers: HeaderMap,
+  ConnectInfo(addr): ConnectInfo<SocketAddr>,
+  format: ConvertedFormat,
+) -> Result<impl IntoResponse, AppError> {
+  let username = query.username.clone();
+  let password = query.password.clone();
+  let lang = request_language(&state.config, query.lang.as_deref(), &headers);
+  let params = CalendarQueryParams {
+    from: query.from,
+    to: query.to,
+    token: query.token,
+    lang,
+    tz: query.tz,
+  };
+  let errors = error_texts(&state.config, lang);
+  // The subscriber sent these credentials, so a rejected login is on them
+  let feed = render_calendar_converted(state, &username, &password, params, &headers, addr, format)
+    .await
+    .map_err(|error| error.for_caller_credentials().localized(&errors))?;
+  let version = feed.version.clone();
+  Ok(
+    feed_response(
+      &headers,
+      format.content_type(),
+      &version,
+      feed.body.clone(),
+      move |encoding| feed.compressed(encoding),
+    )
+    .await,
+  )
+}
+
+/// The shared instance is public, so cache figures are only served to holders of
+/// the calendar token and are not exposed at all without one
+async fn shared_metrics(
+  State(state): State<AppState<SharedConfig>>,
+  query: Query<MetricsQuery>,
+  headers: HeaderMap,
+) -> Result<Response, AppError> {
+  if state.config.calendar_token().is_none() {
+    return Ok(not_found().await.into_response());
+  }
+
+  Ok(metrics(State(state), query, headers).await?.into_response())
 }
 
 /// Shared instance healthz